@@ -0,0 +1,171 @@
+// ============================================================================
+//  Zero-Copy NumPy Views (Python buffer protocol)
+// ============================================================================
+//
+// GaussianSplat (64 bytes) / Surfel (48 bytes) の配列をコピーせずに NumPy へ渡す。
+// ビューはフィールドごとのストライド付き配列 (例: positions = shape (N, 3), strides (64, 4))。
+// ビューが生きている間は元の Vec を再確保できないため、ExportGuard で参照数を数え、
+// 変更系のメソッドは BufferError を返す (bytearray と同じ方針)。
+
+use std::ffi::{c_int, CStr};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use pyo3::exceptions::PyBufferError;
+use pyo3::ffi;
+use pyo3::prelude::*;
+
+const FORMAT_F32: &CStr = c"f";
+
+// Number of live views into one array
+#[derive(Default)]
+pub struct ExportGuard(Arc<AtomicUsize>);
+
+impl ExportGuard {
+    // Fails while any NumPy view still points into the array
+    pub fn check(&self, what: &str) -> PyResult<()> {
+        match self.0.load(Ordering::Acquire) {
+            0 => Ok(()),
+            n => Err(PyBufferError::new_err(format!(
+                "Cannot modify {} while {} NumPy view(s) reference it. Copy them with np.array(...) or delete them first.",
+                what, n,
+            ))),
+        }
+    }
+}
+
+// Read-only strided f32 view; `_owner` keeps the SplatManager (and thus the Vec) alive
+#[pyclass(frozen)]
+pub struct ArrayView {
+    _owner: PyObject,
+    exports: Arc<AtomicUsize>,
+    addr: usize,
+    ndim: c_int,
+    shape: [ffi::Py_ssize_t; 2],
+    strides: [ffi::Py_ssize_t; 2],
+}
+
+impl Drop for ArrayView {
+    fn drop(&mut self) {
+        self.exports.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+#[pymethods]
+impl ArrayView {
+    unsafe fn __getbuffer__(slf: Bound<'_, Self>, view: *mut ffi::Py_buffer, flags: c_int) -> PyResult<()> {
+        if view.is_null() {
+            return Err(PyBufferError::new_err("View is null"));
+        }
+        if flags & ffi::PyBUF_WRITABLE == ffi::PyBUF_WRITABLE {
+            return Err(PyBufferError::new_err("Splat / surfel views are read-only"));
+        }
+        if flags & ffi::PyBUF_STRIDES != ffi::PyBUF_STRIDES {
+            return Err(PyBufferError::new_err("Splat / surfel views are strided; request PyBUF_STRIDES"));
+        }
+
+        let this = slf.get();
+        let items = this.shape[..this.ndim as usize].iter().product::<ffi::Py_ssize_t>();
+        (*view).buf = this.addr as *mut std::os::raw::c_void;
+        (*view).len = items * 4;
+        (*view).readonly = 1;
+        (*view).itemsize = 4;
+        (*view).format = if flags & ffi::PyBUF_FORMAT == ffi::PyBUF_FORMAT {
+            FORMAT_F32.as_ptr() as *mut _
+        } else {
+            std::ptr::null_mut()
+        };
+        (*view).ndim = this.ndim;
+        (*view).shape = this.shape.as_ptr() as *mut _;
+        (*view).strides = this.strides.as_ptr() as *mut _;
+        (*view).suboffsets = std::ptr::null_mut();
+        (*view).internal = std::ptr::null_mut();
+        (*view).obj = slf.into_any().into_ptr();
+        Ok(())
+    }
+
+    unsafe fn __releasebuffer__(&self, _view: *mut ffi::Py_buffer) {}
+}
+
+// NumPy view of `cols` f32 fields at byte `offset` inside each element of `data`.
+// cols == 1 gives a 1-D array of shape (N,), otherwise (N, cols).
+pub fn field_view<'py, T: bytemuck::Pod>(
+    owner: &Bound<'py, PyAny>,
+    data: &[T],
+    guard: &ExportGuard,
+    offset: usize,
+    cols: usize,
+) -> PyResult<Bound<'py, PyAny>> {
+    let py = owner.py();
+    guard.0.fetch_add(1, Ordering::AcqRel);
+    let view = ArrayView {
+        _owner: owner.clone().unbind(),
+        exports: guard.0.clone(),
+        addr: data.as_ptr() as usize + offset,
+        ndim: if cols == 1 { 1 } else { 2 },
+        shape: [data.len() as ffi::Py_ssize_t, cols as ffi::Py_ssize_t],
+        strides: [std::mem::size_of::<T>() as ffi::Py_ssize_t, 4],
+    };
+    let view = Bound::new(py, view)?;
+    py.import("numpy")?.call_method1("asarray", (view,))
+}
+
+// Flattened float32 copy of any array-like, checked to hold `rows` x `cols` values
+pub fn read_f32(obj: &Bound<'_, PyAny>, name: &str, rows: Option<usize>, cols: usize) -> PyResult<Vec<f32>> {
+    let py = obj.py();
+    let arr = py.import("numpy")?.call_method1("ascontiguousarray", (obj, "float32"))?;
+    let values = pyo3::buffer::PyBuffer::<f32>::get(&arr)?.to_vec(py)?;
+    let ok = match rows {
+        Some(rows) => values.len() == rows * cols,
+        None => values.len() % cols == 0,
+    };
+    if !ok {
+        let expected = rows.map_or("N".to_string(), |r| r.to_string());
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "{} must have shape ({}, {}), got {} values", name, expected, cols, values.len(),
+        )));
+    }
+    Ok(values)
+}
+
+// Flattened int64 copy of a (rows,) or (rows, K) array-like and its column count (1 for (rows,))
+pub fn read_index_rows(obj: &Bound<'_, PyAny>, name: &str, rows: usize) -> PyResult<(Vec<i64>, usize)> {
+    let py = obj.py();
+    let arr = py.import("numpy")?.call_method1("ascontiguousarray", (obj, "int64"))?;
+    let shape: Vec<usize> = arr.getattr("shape")?.extract()?;
+    let cols = match shape[..] {
+        [r] if r == rows => 1,
+        [r, c] if r == rows => c,
+        _ => return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "{} must have shape ({},) or ({}, K), got {:?}", name, rows, rows, shape,
+        ))),
+    };
+    Ok((pyo3::buffer::PyBuffer::<i64>::get(&arr)?.to_vec(py)?, cols))
+}
+
+// Boolean copy of a (rows,) array-like (e.g. a keep-mask)
+pub fn read_mask(obj: &Bound<'_, PyAny>, name: &str, rows: usize) -> PyResult<Vec<bool>> {
+    let py = obj.py();
+    let arr = py.import("numpy")?.call_method1("ascontiguousarray", (obj, "bool"))?;
+    let shape: Vec<usize> = arr.getattr("shape")?.extract()?;
+    if shape[..] != [rows] {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "{} must have shape ({},), got {:?}", name, rows, shape,
+        )));
+    }
+    // PyBuffer は bool を扱えないので 1 バイトの整数として読む
+    let bytes = pyo3::buffer::PyBuffer::<u8>::get(&arr.call_method1("view", ("uint8",))?)?.to_vec(py)?;
+    Ok(bytes.into_iter().map(|b| b != 0).collect())
+}
+
+// Owned (len,) bool NumPy array
+pub fn mask_to_numpy<'py>(py: Python<'py>, mask: &[bool]) -> PyResult<Bound<'py, PyAny>> {
+    let bytes: Vec<u8> = mask.iter().map(|&keep| keep as u8).collect();
+    py.import("numpy")?.call_method1("frombuffer", (pyo3::types::PyByteArray::new(py, &bytes), "bool"))
+}
+
+// Owned (rows, cols) NumPy array of `dtype` holding a copy of `values`
+pub fn to_numpy<'py, T: bytemuck::Pod>(py: Python<'py>, values: &[T], dtype: &str, rows: usize, cols: usize) -> PyResult<Bound<'py, PyAny>> {
+    let bytes = pyo3::types::PyByteArray::new(py, bytemuck::cast_slice(values));
+    py.import("numpy")?.call_method1("frombuffer", (bytes, dtype))?.call_method1("reshape", ((rows, cols),))
+}
@@ -0,0 +1,126 @@
+use crate::cpu::CpuPool;
+use crate::error::{GsError, GsResult};
+use crate::gpu::GpuContext;
+
+// ============================================================================
+//  Compute Backend Selection
+// ============================================================================
+//
+// auto: 初回にアダプタを確認し、GPU が無い・デバイスロストした場合は CPU 実装で計算し直す。
+// gpu / cpu: 指定した実装のみを使う (gpu はエラーをそのまま返す)
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Backend {
+    #[default]
+    Auto,
+    Gpu,
+    Cpu,
+}
+
+impl Backend {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::Gpu => "gpu",
+            Self::Cpu => "cpu",
+        }
+    }
+}
+
+impl std::str::FromStr for Backend {
+    type Err = GsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "auto" => Ok(Self::Auto),
+            "gpu" => Ok(Self::Gpu),
+            "cpu" => Ok(Self::Cpu),
+            _ => Err(GsError::invalid(format!("Unknown backend '{}' (expected 'auto', 'gpu' or 'cpu')", s))),
+        }
+    }
+}
+
+// Selected backend, the lazily created GpuContext, the CPU thread pool and the fallback state
+#[derive(Default)]
+pub struct Dispatcher {
+    backend: Backend,
+    gpu: Option<GpuContext>,
+    cpu: CpuPool,
+    // auto でアダプタが無いと分かったら、以降は GPU の初期化を試さない
+    adapter_unavailable: bool,
+    last: Option<Backend>,
+}
+
+impl Dispatcher {
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+
+    pub fn set_backend(&mut self, backend: Backend) {
+        self.backend = backend;
+        self.adapter_unavailable = false;
+    }
+
+    // Backend that produced the latest result (Gpu or Cpu), None before the first computation
+    pub fn last_backend(&self) -> Option<Backend> {
+        self.last
+    }
+
+    pub fn cpu(&self) -> &CpuPool {
+        &self.cpu
+    }
+
+    pub fn cpu_mut(&mut self) -> &mut CpuPool {
+        &mut self.cpu
+    }
+
+    pub fn record(&mut self, backend: Backend) {
+        self.last = Some(backend);
+    }
+
+    // Runs `gpu` or `cpu` according to the backend; auto retries on the CPU after GPU-side failures
+    // (no adapter, device lost, or an input that does not fit in one GPU buffer binding)
+    pub fn run<T>(
+        &mut self,
+        gpu: impl FnOnce(&mut GpuContext) -> GsResult<T>,
+        cpu: impl FnOnce(&CpuPool) -> GsResult<T>,
+    ) -> GsResult<T> {
+        let try_gpu = match self.backend {
+            Backend::Auto => !self.adapter_unavailable,
+            Backend::Gpu => true,
+            Backend::Cpu => false,
+        };
+        if try_gpu {
+            match ensure_gpu(&mut self.gpu).and_then(gpu) {
+                Err(GsError::GpuAdapterUnavailable(_)) if self.backend == Backend::Auto => self.adapter_unavailable = true,
+                // 失われたデバイスは次の呼び出しで ensure_gpu が作り直す
+                Err(GsError::DeviceLost(_)) if self.backend == Backend::Auto => {}
+                // チャンク分割できない入力 (knn の全点など) がバインディング上限を超えた場合。CPU にはこの上限が無い
+                Err(GsError::BufferTooLarge(_)) if self.backend == Backend::Auto => {}
+                result => {
+                    if result.is_ok() { self.record(Backend::Gpu); }
+                    return result;
+                }
+            }
+        }
+        let result = cpu(&self.cpu)?;
+        self.record(Backend::Cpu);
+        Ok(result)
+    }
+}
+
+// GpuContext は初回の GPU 呼び出しで生成し、以降は使い回す (src/gpu.rs)
+// デバイスロスト後は次の呼び出しで作り直す
+fn ensure_gpu(slot: &mut Option<GpuContext>) -> GsResult<&mut GpuContext> {
+    if slot.as_ref().is_none_or(GpuContext::is_lost) {
+        // ドライバが壊れている環境では wgpu のインスタンス生成自体が panic することがある
+        let context = std::panic::catch_unwind(|| pollster::block_on(GpuContext::new())).map_err(|panic| {
+            let reason = panic.downcast_ref::<&str>().copied()
+                .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+                .unwrap_or("unknown panic");
+            GsError::GpuAdapterUnavailable(format!("GPU initialization panicked: {}", reason))
+        })?;
+        *slot = Some(context?);
+    }
+    Ok(slot.as_mut().unwrap())
+}
@@ -0,0 +1,111 @@
+use rayon::prelude::*;
+
+use crate::error::{GsError, GsResult};
+use crate::sh::{self, ShCoeffs, SH_C0};
+use crate::{GaussianSplat, Surfel};
+
+// ============================================================================
+//  CPU Backend (rayon)
+// ============================================================================
+//
+// 点群を BLOCK 個ずつに分けてスレッドに配る。各ブロックは成分ごとの配列 (SoA) に展開してから
+// 分岐の少ない f32 のループで計算するため、コンパイラが SIMD 命令に自動ベクトル化できる。
+// 計算内容は shader.wgsl の compute_main と同じ。
+
+// Splats per work item: large enough to amortize scheduling, small enough to stay in L1/L2
+const BLOCK: usize = 1024;
+
+// Thread pool of the CPU backend. None = rayon's global pool (one thread per core)
+#[derive(Default)]
+pub struct CpuPool {
+    pool: Option<rayon::ThreadPool>,
+}
+
+impl CpuPool {
+    // 設定したスレッド数 (None は全コア)
+    pub fn threads(&self) -> Option<usize> {
+        self.pool.as_ref().map(rayon::ThreadPool::current_num_threads)
+    }
+
+    pub fn set_threads(&mut self, threads: Option<usize>) -> GsResult<()> {
+        self.pool = match threads {
+            None => None,
+            Some(0) => return Err(GsError::invalid("cpu_threads must be >= 1 (None uses all cores)")),
+            Some(n) => Some(
+                rayon::ThreadPoolBuilder::new()
+                    .num_threads(n)
+                    .thread_name(|i| format!("gs-cpu-{}", i))
+                    .build()
+                    .map_err(|e| GsError::invalid(format!("Cannot start {} CPU threads: {}", n, e)))?,
+            ),
+        };
+        Ok(())
+    }
+
+    // Runs `f` inside this pool so that the rayon iterators in it use the configured threads
+    pub fn install<R: Send>(&self, f: impl FnOnce() -> R + Send) -> R {
+        match &self.pool {
+            Some(pool) => pool.install(f),
+            None => f(),
+        }
+    }
+}
+
+// Surfels computed on the CPU (same math as compute_main in shader.wgsl)
+pub fn geometry(pool: &CpuPool, splats: &[GaussianSplat], sh: &ShCoeffs, view_dir: Option<[f32; 3]>) -> Vec<Surfel> {
+    let mut surfels = vec![Surfel::default(); splats.len()];
+    pool.install(|| {
+        surfels.par_chunks_mut(BLOCK).zip(splats.par_chunks(BLOCK)).enumerate().for_each(|(b, (out, block))| {
+            geometry_block(block, b * BLOCK, sh, view_dir, out);
+        });
+    });
+    surfels
+}
+
+// One block of at most BLOCK splats; `first` is the index of block[0] (for the SH rest coefficients)
+fn geometry_block(block: &[GaussianSplat], first: usize, sh: &ShCoeffs, view_dir: Option<[f32; 3]>, out: &mut [Surfel]) {
+    let n = block.len();
+    let mut q = [[0.0f32; BLOCK]; 4];
+    let mut s = [[0.0f32; BLOCK]; 3];
+    for (i, splat) in block.iter().enumerate() {
+        for (qk, v) in q.iter_mut().zip(splat.rot) { qk[i] = v; }
+        for (sk, v) in s.iter_mut().zip(splat.scale) { sk[i] = v.abs(); }
+    }
+
+    // 法線: 正規化したクォータニオン (x, y, z, w) の回転行列のうち、最小スケール軸の列
+    let mut normal = [[0.0f32; BLOCK]; 3];
+    for i in 0..n {
+        let inv = 1.0 / (q[0][i] * q[0][i] + q[1][i] * q[1][i] + q[2][i] * q[2][i] + q[3][i] * q[3][i]).sqrt();
+        let (x, y, z, w) = (q[0][i] * inv, q[1][i] * inv, q[2][i] * inv, q[3][i] * inv);
+        let col_x = [1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y + w * z), 2.0 * (x * z - w * y)];
+        let col_y = [2.0 * (x * y - w * z), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z + w * x)];
+        let col_z = [2.0 * (x * z + w * y), 2.0 * (y * z - w * x), 1.0 - 2.0 * (x * x + y * y)];
+        let use_x = s[0][i] < s[1][i] && s[0][i] < s[2][i];
+        let use_y = !use_x && s[1][i] < s[2][i];
+        let col = if use_x { col_x } else if use_y { col_y } else { col_z };
+        let inv_len = 1.0 / (col[0] * col[0] + col[1] * col[1] + col[2] * col[2]).sqrt();
+        for k in 0..3 { normal[k][i] = col[k] * inv_len; }
+    }
+
+    // 半径と曲率 (surfel_shape_cpu と同じ)
+    let mut radius = [0.0f32; BLOCK];
+    let mut curvature = [0.0f32; BLOCK];
+    for i in 0..n {
+        radius[i] = s[0][i].max(s[1][i]).max(s[2][i]);
+        let var = [s[0][i] * s[0][i], s[1][i] * s[1][i], s[2][i] * s[2][i]];
+        let total = var[0] + var[1] + var[2];
+        curvature[i] = if total > 0.0 { var[0].min(var[1]).min(var[2]) / total } else { 0.0 };
+    }
+
+    for (i, (splat, surfel)) in block.iter().zip(out.iter_mut()).enumerate() {
+        let color = match view_dir {
+            Some(d) => sh::eval_sh_cpu(splat.sh_dc, sh.splat(first + i), sh.degree, d),
+            None => splat.sh_dc.map(|c| (SH_C0 * c + 0.5).clamp(0.0, 1.0)),
+        };
+        *surfel = Surfel {
+            pos: splat.pos, radius: radius[i],
+            color, opacity: splat.opacity,
+            normal: [normal[0][i], normal[1][i], normal[2][i]], curvature: curvature[i],
+        };
+    }
+}
@@ -0,0 +1,192 @@
+use std::fmt;
+
+// ============================================================================
+//  Errors
+// ============================================================================
+//
+// ライブラリ全体で共通のエラー型。Python では GsError を基底とする例外クラス
+// (組み込みの ValueError / OSError / RuntimeError も継承)、WASM では name 付きの
+// JS Error に変換し、「GPU が無い」と「ファイルが壊れている」を呼び出し側で区別できるようにする。
+
+#[derive(Debug)]
+pub enum GsError {
+    Io(std::io::Error),
+    // Malformed PLY. `property` / `line` point at the offending header entry when known.
+    PlyFormat { message: String, property: Option<String>, line: Option<usize> },
+    // Malformed file in one of the other formats (PCD, SPZ, .ksplat, .splat)
+    Format { format: &'static str, message: String },
+    GpuAdapterUnavailable(String),
+    DeviceLost(String),
+    // Input does not fit in the device limits or overflows the output size
+    BufferTooLarge(String),
+    InvalidParameter(String),
+}
+
+pub type GsResult<T> = Result<T, GsError>;
+
+impl GsError {
+    pub fn ply(message: impl Into<String>) -> Self {
+        Self::PlyFormat { message: message.into(), property: None, line: None }
+    }
+
+    pub fn ply_at_line(line: usize, message: impl Into<String>) -> Self {
+        Self::PlyFormat { message: message.into(), property: None, line: Some(line) }
+    }
+
+    pub fn ply_property(property: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::PlyFormat { message: message.into(), property: Some(property.into()), line: None }
+    }
+
+    pub fn format(format: &'static str, message: impl Into<String>) -> Self {
+        Self::Format { format, message: message.into() }
+    }
+
+    pub fn invalid(message: impl Into<String>) -> Self {
+        Self::InvalidParameter(message.into())
+    }
+
+    // Exception class name (Python) / Error.name (JS)
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Io(_) => "GsIOError",
+            Self::PlyFormat { .. } => "PlyFormatError",
+            Self::Format { .. } => "FormatError",
+            Self::GpuAdapterUnavailable(_) => "GpuUnavailableError",
+            Self::DeviceLost(_) => "DeviceLostError",
+            Self::BufferTooLarge(_) => "BufferTooLargeError",
+            Self::InvalidParameter(_) => "InvalidParameterError",
+        }
+    }
+}
+
+impl fmt::Display for GsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{}", e),
+            Self::PlyFormat { message, line: Some(line), .. } => write!(f, "{} (line {})", message, line),
+            Self::PlyFormat { message, .. } => write!(f, "{}", message),
+            Self::Format { format, message } => write!(f, "Invalid {}: {}", format, message),
+            Self::GpuAdapterUnavailable(m) | Self::DeviceLost(m) | Self::BufferTooLarge(m) | Self::InvalidParameter(m) => {
+                write!(f, "{}", m)
+            }
+        }
+    }
+}
+
+impl std::error::Error for GsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for GsError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+// ----------------------------------------------------------------------------
+//  Python exceptions
+// ----------------------------------------------------------------------------
+
+#[cfg(feature = "python")]
+mod python {
+    use pyo3::prelude::*;
+    use pyo3::sync::GILOnceCell;
+    use pyo3::types::PyModule;
+
+    use super::GsError;
+
+    // 多重継承が必要なので Python 側で定義する
+    const EXCEPTIONS: &std::ffi::CStr = cr#"
+class GsError(Exception):
+    """Base class of the errors raised by gs_slam_core"""
+
+class GsIOError(GsError, OSError):
+    """Reading or writing a file failed"""
+
+class FormatError(GsError, ValueError):
+    """The file is not valid in the expected format"""
+
+class PlyFormatError(FormatError):
+    """Malformed PLY; property / line point at the offending header entry when known"""
+    def __init__(self, message, property=None, line=None):
+        super().__init__(message)
+        self.property = property
+        self.line = line
+
+class GpuUnavailableError(GsError, RuntimeError):
+    """No usable GPU adapter or device; the *_cpu methods still work"""
+
+class DeviceLostError(GsError, RuntimeError):
+    """The GPU device was lost during a computation"""
+
+class BufferTooLargeError(GsError, MemoryError):
+    """The input does not fit in the device limits"""
+
+class InvalidParameterError(GsError, ValueError):
+    """An argument is out of range or inconsistent with the current state"""
+"#;
+
+    const NAMES: [&str; 8] = [
+        "GsError", "GsIOError", "FormatError", "PlyFormatError",
+        "GpuUnavailableError", "DeviceLostError", "BufferTooLargeError", "InvalidParameterError",
+    ];
+
+    static MODULE: GILOnceCell<Py<PyModule>> = GILOnceCell::new();
+
+    // Defines the exception classes and exposes them on the extension module
+    pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+        let py = m.py();
+        let module = MODULE.get_or_try_init(py, || {
+            PyModule::from_code(py, EXCEPTIONS, c"gs_slam_core/errors.py", c"gs_slam_core.errors").map(Bound::unbind)
+        })?;
+        for name in NAMES {
+            m.add(name, module.bind(py).getattr(name)?)?;
+        }
+        Ok(())
+    }
+
+    fn to_pyerr(py: Python<'_>, e: GsError) -> PyResult<PyErr> {
+        let Some(module) = MODULE.get(py) else {
+            return Ok(pyo3::exceptions::PyRuntimeError::new_err(e.to_string()));
+        };
+        let class = module.bind(py).getattr(e.name())?;
+        let message = e.to_string();
+        let value = match e {
+            GsError::PlyFormat { property, line, .. } => class.call1((message, property, line))?,
+            GsError::Io(io) => match io.raw_os_error() {
+                Some(errno) => class.call1((errno, message))?,
+                None => class.call1((message,))?,
+            },
+            _ => class.call1((message,))?,
+        };
+        Ok(PyErr::from_value(value))
+    }
+
+    impl From<GsError> for PyErr {
+        fn from(e: GsError) -> Self {
+            Python::with_gil(|py| to_pyerr(py, e).unwrap_or_else(|err| err))
+        }
+    }
+}
+
+#[cfg(feature = "python")]
+pub use python::register as register_exceptions;
+
+// ----------------------------------------------------------------------------
+//  JS errors
+// ----------------------------------------------------------------------------
+
+// wasm-bindgen の戻り値 Err(JsValue) は例外 (async 関数では Promise の reject) になる
+#[cfg(feature = "wasm")]
+impl From<GsError> for wasm_bindgen::JsValue {
+    fn from(e: GsError) -> Self {
+        let error = js_sys::Error::new(&e.to_string());
+        error.set_name(e.name());
+        error.into()
+    }
+}
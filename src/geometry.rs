@@ -0,0 +1,86 @@
+use std::borrow::Cow;
+
+// Geometry kernel (compute_main in shader.wgsl): splat -> surfel (color + normal)
+pub struct GeometryPipeline {
+    pub pipeline: wgpu::ComputePipeline,
+    pub bind_group_layout: wgpu::BindGroupLayout,
+}
+
+impl GeometryPipeline {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Geometry Shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("shader.wgsl"))),
+        });
+
+        let storage = |binding, read_only| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Storage { read_only }, has_dynamic_offset: false, min_binding_size: None },
+            count: None,
+        };
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Geometry Bind Group Layout"),
+            entries: &[
+                // Input Splats
+                storage(0, true),
+                // Output Surfels
+                storage(1, false),
+                // Higher-order SH
+                storage(2, true),
+                // Params
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Geometry Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Geometry Compute Pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: Some("compute_main"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        Self { pipeline, bind_group_layout }
+    }
+
+    pub fn bind_group(
+        &self,
+        device: &wgpu::Device,
+        input: wgpu::BindingResource,
+        output: wgpu::BindingResource,
+        sh: wgpu::BindingResource,
+        params: wgpu::BindingResource,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Geometry Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: input },
+                wgpu::BindGroupEntry { binding: 1, resource: output },
+                wgpu::BindGroupEntry { binding: 2, resource: sh },
+                wgpu::BindGroupEntry { binding: 3, resource: params },
+            ],
+        })
+    }
+
+    pub fn dispatch(&self, encoder: &mut wgpu::CommandEncoder, bind_group: &wgpu::BindGroup, count: u32) {
+        let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+        cpass.set_pipeline(&self.pipeline);
+        cpass.set_bind_group(0, bind_group, &[]);
+        cpass.dispatch_workgroups(count.div_ceil(64), 1, 1);
+    }
+}
@@ -0,0 +1,543 @@
+use std::mem::size_of;
+use std::sync::{Arc, OnceLock};
+
+use crate::error::{GsError, GsResult};
+use crate::geometry::GeometryPipeline;
+use crate::knn::{GridParams, KnnPipeline, KnnQuery, Neighbors};
+use crate::normals::{Observers, OrientParams, OrientPipeline};
+use crate::sh::ShCoeffs;
+use crate::sr::{SrParams, SuperResolutionPipeline};
+use crate::voxel::{Merged, VoxelBuffers, VoxelParams, VoxelPipeline, VoxelTable};
+use crate::{buffer_range, max_chunk_len, sh_buffer_contents, GaussianSplat, GeometryParams, Surfel};
+
+// ============================================================================
+//  Persistent Buffers
+// ============================================================================
+
+// Buffer that is only reallocated when a larger size is requested
+struct GrowableBuffer {
+    label: &'static str,
+    usage: wgpu::BufferUsages,
+    buffer: Option<wgpu::Buffer>,
+}
+
+impl GrowableBuffer {
+    fn new(label: &'static str, usage: wgpu::BufferUsages) -> Self {
+        Self { label, usage, buffer: None }
+    }
+
+    fn ensure(&mut self, device: &wgpu::Device, size: u64) -> &wgpu::Buffer {
+        if self.buffer.as_ref().is_none_or(|b| b.size() < size) {
+            self.buffer = Some(device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(self.label),
+                size,
+                usage: self.usage,
+                mapped_at_creation: false,
+            }));
+        }
+        self.buffer.as_ref().unwrap()
+    }
+
+    // First `size` bytes of the buffer allocated by ensure()
+    fn binding(&self, size: u64) -> wgpu::BindingResource<'_> {
+        buffer_range(self.buffer.as_ref().unwrap(), 0, size)
+    }
+}
+
+// Buffers that readback() can copy from
+#[derive(Copy, Clone)]
+enum Readback {
+    Surfels,
+    Offsets,
+    Neighbors,
+    VoxelCount,
+    Voxels,
+}
+
+// ============================================================================
+//  GPU Context (Headless)
+// ============================================================================
+
+// Device, queue and compiled pipelines, created once and reused across calls
+pub struct GpuContext {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    geometry: GeometryPipeline,
+    sr: SuperResolutionPipeline,
+    knn: KnnPipeline,
+    orient: OrientPipeline,
+    voxel: VoxelPipeline,
+
+    splat_buffer: GrowableBuffer,
+    sh_buffer: GrowableBuffer,
+    surfel_buffer: GrowableBuffer,
+    offset_buffer: GrowableBuffer,
+    staging_buffer: GrowableBuffer,
+    geometry_params: wgpu::Buffer,
+    sr_params: wgpu::Buffer,
+
+    // 近傍探索 (knn.rs)
+    point_buffer: GrowableBuffer,
+    cell_buffer: GrowableBuffer,
+    sorted_buffer: GrowableBuffer,
+    query_buffer: GrowableBuffer,
+    neighbor_buffer: GrowableBuffer,
+    knn_params: wgpu::Buffer,
+
+    // 法線の向き (normals.rs)
+    camera_buffer: GrowableBuffer,
+    observer_buffer: GrowableBuffer,
+    orient_params: wgpu::Buffer,
+
+    // ボクセルダウンサンプリング (voxel.rs)
+    voxel_attr_buffer: GrowableBuffer,
+    voxel_table_buffer: GrowableBuffer,
+    voxel_of_buffer: GrowableBuffer,
+    leader_buffer: GrowableBuffer,
+    voxel_cursor_buffer: GrowableBuffer,
+    member_buffer: GrowableBuffer,
+    voxel_out_buffer: GrowableBuffer,
+    voxel_params: wgpu::Buffer,
+
+    // デバイスロスト時に wgpu のコールバックが理由を書き込む
+    lost: Arc<OnceLock<String>>,
+}
+
+impl GpuContext {
+    pub async fn new() -> GsResult<Self> {
+        // 1. Instance
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            flags: wgpu::InstanceFlags::default(),
+            dx12_shader_compiler: wgpu::Dx12Compiler::Fxc,
+            gles_minor_version: wgpu::Gles3MinorVersion::Automatic,
+        });
+
+        // 2. Adapter
+        let adapter = instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            compatible_surface: None,
+            force_fallback_adapter: false,
+        }).await.ok_or_else(|| GsError::GpuAdapterUnavailable("No GPU adapter found".to_string()))?;
+
+        // 3. Device
+        // WSL2対策: required_limitsを下げておく (バッファサイズ上限のみアダプタの値まで引き上げ、超過分はチャンク分割)
+        let adapter_limits = adapter.limits();
+        let required_limits = wgpu::Limits {
+            max_storage_buffer_binding_size: adapter_limits.max_storage_buffer_binding_size,
+            max_buffer_size: adapter_limits.max_buffer_size,
+            ..wgpu::Limits::downlevel_defaults()
+        };
+        let (device, queue) = adapter.request_device(&wgpu::DeviceDescriptor {
+            label: Some("GS-SLAM Device"),
+            required_features: wgpu::Features::empty(),
+            required_limits,
+            memory_hints: wgpu::MemoryHints::default(),
+        }, None).await.map_err(|e| GsError::GpuAdapterUnavailable(format!("Failed to create device: {}", e)))?;
+
+        let lost = Arc::new(OnceLock::new());
+        let lost_reason = lost.clone();
+        device.set_device_lost_callback(move |reason, message| {
+            let _ = lost_reason.set(format!("{:?}: {}", reason, message));
+        });
+
+        // 4. Pipelines (compiled once)
+        let geometry = GeometryPipeline::new(&device);
+        let sr = SuperResolutionPipeline::new(&device);
+        let knn = KnnPipeline::new(&device);
+        let orient = OrientPipeline::new(&device);
+        let voxel = VoxelPipeline::new(&device);
+
+        let uniform = |label, size: usize| device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: size as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let geometry_params = uniform("Geometry Param Buffer", size_of::<GeometryParams>());
+        let sr_params = uniform("SR Param Buffer", size_of::<SrParams>());
+        let knn_params = uniform("KNN Param Buffer", size_of::<GridParams>());
+        let orient_params = uniform("Orient Param Buffer", size_of::<OrientParams>());
+        let voxel_params = uniform("Voxel Param Buffer", size_of::<VoxelParams>());
+
+        let storage_in = wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST;
+        Ok(Self {
+            device,
+            queue,
+            geometry,
+            sr,
+            knn,
+            orient,
+            voxel,
+            splat_buffer: GrowableBuffer::new("Input Buffer", storage_in),
+            sh_buffer: GrowableBuffer::new("SH Buffer", storage_in),
+            // orient_normals は既存の Surfel を書き込んでその場で更新する
+            surfel_buffer: GrowableBuffer::new("Output Buffer", wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST),
+            offset_buffer: GrowableBuffer::new("SR Offset Buffer", wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC),
+            staging_buffer: GrowableBuffer::new("Staging Buffer", wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST),
+            geometry_params,
+            sr_params,
+            point_buffer: GrowableBuffer::new("KNN Point Buffer", storage_in),
+            cell_buffer: GrowableBuffer::new("KNN Cell Buffer", wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST),
+            sorted_buffer: GrowableBuffer::new("KNN Sorted Buffer", wgpu::BufferUsages::STORAGE),
+            query_buffer: GrowableBuffer::new("KNN Query Buffer", storage_in),
+            neighbor_buffer: GrowableBuffer::new("KNN Neighbor Buffer", wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC),
+            knn_params,
+            camera_buffer: GrowableBuffer::new("Camera Buffer", storage_in),
+            observer_buffer: GrowableBuffer::new("Observer Buffer", storage_in),
+            orient_params,
+            voxel_attr_buffer: GrowableBuffer::new("Voxel Attribute Buffer", storage_in),
+            voxel_table_buffer: GrowableBuffer::new("Voxel Hash Table Buffer", wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST),
+            voxel_of_buffer: GrowableBuffer::new("Voxel Index Buffer", wgpu::BufferUsages::STORAGE),
+            leader_buffer: GrowableBuffer::new("Voxel Leader Buffer", wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC),
+            voxel_cursor_buffer: GrowableBuffer::new("Voxel Cursor Buffer", wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST),
+            member_buffer: GrowableBuffer::new("Voxel Member Buffer", wgpu::BufferUsages::STORAGE),
+            voxel_out_buffer: GrowableBuffer::new("Voxel Output Buffer", wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC),
+            voxel_params,
+            lost,
+        })
+    }
+
+    // 失われたデバイスは作り直すしかない (ensure_gpu が再生成する)
+    pub fn is_lost(&self) -> bool {
+        self.lost.get().is_some()
+    }
+
+    fn check_lost(&self) -> GsResult<()> {
+        match self.lost.get() {
+            Some(reason) => Err(GsError::DeviceLost(format!("GPU device lost ({})", reason))),
+            None => Ok(()),
+        }
+    }
+
+    fn upload_splats(&mut self, splats: &[GaussianSplat]) -> u64 {
+        let bytes: &[u8] = bytemuck::cast_slice(splats);
+        let buffer = self.splat_buffer.ensure(&self.device, bytes.len() as u64);
+        self.queue.write_buffer(buffer, 0, bytes);
+        bytes.len() as u64
+    }
+
+    // デバイス上限を超える点群はチャンクに分割して順に処理し、結果を連結する
+    pub async fn compute_geometry(&mut self, splats: &[GaussianSplat], sh: &ShCoeffs, params: GeometryParams) -> GsResult<Vec<Surfel>> {
+        self.check_lost()?;
+        self.queue.write_buffer(&self.geometry_params, 0, bytemuck::bytes_of(&params));
+
+        let sh_stride = sh.stride();
+        let chunk_len = max_chunk_len(
+            &self.device.limits(),
+            &[size_of::<GaussianSplat>() as u64, size_of::<Surfel>() as u64, (sh_stride * 4) as u64],
+            1,
+        )?;
+
+        let mut result = Vec::with_capacity(splats.len());
+        for (i, chunk) in splats.chunks(chunk_len).enumerate() {
+            let input_size = self.upload_splats(chunk);
+
+            let sh_bytes = match sh_stride {
+                0 => sh_buffer_contents(sh),
+                _ => bytemuck::cast_slice(&sh.coeffs[i * chunk_len * sh_stride..][..chunk.len() * sh_stride]),
+            };
+            let sh_buffer = self.sh_buffer.ensure(&self.device, sh_bytes.len() as u64);
+            self.queue.write_buffer(sh_buffer, 0, sh_bytes);
+
+            let output_size = (chunk.len() * size_of::<Surfel>()) as u64;
+            let output_buffer = self.surfel_buffer.ensure(&self.device, output_size);
+
+            let bind_group = self.geometry.bind_group(
+                &self.device,
+                buffer_range(self.splat_buffer.buffer.as_ref().unwrap(), 0, input_size),
+                buffer_range(output_buffer, 0, output_size),
+                buffer_range(self.sh_buffer.buffer.as_ref().unwrap(), 0, sh_bytes.len() as u64),
+                self.geometry_params.as_entire_binding(),
+            );
+
+            let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Geometry Encoder") });
+            self.geometry.dispatch(&mut encoder, &bind_group, chunk.len() as u32);
+            self.queue.submit(Some(encoder.finish()));
+
+            result.extend(self.readback::<Surfel>(Readback::Surfels, 0, output_size).await?);
+        }
+        Ok(result)
+    }
+
+    // 無効なスプラットの Surfel は GPU 上で詰めて除くので、結果は splats x factor 以下
+    pub async fn super_resolution(&mut self, splats: &[GaussianSplat], params: SrParams) -> GsResult<Vec<Surfel>> {
+        self.check_lost()?;
+        let factor = params.factor();
+        let total = splats.len().checked_mul(factor as usize)
+            .ok_or_else(|| GsError::BufferTooLarge(format!("Super resolution output overflows: {} splats x factor {}", splats.len(), factor)))?;
+        let surfel_bytes = (size_of::<Surfel>() as u64).checked_mul(factor as u64)
+            .ok_or_else(|| GsError::BufferTooLarge(format!("Super resolution factor {} is too large", factor)))?;
+        let chunk_len = max_chunk_len(&self.device.limits(), &[size_of::<GaussianSplat>() as u64, surfel_bytes], factor as u64)?;
+
+        self.queue.write_buffer(&self.sr_params, 0, bytemuck::bytes_of(&params));
+
+        let mut result = Vec::with_capacity(total);
+        for chunk in splats.chunks(chunk_len) {
+            let input_size = self.upload_splats(chunk);
+
+            let splat_count = chunk.len() as u32;
+            let output_size = (splat_count * factor) as u64 * size_of::<Surfel>() as u64;
+            let output_buffer = self.surfel_buffer.ensure(&self.device, output_size);
+            let offset_buffer = self.offset_buffer.ensure(&self.device, crate::scan::buffer_len(splat_count + 1) * 4);
+
+            let bind_group = self.sr.bind_group(
+                &self.device,
+                buffer_range(self.splat_buffer.buffer.as_ref().unwrap(), 0, input_size),
+                buffer_range(output_buffer, 0, output_size),
+                self.sr_params.as_entire_binding(),
+                buffer_range(offset_buffer, 0, (splat_count as u64 + 1) * 4),
+            );
+
+            let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("SR Encoder") });
+            self.sr.dispatch(&self.device, &mut encoder, &bind_group, offset_buffer, splat_count, factor);
+            self.queue.submit(Some(encoder.finish()));
+
+            // offsets の末尾が書き込まれた Surfel の数
+            let written = self.readback::<u32>(Readback::Offsets, splat_count as u64 * 4, 4).await?[0];
+            if written > 0 {
+                result.extend(self.readback::<Surfel>(Readback::Surfels, 0, written as u64 * size_of::<Surfel>() as u64).await?);
+            }
+        }
+        Ok(result)
+    }
+
+    // ハッシュグリッドは一度に作る (点群全体が 1 つのバインディングに収まる必要がある)。クエリはチャンクに分割する
+    pub async fn knn(&mut self, points: &[[f32; 3]], queries: &[[f32; 3]], query: KnnQuery) -> GsResult<Neighbors> {
+        self.check_lost()?;
+        let width = query.k().min(points.len());
+        if width == 0 || queries.is_empty() {
+            return Ok(crate::knn::search_empty(&query, queries.len()));
+        }
+        let limits = self.device.limits();
+        let index_len = max_chunk_len(&limits, &[16], 1)?;
+        if points.len() > index_len {
+            return Err(GsError::BufferTooLarge(format!(
+                "Neighbor index over {} points exceeds the device limits ({} points per dispatch)", points.len(), index_len,
+            )));
+        }
+        let chunk_len = max_chunk_len(&limits, &[16, width as u64 * 8], 1)?;
+
+        let params = GridParams::new(points, &query, width);
+        self.queue.write_buffer(&self.knn_params, 0, bytemuck::bytes_of(&params));
+
+        // 1. Build the grid
+        let point_data: Vec<[f32; 4]> = points.iter().map(|p| [p[0], p[1], p[2], 0.0]).collect();
+        let point_bytes: &[u8] = bytemuck::cast_slice(&point_data);
+        let point_buffer = self.point_buffer.ensure(&self.device, point_bytes.len() as u64);
+        self.queue.write_buffer(point_buffer, 0, point_bytes);
+        let table_len = params.table_len();
+        let cell_buffer = self.cell_buffer.ensure(&self.device, crate::scan::buffer_len(table_len + 1) * 4);
+        let sorted_size = points.len() as u64 * 16;
+        let sorted_buffer = self.sorted_buffer.ensure(&self.device, sorted_size);
+
+        let point_size = point_bytes.len() as u64;
+        let cell_size = (table_len as u64 + 1) * 4;
+        let point_buffer = self.point_buffer.buffer.as_ref().unwrap();
+        let build = self.knn.build_bind_group(
+            &self.device,
+            buffer_range(point_buffer, 0, point_size),
+            buffer_range(cell_buffer, 0, cell_size),
+            buffer_range(sorted_buffer, 0, sorted_size),
+            self.knn_params.as_entire_binding(),
+        );
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("KNN Build Encoder") });
+        encoder.clear_buffer(cell_buffer, 0, Some(cell_size));
+        self.knn.build(&self.device, &mut encoder, &build, cell_buffer, points.len() as u32, table_len);
+        self.queue.submit(Some(encoder.finish()));
+
+        // 2. Query per chunk
+        let mut pairs = Vec::with_capacity(queries.len() * width);
+        for chunk in queries.chunks(chunk_len) {
+            let query_data: Vec<[f32; 4]> = chunk.iter().map(|p| [p[0], p[1], p[2], 0.0]).collect();
+            let query_bytes: &[u8] = bytemuck::cast_slice(&query_data);
+            let query_buffer = self.query_buffer.ensure(&self.device, query_bytes.len() as u64);
+            self.queue.write_buffer(query_buffer, 0, query_bytes);
+            let output_size = (chunk.len() * width * 8) as u64;
+            self.neighbor_buffer.ensure(&self.device, output_size);
+
+            let bind_group = self.knn.query_bind_group(
+                &self.device,
+                buffer_range(self.cell_buffer.buffer.as_ref().unwrap(), 0, cell_size),
+                buffer_range(self.sorted_buffer.buffer.as_ref().unwrap(), 0, sorted_size),
+                self.knn_params.as_entire_binding(),
+                buffer_range(self.query_buffer.buffer.as_ref().unwrap(), 0, query_bytes.len() as u64),
+                buffer_range(self.neighbor_buffer.buffer.as_ref().unwrap(), 0, output_size),
+            );
+            let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("KNN Query Encoder") });
+            self.knn.query(&mut encoder, &bind_group, chunk.len() as u32);
+            self.queue.submit(Some(encoder.finish()));
+
+            pairs.extend(self.readback::<[u32; 2]>(Readback::Neighbors, 0, output_size).await?);
+        }
+        Ok(Neighbors::from_gpu(query.k(), width, &pairs))
+    }
+
+    // カメラ位置は一度に渡す (1 つのバインディングに収まる必要がある)。Surfel と観測キーフレームはチャンクに分割する
+    pub async fn orient_normals(&mut self, surfels: &[Surfel], cameras: &[[f32; 3]], observers: &Observers) -> GsResult<Vec<Surfel>> {
+        self.check_lost()?;
+        if surfels.is_empty() || cameras.is_empty() {
+            return Ok(surfels.to_vec());
+        }
+        let limits = self.device.limits();
+        let camera_len = max_chunk_len(&limits, &[16], 1)?;
+        if cameras.len() > camera_len {
+            return Err(GsError::BufferTooLarge(format!(
+                "{} camera positions exceed the device limits ({} per binding)", cameras.len(), camera_len,
+            )));
+        }
+        let width = observers.width;
+        let chunk_len = max_chunk_len(&limits, &[size_of::<Surfel>() as u64, width as u64 * 4], 1)?;
+
+        let params = OrientParams { camera_count: cameras.len() as u32, observer_width: width as u32, _pad: [0; 2] };
+        self.queue.write_buffer(&self.orient_params, 0, bytemuck::bytes_of(&params));
+        let camera_data: Vec<[f32; 4]> = cameras.iter().map(|c| [c[0], c[1], c[2], 0.0]).collect();
+        let camera_bytes: &[u8] = bytemuck::cast_slice(&camera_data);
+        let camera_buffer = self.camera_buffer.ensure(&self.device, camera_bytes.len() as u64);
+        self.queue.write_buffer(camera_buffer, 0, camera_bytes);
+
+        let mut result = Vec::with_capacity(surfels.len());
+        for (i, chunk) in surfels.chunks(chunk_len).enumerate() {
+            let surfel_bytes: &[u8] = bytemuck::cast_slice(chunk);
+            let surfel_buffer = self.surfel_buffer.ensure(&self.device, surfel_bytes.len() as u64);
+            self.queue.write_buffer(surfel_buffer, 0, surfel_bytes);
+
+            // 観測情報なし (width 0) でもバインディングは空にできないので 1 要素置く
+            let observer_bytes: &[u8] = match width {
+                0 => bytemuck::bytes_of(&crate::knn::NONE),
+                _ => bytemuck::cast_slice(&observers.indices[i * chunk_len * width..][..chunk.len() * width]),
+            };
+            let observer_buffer = self.observer_buffer.ensure(&self.device, observer_bytes.len() as u64);
+            self.queue.write_buffer(observer_buffer, 0, observer_bytes);
+
+            let bind_group = self.orient.bind_group(
+                &self.device,
+                buffer_range(self.surfel_buffer.buffer.as_ref().unwrap(), 0, surfel_bytes.len() as u64),
+                buffer_range(self.camera_buffer.buffer.as_ref().unwrap(), 0, camera_bytes.len() as u64),
+                buffer_range(self.observer_buffer.buffer.as_ref().unwrap(), 0, observer_bytes.len() as u64),
+                self.orient_params.as_entire_binding(),
+            );
+            let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Orient Encoder") });
+            self.orient.dispatch(&mut encoder, &bind_group, chunk.len() as u32);
+            self.queue.submit(Some(encoder.finish()));
+
+            result.extend(self.readback::<Surfel>(Readback::Surfels, 0, surfel_bytes.len() as u64).await?);
+        }
+        Ok(result)
+    }
+
+    // デバイス上限を超える点群はチャンクごとにまとめる。(結果, チャンクの先頭インデックス) を返し、
+    // 複数のチャンクは Merged::combine で CPU でまとめ直す
+    pub async fn voxel_merge(&mut self, table: &VoxelTable, inv_size: f32) -> GsResult<Vec<(Merged, u32)>> {
+        self.check_lost()?;
+        let width = table.width;
+        // ハッシュ表は点数の 2 倍から 4 倍 (2 のべき乗) で、数え上げ + scan の作業領域 + 最小インデックスなので 1 点あたり最大 36 バイト
+        let chunk_len = max_chunk_len(&self.device.limits(), &[(width as u64 + 1) * 4, 36, 4], 1)?;
+        let mut parts = Vec::new();
+        for (i, rows) in table.values.chunks(chunk_len * width).enumerate() {
+            let merged = self.voxel_merge_chunk(table, rows, inv_size).await?;
+            parts.push((merged, (i * chunk_len) as u32));
+        }
+        Ok(parts)
+    }
+
+    // Bindings of the voxel buffers (allocated by voxel_merge_chunk) for [attrs, table, per-point] byte sizes
+    fn voxel_buffers(&self, [attr_size, table_size, point_size]: [u64; 3]) -> VoxelBuffers<'_> {
+        let scanned_size = point_size + 4;
+        VoxelBuffers {
+            attrs: self.voxel_attr_buffer.binding(attr_size),
+            table: self.voxel_table_buffer.binding(table_size),
+            voxel_of: self.voxel_of_buffer.binding(point_size),
+            params: self.voxel_params.as_entire_binding(),
+            leaders: self.leader_buffer.binding(scanned_size),
+            cursor: self.voxel_cursor_buffer.binding(scanned_size),
+            members: self.member_buffer.binding(point_size),
+        }
+    }
+
+    async fn voxel_merge_chunk(&mut self, table: &VoxelTable, rows: &[f32], inv_size: f32) -> GsResult<Merged> {
+        let width = table.width;
+        let count = rows.len() / width;
+        let params = VoxelParams::new(table, count, inv_size);
+        self.queue.write_buffer(&self.voxel_params, 0, bytemuck::bytes_of(&params));
+
+        let attr_bytes: &[u8] = bytemuck::cast_slice(rows);
+        let attr_buffer = self.voxel_attr_buffer.ensure(&self.device, attr_bytes.len() as u64);
+        self.queue.write_buffer(attr_buffer, 0, attr_bytes);
+        let table_len = params.table_len();
+        let table_size = params.table_size();
+        let scan_size = crate::scan::buffer_len(count as u32 + 1) * 4;
+        let point_size = count as u64 * 4;
+        self.voxel_table_buffer.ensure(&self.device, table_size);
+        self.voxel_of_buffer.ensure(&self.device, point_size);
+        self.leader_buffer.ensure(&self.device, scan_size);
+        self.voxel_cursor_buffer.ensure(&self.device, scan_size);
+        self.member_buffer.ensure(&self.device, point_size);
+
+        let sizes = [attr_bytes.len() as u64, table_size, point_size];
+        let leader_buffer = self.leader_buffer.buffer.as_ref().unwrap();
+        let cursor_buffer = self.voxel_cursor_buffer.buffer.as_ref().unwrap();
+        let buffers = self.voxel_buffers(sizes);
+
+        // 1. Group by voxel
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Voxel Group Encoder") });
+        let table_buffer = self.voxel_table_buffer.buffer.as_ref().unwrap();
+        encoder.clear_buffer(table_buffer, 0, Some(table_size));
+        encoder.clear_buffer(leader_buffer, 0, Some(scan_size));
+        encoder.clear_buffer(cursor_buffer, 0, Some(scan_size));
+        self.voxel.group(&self.device, &mut encoder, &buffers, table_buffer, leader_buffer, cursor_buffer, count as u32, table_len);
+        self.queue.submit(Some(encoder.finish()));
+        let voxel_count = self.readback::<u32>(Readback::VoxelCount, count as u64 * 4, 4).await?[0];
+
+        // 2. Reduce every voxel
+        let output_size = voxel_count as u64 * (width as u64 + 1) * 4;
+        self.voxel_out_buffer.ensure(&self.device, output_size);
+        let buffers = self.voxel_buffers(sizes);
+        let output = self.voxel_out_buffer.binding(output_size);
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Voxel Reduce Encoder") });
+        self.voxel.reduce(&self.device, &mut encoder, &buffers, output, voxel_count);
+        self.queue.submit(Some(encoder.finish()));
+
+        let words = self.readback::<u32>(Readback::Voxels, 0, output_size).await?;
+        Ok(Merged::from_gpu(width, &words))
+    }
+
+    // Copies `size` bytes at `offset` of the surfel / offset / neighbor / voxel count / voxel output buffer to the host
+    async fn readback<T: bytemuck::Pod>(&mut self, source: Readback, offset: u64, size: u64) -> GsResult<Vec<T>> {
+        let source = match source {
+            Readback::Surfels => &self.surfel_buffer,
+            Readback::Offsets => &self.offset_buffer,
+            Readback::Neighbors => &self.neighbor_buffer,
+            Readback::VoxelCount => &self.leader_buffer,
+            Readback::Voxels => &self.voxel_out_buffer,
+        };
+        let staging_buffer = self.staging_buffer.ensure(&self.device, size);
+
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        encoder.copy_buffer_to_buffer(source.buffer.as_ref().unwrap(), offset, staging_buffer, 0, size);
+        self.queue.submit(Some(encoder.finish()));
+
+        let buffer_slice = staging_buffer.slice(..size);
+        let (sender, receiver) = futures_intrusive::channel::shared::oneshot_channel();
+        buffer_slice.map_async(wgpu::MapMode::Read, move |v| { let _ = sender.send(v); });
+        self.device.poll(wgpu::Maintain::Wait);
+
+        match receiver.receive().await {
+            Some(Ok(())) => {
+                let data = buffer_slice.get_mapped_range();
+                let result: Vec<T> = bytemuck::cast_slice(&data).to_vec();
+                drop(data);
+                staging_buffer.unmap();
+                Ok(result)
+            }
+            // マップ失敗はデバイスロストとして扱う (理由が分かればそれを返す)
+            other => {
+                self.check_lost()?;
+                Err(GsError::DeviceLost(match other {
+                    Some(Err(e)) => format!("Failed to read back results: {}", e),
+                    _ => "Failed to read back results: map callback was dropped".to_string(),
+                }))
+            }
+        }
+    }
+}
@@ -0,0 +1,472 @@
+use std::borrow::Cow;
+
+use rayon::prelude::*;
+
+use crate::cpu::CpuPool;
+use crate::error::{GsError, GsResult};
+use crate::scan::ScanPipeline;
+
+// ============================================================================
+//  Nearest Neighbor Search
+// ============================================================================
+//
+// GPU: 一様ハッシュグリッド (knn.wgsl)。点をセルのハッシュ順に並べ (counting sort + prefix sum)、
+//      クエリごとに近いセルから順に走査する。
+// CPU: KD-tree (中央値分割)。
+// どちらも厳密な近傍を返し、同距離の点はインデックスの小さい順に並べるので結果は一致する。
+
+// Missing neighbor (fewer than k points, or fewer than k within the radius)
+pub const NONE: u32 = u32::MAX;
+
+// Points per KD-tree leaf
+const LEAF_SIZE: usize = 16;
+
+// Point set that an index is built over
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum PointSet {
+    // GaussianSplat::pos
+    #[default]
+    Splats,
+    // Surfel::pos (compute_geometry / compute_super_resolution の結果)
+    Surfels,
+}
+
+impl std::str::FromStr for PointSet {
+    type Err = GsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "splats" => Ok(Self::Splats),
+            "surfels" => Ok(Self::Surfels),
+            _ => Err(GsError::invalid(format!("Unknown point set '{}' (expected 'splats' or 'surfels')", s))),
+        }
+    }
+}
+
+// The k nearest points of every query, optionally limited to a radius
+#[derive(Copy, Clone, Debug)]
+pub struct KnnQuery {
+    k: usize,
+    radius: Option<f32>,
+}
+
+impl KnnQuery {
+    pub fn nearest(k: usize) -> GsResult<Self> {
+        if k < 1 {
+            return Err(GsError::invalid("k must be >= 1"));
+        }
+        Ok(Self { k, radius: None })
+    }
+
+    // 半径 radius 以内の点のうち近い順に最大 max_neighbors 個
+    pub fn within(radius: f32, max_neighbors: usize) -> GsResult<Self> {
+        if !(radius > 0.0 && radius.is_finite()) {
+            return Err(GsError::invalid(format!("radius must be a positive number, got {}", radius)));
+        }
+        if max_neighbors < 1 {
+            return Err(GsError::invalid("max_neighbors must be >= 1"));
+        }
+        Ok(Self { k: max_neighbors, radius: Some(radius) })
+    }
+
+    pub fn k(&self) -> usize {
+        self.k
+    }
+
+    // Squared search radius (f32::MAX for plain kNN)
+    fn max_dist2(&self) -> f32 {
+        self.radius.map_or(f32::MAX, |r| r * r)
+    }
+}
+
+// Neighbors of every query, k per row sorted by distance. Missing entries are NONE / infinity
+pub struct Neighbors {
+    pub k: usize,
+    pub indices: Vec<u32>,
+    pub distances: Vec<f32>,
+}
+
+impl Neighbors {
+    // Rows of `width` (<= k) (index, squared distance) pairs, padded to k columns
+    fn from_squared(k: usize, width: usize, pairs: impl ExactSizeIterator<Item = (u32, f32)>) -> Self {
+        let rows = pairs.len() / width.max(1);
+        let mut result = Self { k, indices: vec![NONE; rows * k], distances: vec![f32::INFINITY; rows * k] };
+        for (n, (i, d2)) in pairs.enumerate().filter(|&(_, (i, _))| i != NONE) {
+            let slot = n / width * k + n % width;
+            result.indices[slot] = i;
+            result.distances[slot] = d2.sqrt();
+        }
+        result
+    }
+
+    pub fn row(&self, query: usize) -> (&[u32], &[f32]) {
+        (&self.indices[query * self.k..][..self.k], &self.distances[query * self.k..][..self.k])
+    }
+
+    // GPU output: (index, squared distance bits) pairs, `width` per query
+    pub fn from_gpu(k: usize, width: usize, pairs: &[[u32; 2]]) -> Self {
+        Self::from_squared(k, width, pairs.iter().map(|&[i, d2]| (i, f32::from_bits(d2))))
+    }
+}
+
+// (d2, index) の辞書順 (closer in knn.wgsl)
+fn closer(d2: f32, idx: u32, other_d2: f32, other_idx: u32) -> bool {
+    d2 < other_d2 || (d2 == other_d2 && idx < other_idx)
+}
+
+fn squared_distance(a: [f32; 3], b: [f32; 3]) -> f32 {
+    let d = [a[0] - b[0], a[1] - b[1], a[2] - b[2]];
+    d[0] * d[0] + d[1] * d[1] + d[2] * d[2]
+}
+
+// ============================================================================
+//  GPU Hash Grid
+// ============================================================================
+
+// Uniform of the hash grid kernels (Params in knn.wgsl)
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GridParams {
+    // origin(xyz), cell_size(w) -> 16 bytes
+    origin: [f32; 3],
+    cell_size: f32,
+    // dims(xyz), table_mask(w) -> 16 bytes
+    dims: [i32; 3],
+    table_mask: u32,
+    // k, max_dist2 -> 16 bytes
+    k: u32,
+    max_dist2: f32,
+    _pad: [u32; 2],
+}
+
+impl GridParams {
+    // グリッドは外れ値を除いた範囲 (trimmed_bounds) だけを覆う。範囲外の点は端のセルに寄せるので、
+    // 遠く離れた 1 点でセルが粗くなり全点が数セルに集まる (探索が O(N^2) になる) ことはない。
+    // セルの一辺: 半径探索は半径 (隣接セルまでで済む)、kNN は 1 セルあたり k 点前後になる大きさ。
+    // 面状の点群では体積からの推定が 0 になるので、最大辺の正方形に並んでいるとした推定も使う
+    pub fn new(points: &[[f32; 3]], query: &KnnQuery, k: usize) -> Self {
+        let (min, max) = trimmed_bounds(points);
+        let extent = [0, 1, 2].map(|a| (max[a] - min[a]).max(0.0));
+        let longest = extent[0].max(extent[1]).max(extent[2]);
+        let cell_size = match query.radius {
+            Some(r) => r,
+            None => {
+                let per_point = k as f32 / points.len() as f32;
+                (extent[0] * extent[1] * extent[2] * per_point).cbrt().max(longest * per_point.sqrt())
+            }
+        };
+        // セル座標は各軸 2^20 個まで
+        let cell_size = if cell_size > 0.0 && cell_size.is_finite() { cell_size.max(longest / 1048576.0) } else { 1.0 };
+        let table = points.len().next_power_of_two().max(64) as u32;
+        Self {
+            origin: min,
+            cell_size,
+            dims: extent.map(|e| (e / cell_size).floor() as i32 + 1),
+            table_mask: table - 1,
+            k: k as u32,
+            max_dist2: query.max_dist2(),
+            _pad: [0; 2],
+        }
+    }
+
+    pub fn table_len(&self) -> u32 {
+        self.table_mask + 1
+    }
+}
+
+// Points sampled per axis to estimate the grid bounds
+const BOUNDS_SAMPLE: usize = 1 << 16;
+// 各軸の両端から除く割合 (1 / BOUNDS_TRIM ずつ)
+const BOUNDS_TRIM: usize = 100;
+
+// Per-axis 1% / 99% quantiles of (a strided sample of) the points
+fn trimmed_bounds(points: &[[f32; 3]]) -> ([f32; 3], [f32; 3]) {
+    let step = (points.len() / BOUNDS_SAMPLE).max(1);
+    let mut min = [0.0; 3];
+    let mut max = [0.0; 3];
+    for a in 0..3 {
+        let mut values: Vec<f32> = points.iter().step_by(step).map(|p| p[a]).filter(|v| v.is_finite()).collect();
+        if values.is_empty() { continue; }
+        values.sort_unstable_by(f32::total_cmp);
+        let trim = values.len() / BOUNDS_TRIM;
+        min[a] = values[trim];
+        max[a] = values[values.len() - 1 - trim];
+    }
+    (min, max)
+}
+
+// count_cells → prefix sum (scan.rs) → scatter_points でグリッドを作り、query_neighbors で探索する
+pub struct KnnPipeline {
+    count: wgpu::ComputePipeline,
+    scatter: wgpu::ComputePipeline,
+    query: wgpu::ComputePipeline,
+    scan: ScanPipeline,
+    build_layout: wgpu::BindGroupLayout,
+    query_layout: wgpu::BindGroupLayout,
+}
+
+impl KnnPipeline {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("KNN Shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("knn.wgsl"))),
+        });
+
+        let storage = |binding, read_only| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Storage { read_only }, has_dynamic_offset: false, min_binding_size: None },
+            count: None,
+        };
+        let uniform = wgpu::BindGroupLayoutEntry {
+            binding: 3,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None },
+            count: None,
+        };
+
+        let build_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("KNN Build Bind Group Layout"),
+            entries: &[
+                // Points (xyz + pad)
+                storage(0, true),
+                // Cells (count → start → end per hash)
+                storage(1, false),
+                // Points sorted by cell hash (xyz + original index)
+                storage(2, false),
+                // Params
+                uniform,
+            ],
+        });
+        // downlevel の上限 (ストレージバッファ 4 個) に収めるため、探索は並べ替えた点だけを読む
+        let query_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("KNN Query Bind Group Layout"),
+            entries: &[
+                storage(1, false),
+                storage(2, false),
+                uniform,
+                // Queries (xyz + pad)
+                storage(4, true),
+                // Output (index, squared distance bits), k per query
+                storage(5, false),
+            ],
+        });
+
+        let pipeline = |label, layout: &wgpu::BindGroupLayout, entry_point| {
+            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("KNN Pipeline Layout"),
+                bind_group_layouts: &[layout],
+                push_constant_ranges: &[],
+            });
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point: Some(entry_point),
+                compilation_options: Default::default(),
+                cache: None,
+            })
+        };
+
+        Self {
+            count: pipeline("KNN Count Pipeline", &build_layout, "count_cells"),
+            scatter: pipeline("KNN Scatter Pipeline", &build_layout, "scatter_points"),
+            query: pipeline("KNN Query Pipeline", &query_layout, "query_neighbors"),
+            scan: ScanPipeline::new(device),
+            build_layout,
+            query_layout,
+        }
+    }
+
+    pub fn build_bind_group(
+        &self,
+        device: &wgpu::Device,
+        points: wgpu::BindingResource,
+        cells: wgpu::BindingResource,
+        sorted: wgpu::BindingResource,
+        params: wgpu::BindingResource,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("KNN Build Bind Group"),
+            layout: &self.build_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: points },
+                wgpu::BindGroupEntry { binding: 1, resource: cells },
+                wgpu::BindGroupEntry { binding: 2, resource: sorted },
+                wgpu::BindGroupEntry { binding: 3, resource: params },
+            ],
+        })
+    }
+
+    pub fn query_bind_group(
+        &self,
+        device: &wgpu::Device,
+        cells: wgpu::BindingResource,
+        sorted: wgpu::BindingResource,
+        params: wgpu::BindingResource,
+        queries: wgpu::BindingResource,
+        neighbors: wgpu::BindingResource,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("KNN Query Bind Group"),
+            layout: &self.query_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 1, resource: cells },
+                wgpu::BindGroupEntry { binding: 2, resource: sorted },
+                wgpu::BindGroupEntry { binding: 3, resource: params },
+                wgpu::BindGroupEntry { binding: 4, resource: queries },
+                wgpu::BindGroupEntry { binding: 5, resource: neighbors },
+            ],
+        })
+    }
+
+    // `cells` is bound with table_len + 1 elements and must be zeroed, with scan::buffer_len(table_len + 1) u32 allocated
+    pub fn build(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        bind_group: &wgpu::BindGroup,
+        cells: &wgpu::Buffer,
+        point_count: u32,
+        table_len: u32,
+    ) {
+        pass(encoder, &self.count, bind_group, point_count);
+        self.scan.encode(device, encoder, cells, table_len + 1);
+        pass(encoder, &self.scatter, bind_group, point_count);
+    }
+
+    pub fn query(&self, encoder: &mut wgpu::CommandEncoder, bind_group: &wgpu::BindGroup, query_count: u32) {
+        pass(encoder, &self.query, bind_group, query_count);
+    }
+}
+
+fn pass(encoder: &mut wgpu::CommandEncoder, pipeline: &wgpu::ComputePipeline, bind_group: &wgpu::BindGroup, threads: u32) {
+    let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+    cpass.set_pipeline(pipeline);
+    cpass.set_bind_group(0, bind_group, &[]);
+    cpass.dispatch_workgroups(threads.div_ceil(64), 1, 1);
+}
+
+// ============================================================================
+//  CPU KD-tree
+// ============================================================================
+
+// Leaf: `order[start..end]`. Split: children at `left` and `left + 1`, split plane `value` on `axis`
+#[derive(Copy, Clone)]
+struct Node {
+    start: u32,
+    end: u32,
+    axis: u8,
+    value: f32,
+    left: u32,
+}
+
+const LEAF: u8 = 3;
+
+pub struct KdTree<'a> {
+    points: &'a [[f32; 3]],
+    order: Vec<u32>,
+    nodes: Vec<Node>,
+}
+
+impl<'a> KdTree<'a> {
+    pub fn build(points: &'a [[f32; 3]]) -> Self {
+        let mut tree = Self { points, order: (0..points.len() as u32).collect(), nodes: Vec::new() };
+        tree.nodes.push(Node { start: 0, end: points.len() as u32, axis: LEAF, value: 0.0, left: 0 });
+        // 幅優先で分割 (子は常に隣り合わせに置く)
+        let mut next = 0;
+        while next < tree.nodes.len() {
+            tree.split(next);
+            next += 1;
+        }
+        tree
+    }
+
+    // 最も広がった軸の中央値で 2 分割する
+    fn split(&mut self, node: usize) {
+        let Node { start, end, .. } = self.nodes[node];
+        let (start, end) = (start as usize, end as usize);
+        if end - start <= LEAF_SIZE { return; }
+
+        let points = self.points;
+        let ids = &mut self.order[start..end];
+        let mut min = [f32::MAX; 3];
+        let mut max = [f32::MIN; 3];
+        for &i in ids.iter() {
+            for a in 0..3 {
+                min[a] = min[a].min(points[i as usize][a]);
+                max[a] = max[a].max(points[i as usize][a]);
+            }
+        }
+        let spread = [0, 1, 2].map(|a| max[a] - min[a]);
+        let axis = if spread[0] >= spread[1] && spread[0] >= spread[2] { 0 } else if spread[1] >= spread[2] { 1 } else { 2 };
+
+        let mid = ids.len() / 2;
+        ids.select_nth_unstable_by(mid, |&a, &b| points[a as usize][axis].total_cmp(&points[b as usize][axis]));
+        let value = points[ids[mid] as usize][axis];
+
+        let left = self.nodes.len() as u32;
+        let mid = (start + mid) as u32;
+        self.nodes.push(Node { start: start as u32, end: mid, axis: LEAF, value: 0.0, left: 0 });
+        self.nodes.push(Node { start: mid, end: end as u32, axis: LEAF, value: 0.0, left: 0 });
+        self.nodes[node] = Node { axis: axis as u8, value, left, ..self.nodes[node] };
+    }
+
+    // Fills one result row (length k) with the nearest points within max_dist2, sorted by (distance, index)
+    fn search(&self, q: [f32; 3], max_dist2: f32, indices: &mut [u32], dist2: &mut [f32]) {
+        indices.fill(NONE);
+        dist2.fill(max_dist2);
+        let k = indices.len();
+        let mut found = 0;
+        // (node, 分割面までの距離の 2 乗)
+        let mut stack = vec![(0u32, 0.0f32)];
+        while let Some((node, plane_d2)) = stack.pop() {
+            let bound = if found == k { dist2[k - 1] } else { max_dist2 };
+            if plane_d2 > bound { continue; }
+            let node = self.nodes[node as usize];
+            if node.axis == LEAF {
+                for &i in &self.order[node.start as usize..node.end as usize] {
+                    let d2 = squared_distance(q, self.points[i as usize]);
+                    if d2 > max_dist2 || (found == k && !closer(d2, i, dist2[k - 1], indices[k - 1])) { continue; }
+                    let mut j = found.min(k - 1);
+                    while j > 0 && closer(d2, i, dist2[j - 1], indices[j - 1]) {
+                        dist2[j] = dist2[j - 1];
+                        indices[j] = indices[j - 1];
+                        j -= 1;
+                    }
+                    dist2[j] = d2;
+                    indices[j] = i;
+                    found = (found + 1).min(k);
+                }
+                continue;
+            }
+            // 分割面の反対側は、面までの距離が現在の k 番目以下なら候補が残っている
+            let diff = q[node.axis as usize] - node.value;
+            let (near, far) = if diff < 0.0 { (node.left, node.left + 1) } else { (node.left + 1, node.left) };
+            stack.push((far, diff * diff));
+            stack.push((near, plane_d2));
+        }
+    }
+}
+
+// Result when there is nothing to search (no points): every row is empty
+pub fn search_empty(query: &KnnQuery, queries: usize) -> Neighbors {
+    Neighbors::from_squared(query.k, 1, std::iter::repeat_n((NONE, 0.0), queries))
+}
+
+// CPU version of the GPU search: one KD-tree over `points`, queries in parallel
+pub fn search_cpu(pool: &CpuPool, points: &[[f32; 3]], queries: &[[f32; 3]], query: &KnnQuery) -> Neighbors {
+    let width = query.k.min(points.len());
+    if width == 0 || queries.is_empty() {
+        return search_empty(query, queries.len());
+    }
+    let tree = KdTree::build(points);
+    let mut indices = vec![NONE; queries.len() * width];
+    let mut dist2 = vec![0.0; queries.len() * width];
+    pool.install(|| {
+        indices.par_chunks_mut(width).zip(dist2.par_chunks_mut(width)).zip(queries.par_iter()).for_each(|((idx, d2), &q)| {
+            tree.search(q, query.max_dist2(), idx, d2);
+        });
+    });
+    Neighbors::from_squared(query.k, width, indices.into_iter().zip(dist2))
+}
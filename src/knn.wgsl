@@ -0,0 +1,161 @@
+// src/knn.wgsl
+// 一様ハッシュグリッドによる近傍探索 (src/knn.rs)
+// 1. count_cells: 各点のセルのハッシュごとに点数を数える
+// 2. prefix sum (scan.wgsl) で各ハッシュの開始位置にする
+// 3. scatter_points: 点 (xyz + 元のインデックス) をハッシュ順に並べる (以降 cells[h] は h の終端 = h + 1 の開始)
+// 4. query_neighbors: クエリごとにセルを内側の殻 (ring) から外側へ走査して k 近傍を求める
+// グリッドは外れ値を除いた範囲だけを覆い、範囲外の点は端のセルに入れる (point_cell)。
+// クエリは範囲外でもよく、その場合はグリッドまでの距離の殻から走査を始める
+// ストレージバッファは 1 ステージ 4 個まで (downlevel の上限) なので、探索は並べ替えた点だけを読む
+
+struct Params {
+    // グリッドの原点 (外れ値を除いた点群の最小座標) とセルの一辺
+    origin: vec3<f32>,
+    cell_size: f32,
+    // 点群を覆うセル数 (各軸)
+    dims: vec3<i32>,
+    // ハッシュテーブルの大きさ - 1 (2 のべき乗)
+    table_mask: u32,
+    k: u32,
+    // 探索半径の 2 乗 (kNN は f32 の最大値)
+    max_dist2: f32,
+    _pad0: u32,
+    _pad1: u32,
+};
+
+const NONE: u32 = 0xffffffffu;
+// セル座標の上限 (遠すぎるクエリで i32 があふれないように)
+const MAX_CELL: f32 = 16777216.0;
+
+@group(0) @binding(0) var<storage, read> points : array<vec4<f32>>;
+@group(0) @binding(1) var<storage, read_write> cells : array<atomic<u32>>;
+// bitcast した xyz + 元のインデックス (w)。インデックスを f32 に入れると非正規化数になり得るので u32 で持つ
+@group(0) @binding(2) var<storage, read_write> sorted : array<vec4<u32>>;
+@group(0) @binding(3) var<uniform> params : Params;
+@group(0) @binding(4) var<storage, read> queries : array<vec4<f32>>;
+// (インデックス, bitcast した距離の 2 乗) を k 個ずつ。平方根はホスト側でとる
+@group(0) @binding(5) var<storage, read_write> out_neighbors : array<vec2<u32>>;
+
+fn cell_of(p: vec3<f32>) -> vec3<i32> {
+    let c = floor((p - params.origin) / params.cell_size);
+    return vec3<i32>(clamp(c, vec3<f32>(-MAX_CELL), vec3<f32>(MAX_CELL)));
+}
+
+// 点の入るセル。グリッドの外の点は最も近い端のセルに入れる
+// (端のセルより外側の点はクエリから見て端のセル以上に遠いので、殻による打ち切りはそのまま正しい)
+fn point_cell(p: vec3<f32>) -> vec3<i32> {
+    return clamp(cell_of(p), vec3<i32>(0), params.dims - vec3<i32>(1));
+}
+
+fn cell_hash(c: vec3<i32>) -> u32 {
+    let u = bitcast<vec3<u32>>(c);
+    return ((u.x * 73856093u) ^ (u.y * 19349663u) ^ (u.z * 83492791u)) & params.table_mask;
+}
+
+@compute @workgroup_size(64)
+fn count_cells(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let idx = global_id.x;
+    if (idx >= arrayLength(&points)) { return; }
+    atomicAdd(&cells[cell_hash(point_cell(points[idx].xyz))], 1u);
+}
+
+@compute @workgroup_size(64)
+fn scatter_points(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let idx = global_id.x;
+    if (idx >= arrayLength(&points)) { return; }
+    let p = points[idx].xyz;
+    let slot = atomicAdd(&cells[cell_hash(point_cell(p))], 1u);
+    sorted[slot] = vec4<u32>(bitcast<vec3<u32>>(p), idx);
+}
+
+// (d2, index) の辞書順。CPU の KD-tree (knn.rs) と同じ順序で同距離を決める
+fn closer(d2: f32, idx: u32, other_d2: f32, other_idx: u32) -> bool {
+    return d2 < other_d2 || (d2 == other_d2 && idx < other_idx);
+}
+
+fn closer_than(d2: f32, idx: u32, slot: u32) -> bool {
+    let other = out_neighbors[slot];
+    return closer(d2, idx, bitcast<f32>(other.y), other.x);
+}
+
+// 出力の行 (距離順) に候補を挿入する。found は行に入っている数
+fn insert(base: u32, found: u32, d2: f32, idx: u32) -> u32 {
+    let k = params.k;
+    if (d2 > params.max_dist2) { return found; }
+    if (found == k && !closer_than(d2, idx, base + k - 1u)) { return found; }
+
+    var j = min(found, k - 1u);
+    loop {
+        if (j == 0u || !closer_than(d2, idx, base + j - 1u)) { break; }
+        out_neighbors[base + j] = out_neighbors[base + j - 1u];
+        j = j - 1u;
+    }
+    out_neighbors[base + j] = vec2<u32>(idx, bitcast<u32>(d2));
+    return min(found + 1u, k);
+}
+
+// セル c に入っている点を候補にする (ハッシュが衝突した別のセルの点は除く)
+fn visit_cell(base: u32, found: u32, q: vec3<f32>, c: vec3<i32>) -> u32 {
+    if (any(c < vec3<i32>(0)) || any(c >= params.dims)) { return found; }
+    let h = cell_hash(c);
+    var start = 0u;
+    if (h > 0u) { start = atomicLoad(&cells[h - 1u]); }
+    let end = atomicLoad(&cells[h]);
+
+    var n = found;
+    for (var s = start; s < end; s = s + 1u) {
+        let entry = sorted[s];
+        let p = bitcast<vec3<f32>>(entry.xyz);
+        if (any(point_cell(p) != c)) { continue; }
+        let d = q - p;
+        n = insert(base, n, d.x * d.x + d.y * d.y + d.z * d.z, entry.w);
+    }
+    return n;
+}
+
+@compute @workgroup_size(64)
+fn query_neighbors(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let qi = global_id.x;
+    if (qi >= arrayLength(&queries)) { return; }
+    let k = params.k;
+    let base = qi * k;
+    for (var j = 0u; j < k; j = j + 1u) {
+        out_neighbors[base + j] = vec2<u32>(NONE, bitcast<u32>(params.max_dist2));
+    }
+
+    let q = queries[qi].xyz;
+    let qc = cell_of(q);
+    let last = params.dims - vec3<i32>(1);
+    // グリッドまでの距離 (これより内側の殻は空) と、グリッド全体を覆う殻
+    let to_grid = max(max(-qc, qc - last), vec3<i32>(0));
+    let to_far = max(qc, last - qc);
+    let r_min = max(to_grid.x, max(to_grid.y, to_grid.z));
+    var r_max = max(to_far.x, max(to_far.y, to_far.z));
+    // 半径探索ではセル r 個分より外側に候補はない。グリッドの外のクエリは端のセルに寄せた点を
+    // 探すので、グリッドまでの殻 (r_min) から数える
+    if (params.max_dist2 < 3.0e38) {
+        r_max = min(r_max, r_min + i32(ceil(sqrt(params.max_dist2) / params.cell_size)));
+    }
+
+    var found = 0u;
+    for (var r = r_min; r <= r_max; r = r + 1) {
+        // 殻 r (チェビシェフ距離が r のセル) のうちグリッドと重なる部分
+        let lo = max(-vec3<i32>(r), -qc);
+        let hi = min(vec3<i32>(r), last - qc);
+        for (var dx = lo.x; dx <= hi.x; dx = dx + 1) {
+            for (var dy = lo.y; dy <= hi.y; dy = dy + 1) {
+                if (abs(dx) == r || abs(dy) == r) {
+                    for (var dz = lo.z; dz <= hi.z; dz = dz + 1) {
+                        found = visit_cell(base, found, q, qc + vec3<i32>(dx, dy, dz));
+                    }
+                } else {
+                    if (-r >= lo.z) { found = visit_cell(base, found, q, qc + vec3<i32>(dx, dy, -r)); }
+                    if (r != 0 && r <= hi.z) { found = visit_cell(base, found, q, qc + vec3<i32>(dx, dy, r)); }
+                }
+            }
+        }
+        // 殻 r まで走査すると、距離 r * cell_size 未満の点はすべて見つかっている
+        let covered = f32(r) * params.cell_size;
+        if (found == k && bitcast<f32>(out_neighbors[base + k - 1u].y) < covered * covered) { break; }
+    }
+}
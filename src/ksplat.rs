@@ -0,0 +1,360 @@
+use std::collections::HashMap;
+use std::io::Write;
+
+use half::f16;
+
+use crate::error::{GsError, GsResult};
+use crate::sh::{self, ShCoeffs};
+use crate::splat::{decode_color, encode_color, normalize_quat};
+use crate::{GaussianSplat, SplatData};
+
+// ============================================================================
+//  GaussianSplats3D .ksplat (v0.1, little endian)
+// ============================================================================
+//
+// [main header 4096 bytes][section header 1024 bytes x maxSectionCount][section data ...]
+//
+// セクションデータ: 部分バケットの長さ (u32 x partial) → バケット中心 (f32x3 x bucketCount)
+//                   → スプラット (bytes_per_splat x maxSplatCount)
+// スプラット: center / scale (線形) / rotation (wxyz) / rgba u8 / SH (係数 → RGB のインターリーブ)
+//
+// compression level
+//   0: f32 成分
+//   1: center はバケット中心からの u16 固定小数点、scale / rotation / SH は f16
+//   2: level 1 と同じだが SH を [min, max] の u8 に量子化
+
+const MAIN_HEADER_SIZE: usize = 4096;
+const SECTION_HEADER_SIZE: usize = 1024;
+const VERSION: (u8, u8) = (0, 1);
+
+const BUCKET_SIZE: usize = 256;
+const BUCKET_BLOCK_SIZE: f32 = 5.0;
+const BUCKET_STORAGE_SIZE: usize = 12;
+const COMPRESSION_SCALE_RANGE: u32 = 32767;
+const DEFAULT_SH_RANGE: (f32, f32) = (-1.5, 1.5);
+
+pub const MAX_COMPRESSION_LEVEL: u32 = 2;
+
+fn u16_at(b: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([b[at], b[at + 1]])
+}
+
+fn u32_at(b: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([b[at], b[at + 1], b[at + 2], b[at + 3]])
+}
+
+fn f32_at(b: &[u8], at: usize) -> f32 {
+    f32::from_le_bytes([b[at], b[at + 1], b[at + 2], b[at + 3]])
+}
+
+fn push_f16(out: &mut Vec<u8>, v: f32) {
+    out.extend_from_slice(&f16::from_f32(v).to_le_bytes());
+}
+
+fn f16_at(b: &[u8], at: usize) -> f32 {
+    f16::from_le_bytes([b[at], b[at + 1]]).to_f32()
+}
+
+// Bytes of center + scale + rotation + color, and of one SH value
+fn component_sizes(level: u32) -> (usize, usize) {
+    match level {
+        0 => (44, 4),
+        1 => (24, 2),
+        _ => (24, 1),
+    }
+}
+
+// Cheap header check used for format sniffing
+pub fn is_ksplat(data: &[u8]) -> bool {
+    data.len() >= MAIN_HEADER_SIZE
+        && (data[0], data[1]) == VERSION
+        && u32_at(data, 8) <= u32_at(data, 4)
+        && u32_at(data, 16) <= u32_at(data, 12)
+        && u16_at(data, 20) as u32 <= MAX_COMPRESSION_LEVEL
+}
+
+struct Section {
+    splat_count: usize,
+    max_splat_count: usize,
+    bucket_size: usize,
+    bucket_count: usize,
+    bucket_block_size: f32,
+    bucket_storage_size: usize,
+    scale_range: u32,
+    storage_size: usize,
+    full_bucket_count: usize,
+    partial_bucket_count: usize,
+    sh_degree: u32,
+}
+
+impl Section {
+    fn parse(h: &[u8]) -> Self {
+        Section {
+            splat_count: u32_at(h, 0) as usize,
+            max_splat_count: u32_at(h, 4) as usize,
+            bucket_size: u32_at(h, 8) as usize,
+            bucket_count: u32_at(h, 12) as usize,
+            bucket_block_size: f32_at(h, 16),
+            bucket_storage_size: u16_at(h, 20) as usize,
+            scale_range: u32_at(h, 24),
+            storage_size: u32_at(h, 28) as usize,
+            full_bucket_count: u32_at(h, 32) as usize,
+            partial_bucket_count: u32_at(h, 36) as usize,
+            sh_degree: u16_at(h, 40) as u32,
+        }
+    }
+}
+
+pub fn read_ksplat(data: &[u8]) -> GsResult<SplatData> {
+    let bad = |msg: String| GsError::format(".ksplat", msg);
+    if data.len() < MAIN_HEADER_SIZE {
+        return Err(bad(format!("file is truncated: {} bytes, header needs {}", data.len(), MAIN_HEADER_SIZE)));
+    }
+    if (data[0], data[1]) != VERSION {
+        return Err(bad(format!("unsupported version {}.{} (expected {}.{})", data[0], data[1], VERSION.0, VERSION.1)));
+    }
+    let max_sections = u32_at(data, 4) as usize;
+    let section_count = u32_at(data, 8) as usize;
+    let level = u16_at(data, 20) as u32;
+    if level > MAX_COMPRESSION_LEVEL {
+        return Err(bad(format!("unsupported compression level {}", level)));
+    }
+    if section_count > max_sections {
+        return Err(bad(format!("declares {} sections but only {} section headers", section_count, max_sections)));
+    }
+    // 範囲が未設定 (0, 0) の古いファイルは既定値を使う
+    let (sh_min, sh_max) = match (f32_at(data, 36), f32_at(data, 40)) {
+        (min, max) if min < max => (min, max),
+        _ => DEFAULT_SH_RANGE,
+    };
+
+    let headers_end = max_sections.checked_mul(SECTION_HEADER_SIZE)
+        .and_then(|n| n.checked_add(MAIN_HEADER_SIZE))
+        .filter(|&end| end <= data.len())
+        .ok_or_else(|| bad("section headers run past the end of the file".to_string()))?;
+    let sections: Vec<Section> = (0..section_count)
+        .map(|i| Section::parse(&data[MAIN_HEADER_SIZE + i * SECTION_HEADER_SIZE..]))
+        .collect();
+
+    // 全セクションで SH 次数を揃える (少ない方に合わせる)
+    let degree = sections.iter().map(|s| s.sh_degree).min().unwrap_or(0);
+    if degree > sh::MAX_SH_DEGREE {
+        return Err(bad(format!("unsupported SH degree {}", degree)));
+    }
+
+    let mut splats = Vec::new();
+    let mut coeffs = Vec::new();
+    let mut base = headers_end;
+    for (i, s) in sections.iter().enumerate() {
+        let section = data.get(base..base + s.storage_size)
+            .ok_or_else(|| bad(format!("section {} runs past the end of the file", i)))?;
+        base += s.storage_size;
+
+        let sh_values = sh::rest_count(s.sh_degree) * 3;
+        let (fixed, sh_size) = component_sizes(level);
+        let bytes_per_splat = fixed + sh_values * sh_size;
+        let lengths_size = s.partial_bucket_count * 4;
+        let splat_base = lengths_size + s.bucket_count * s.bucket_storage_size;
+        if s.splat_count > s.max_splat_count || splat_base + s.max_splat_count * bytes_per_splat > section.len() {
+            return Err(bad(format!("section {} is smaller than its {} splats", i, s.max_splat_count)));
+        }
+
+        // スプラット番号 → バケット: 満杯のバケットが先、部分バケットが後
+        let full_len = s.full_bucket_count.saturating_mul(s.bucket_size);
+        let mut partial_ends = Vec::with_capacity(s.partial_bucket_count);
+        if level >= 1 {
+            if s.bucket_storage_size < BUCKET_STORAGE_SIZE || s.scale_range == 0 {
+                return Err(bad(format!("section {} has invalid bucket parameters", i)));
+            }
+            let mut end = full_len;
+            for p in 0..s.partial_bucket_count {
+                end = end.saturating_add(u32_at(section, p * 4) as usize);
+                partial_ends.push(end);
+            }
+            if end < s.splat_count || s.full_bucket_count + s.partial_bucket_count > s.bucket_count {
+                return Err(bad(format!("section {} buckets do not cover its splats", i)));
+            }
+        }
+        let bucket_of = |j: usize| {
+            if j < full_len { j / s.bucket_size } else { s.full_bucket_count + partial_ends.partition_point(|&end| end <= j) }
+        };
+        let bucket_center = |b: usize| {
+            let at = lengths_size + b * s.bucket_storage_size;
+            [f32_at(section, at), f32_at(section, at + 4), f32_at(section, at + 8)]
+        };
+        let position_scale = s.bucket_block_size / 2.0 / s.scale_range as f32;
+
+        for j in 0..s.splat_count {
+            let r = &section[splat_base + j * bytes_per_splat..][..bytes_per_splat];
+            let (pos, scale, rot, color_at) = if level == 0 {
+                (
+                    [f32_at(r, 0), f32_at(r, 4), f32_at(r, 8)],
+                    [f32_at(r, 12), f32_at(r, 16), f32_at(r, 20)],
+                    [f32_at(r, 24), f32_at(r, 28), f32_at(r, 32), f32_at(r, 36)],
+                    40,
+                )
+            } else {
+                let center = bucket_center(bucket_of(j));
+                let pos = [0, 1, 2].map(|a| (u16_at(r, a * 2) as f32 - s.scale_range as f32) * position_scale + center[a]);
+                (
+                    pos,
+                    [f16_at(r, 6), f16_at(r, 8), f16_at(r, 10)],
+                    [f16_at(r, 12), f16_at(r, 14), f16_at(r, 16), f16_at(r, 18)],
+                    20,
+                )
+            };
+            let (sh_dc, opacity) = decode_color([r[color_at], r[color_at + 1], r[color_at + 2], r[color_at + 3]]);
+            let [w, x, y, z] = rot;
+            splats.push(GaussianSplat {
+                pos,
+                opacity,
+                scale,
+                rot: normalize_quat([x, y, z, w]),
+                sh_dc,
+                ..Default::default()
+            });
+
+            let sh_at = color_at + 4;
+            coeffs.extend((0..sh::rest_count(degree) * 3).map(|k| match level {
+                0 => f32_at(r, sh_at + k * 4),
+                1 => f16_at(r, sh_at + k * 2),
+                _ => sh_min + r[sh_at + k] as f32 / 255.0 * (sh_max - sh_min),
+            }));
+        }
+    }
+
+    Ok(SplatData { splats, sh: ShCoeffs { degree, coeffs } })
+}
+
+// Splat indices sharing one grid cell, and the cell center
+type Bucket = (Vec<usize>, [f32; 3]);
+
+// Buckets of at most BUCKET_SIZE splats, returned as (full buckets, partial buckets)
+fn build_buckets(splats: &[GaussianSplat]) -> (Vec<Bucket>, Vec<Bucket>) {
+    let mut cells: HashMap<[i64; 3], Vec<usize>> = HashMap::new();
+    for (i, s) in splats.iter().enumerate() {
+        let cell = s.pos.map(|v| (v / BUCKET_BLOCK_SIZE).floor() as i64);
+        cells.entry(cell).or_default().push(i);
+    }
+    // HashMap の順序に依存しないよう、セルを座標順に並べる
+    let mut cells: Vec<_> = cells.into_iter().collect();
+    cells.sort_unstable_by_key(|(cell, _)| *cell);
+
+    let (mut full, mut partial) = (Vec::new(), Vec::new());
+    for (cell, indices) in cells {
+        let center = cell.map(|c| (c as f32 + 0.5) * BUCKET_BLOCK_SIZE);
+        for chunk in indices.chunks(BUCKET_SIZE) {
+            let bucket = (chunk.to_vec(), center);
+            if chunk.len() == BUCKET_SIZE { full.push(bucket) } else { partial.push(bucket) }
+        }
+    }
+    (full, partial)
+}
+
+// Single-section .ksplat. Levels 1 and 2 group the splats by bucket, so the output order differs from `splats`.
+pub fn write_ksplat<W: Write>(w: &mut W, splats: &[GaussianSplat], sh: &ShCoeffs, level: u32) -> GsResult<()> {
+    if level > MAX_COMPRESSION_LEVEL {
+        return Err(GsError::invalid(format!(
+            "compression_level must be 0 - {}, got {}", MAX_COMPRESSION_LEVEL, level,
+        )));
+    }
+    let count = splats.len();
+    let sh_values = sh.stride();
+    let (fixed, sh_size) = component_sizes(level);
+    let bytes_per_splat = fixed + sh_values * sh_size;
+
+    let (full, partial) = if level >= 1 { build_buckets(splats) } else { (Vec::new(), Vec::new()) };
+    let buckets: Vec<_> = full.iter().chain(&partial).collect();
+    // level 0 はバケットを使わず入力順のまま書き出す
+    let all: Vec<usize> = (0..count).collect();
+    let groups: Vec<(&[usize], [f32; 3])> = if level >= 1 {
+        buckets.iter().map(|(indices, center)| (indices.as_slice(), *center)).collect()
+    } else {
+        vec![(all.as_slice(), [0.0; 3])]
+    };
+
+    let bucket_storage = if level >= 1 { BUCKET_STORAGE_SIZE } else { 0 };
+    let mut section = Vec::with_capacity(partial.len() * 4 + buckets.len() * bucket_storage + count * bytes_per_splat);
+    for (indices, _) in &partial {
+        section.extend_from_slice(&(indices.len() as u32).to_le_bytes());
+    }
+    for (_, center) in &buckets {
+        for v in center {
+            section.extend_from_slice(&v.to_le_bytes());
+        }
+    }
+
+    let (sh_min, sh_max) = DEFAULT_SH_RANGE;
+    let range = COMPRESSION_SCALE_RANGE as f32;
+    let position_scale = range / (BUCKET_BLOCK_SIZE / 2.0);
+    for (indices, center) in groups {
+        for &i in indices {
+            let s = &splats[i];
+            let [x, y, z, qw] = normalize_quat(s.rot);
+            let rot = [qw, x, y, z];
+            if level == 0 {
+                for v in s.pos.iter().chain(&s.scale).chain(&rot) {
+                    section.extend_from_slice(&v.to_le_bytes());
+                }
+            } else {
+                for (p, c) in s.pos.iter().zip(center) {
+                    let q = ((p - c) * position_scale).round() + range;
+                    section.extend_from_slice(&(q.clamp(0.0, 2.0 * range) as u16).to_le_bytes());
+                }
+                for &v in s.scale.iter().chain(&rot) {
+                    push_f16(&mut section, v);
+                }
+            }
+            section.extend_from_slice(&encode_color(s));
+            for &v in if sh_values > 0 { sh.splat(i) } else { &[] } {
+                match level {
+                    0 => section.extend_from_slice(&v.to_le_bytes()),
+                    1 => push_f16(&mut section, v),
+                    _ => section.push(((v.clamp(sh_min, sh_max) - sh_min) / (sh_max - sh_min) * 255.0).round() as u8),
+                }
+            }
+        }
+    }
+
+    // 中心はバウンディングボックスの中央 (ローダーでは参照されない)
+    let mut min = [f32::MAX; 3];
+    let mut max = [f32::MIN; 3];
+    for s in splats {
+        for a in 0..3 {
+            min[a] = min[a].min(s.pos[a]);
+            max[a] = max[a].max(s.pos[a]);
+        }
+    }
+    let center = if count > 0 { [0, 1, 2].map(|a| (min[a] + max[a]) / 2.0) } else { [0.0; 3] };
+
+    let mut header = vec![0u8; MAIN_HEADER_SIZE + SECTION_HEADER_SIZE];
+    let mut put = |at: usize, bytes: &[u8]| header[at..at + bytes.len()].copy_from_slice(bytes);
+    put(0, &[VERSION.0, VERSION.1]);
+    put(4, &1u32.to_le_bytes());
+    put(8, &1u32.to_le_bytes());
+    put(12, &(count as u32).to_le_bytes());
+    put(16, &(count as u32).to_le_bytes());
+    put(20, &(level as u16).to_le_bytes());
+    for (a, v) in center.iter().enumerate() {
+        put(24 + a * 4, &v.to_le_bytes());
+    }
+    put(36, &sh_min.to_le_bytes());
+    put(40, &sh_max.to_le_bytes());
+
+    let s = MAIN_HEADER_SIZE;
+    put(s, &(count as u32).to_le_bytes());
+    put(s + 4, &(count as u32).to_le_bytes());
+    put(s + 8, &(BUCKET_SIZE as u32).to_le_bytes());
+    put(s + 12, &(buckets.len() as u32).to_le_bytes());
+    put(s + 16, &BUCKET_BLOCK_SIZE.to_le_bytes());
+    put(s + 20, &(bucket_storage as u16).to_le_bytes());
+    put(s + 24, &COMPRESSION_SCALE_RANGE.to_le_bytes());
+    put(s + 28, &(section.len() as u32).to_le_bytes());
+    put(s + 32, &(full.len() as u32).to_le_bytes());
+    put(s + 36, &(partial.len() as u32).to_le_bytes());
+    put(s + 40, &(sh.degree as u16).to_le_bytes());
+
+    w.write_all(&header)?;
+    w.write_all(&section)?;
+    Ok(())
+}
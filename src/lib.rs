@@ -1,13 +1,44 @@
-use std::borrow::Cow;
 use bytemuck::{Pod, Zeroable};
 
+#[cfg(feature = "wasm")]
+use std::borrow::Cow;
 #[cfg(feature = "wasm")]
 use wgpu::util::DeviceExt;
 
-#[cfg(any(feature = "python", feature = "wasm"))]
+#[cfg(feature = "wasm")]
 use nalgebra as na;
 
+#[cfg(feature = "python")]
+mod array_view;
+#[cfg(feature = "python")]
+mod backend;
+#[cfg(feature = "python")]
+mod cpu;
+mod error;
+mod geometry;
+#[cfg(feature = "python")]
+mod gpu;
+mod ksplat;
+#[cfg(feature = "python")]
+mod knn;
+mod lzf;
+#[cfg(feature = "python")]
+mod normals;
+#[cfg(feature = "python")]
+mod outlier;
+mod pcd;
+mod ply;
+mod pointcloud;
+mod scan;
+mod sh;
+mod splat;
+mod spz;
 mod sr;
+#[cfg(feature = "python")]
+mod voxel;
+
+use error::{GsError, GsResult};
+use sh::ShCoeffs;
 
 
 // ============================================================================
@@ -17,15 +48,15 @@ mod sr;
 #[repr(C)]
 #[derive(Copy, Clone, Debug, Default, Pod, Zeroable)]
 pub struct GaussianSplat {
-    // pos(xyz), opacity(w) -> 16 bytes
+    // pos(xyz), opacity(w) -> 16 bytes (opacity is activated: 0.0 - 1.0)
     pub pos: [f32; 3],
     pub opacity: f32,
     
-    // scale(xyz), pad(w) -> 16 bytes
+    // scale(xyz), pad(w) -> 16 bytes (linear scale, i.e. exp() already applied)
     pub scale: [f32; 3],
     pub _pad1: f32,
     
-    // rot(xyzw) -> 16 bytes
+    // rot(xyzw) -> 16 bytes (always x, y, z, w after loading; see QuatOrder)
     pub rot: [f32; 4],
     
     // sh(xyz), pad(w) -> 16 bytes
@@ -36,29 +67,147 @@ pub struct GaussianSplat {
 #[repr(C)]
 #[derive(Copy, Clone, Debug, Default, Pod, Zeroable)]
 pub struct Surfel {
-    // pos(xyz), pad(w) -> 16 bytes
+    // pos(xyz), radius(w) -> 16 bytes (radius: largest tangent scale of the footprint)
     pub pos: [f32; 3],
-    pub _pad0: f32,
+    pub radius: f32,
     
-    // color(xyz), pad(w) -> 16 bytes
+    // color(xyz), opacity(w) -> 16 bytes
     pub color: [f32; 3],
-    pub _pad1: f32,
+    pub opacity: f32,
     
-    // normal(xyz), pad(w) -> 16 bytes
+    // normal(xyz), curvature(w) -> 16 bytes (curvature: PCL surface variation, see surfel_shape_cpu)
     pub normal: [f32; 3],
-    pub _pad2: f32,
+    pub curvature: f32,
 }
 
-// Input PLY format (Packed)
-#[repr(C, packed)]
+// Uniform of the geometry kernel (compute_main)
+#[repr(C)]
 #[derive(Copy, Clone, Debug, Default, Pod, Zeroable)]
-struct RawSplat {
-    x: f32, y: f32, z: f32,
-    nx: f32, ny: f32, nz: f32,
-    f_dc_0: f32, f_dc_1: f32, f_dc_2: f32,
-    opacity: f32,
-    scale_0: f32, scale_1: f32, scale_2: f32,
-    rot_0: f32, rot_1: f32, rot_2: f32, rot_3: f32,
+struct GeometryParams {
+    // view_dir(xyz), sh_degree(w) -> 16 bytes
+    view_dir: [f32; 3],
+    sh_degree: u32,
+
+    // camera_pos(xyz), view_mode(w) -> 16 bytes
+    camera_pos: [f32; 3],
+    view_mode: u32,
+}
+
+const VIEW_MODE_DC: u32 = 0;
+#[cfg(feature = "python")]
+const VIEW_MODE_DIRECTION: u32 = 1;
+#[cfg(feature = "wasm")]
+const VIEW_MODE_CAMERA: u32 = 2;
+
+// Component order of the rotation quaternion in the source file.
+// Internally GaussianSplat::rot is always (x, y, z, w).
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum QuatOrder {
+    // (w, x, y, z): rot_0 is the real part, as written by the reference 3DGS trainer
+    #[default]
+    Wxyz,
+    // (x, y, z, w)
+    Xyzw,
+}
+
+impl QuatOrder {
+    pub fn to_xyzw(self, q: [f32; 4]) -> [f32; 4] {
+        match self {
+            Self::Wxyz => [q[1], q[2], q[3], q[0]],
+            Self::Xyzw => q,
+        }
+    }
+
+    pub fn from_xyzw(self, q: [f32; 4]) -> [f32; 4] {
+        match self {
+            Self::Wxyz => [q[3], q[0], q[1], q[2]],
+            Self::Xyzw => q,
+        }
+    }
+}
+
+impl std::str::FromStr for QuatOrder {
+    type Err = GsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "wxyz" => Ok(Self::Wxyz),
+            "xyzw" => Ok(Self::Xyzw),
+            _ => Err(GsError::invalid(format!("Unknown quaternion order '{}' (expected 'wxyz' or 'xyzw')", s))),
+        }
+    }
+}
+
+// Domain in which the source file stores scale and opacity.
+// GaussianSplat always holds activated values (linear scale, opacity in 0.0 - 1.0).
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ValueDomain {
+    // log(scale) and logit(opacity), as written by the reference 3DGS trainer
+    #[default]
+    PreActivation,
+    // Linear scale and opacity already passed through the sigmoid
+    Activated,
+}
+
+impl ValueDomain {
+    pub fn activate_scale(self, s: [f32; 3]) -> [f32; 3] {
+        match self {
+            Self::PreActivation => s.map(f32::exp),
+            Self::Activated => s,
+        }
+    }
+
+    pub fn activate_opacity(self, o: f32) -> f32 {
+        match self {
+            Self::PreActivation => 1.0 / (1.0 + (-o).exp()),
+            Self::Activated => o,
+        }
+    }
+
+    // Inverse of activate_scale (used when writing trainer-compatible files)
+    pub fn deactivate_scale(self, s: [f32; 3]) -> [f32; 3] {
+        match self {
+            Self::PreActivation => s.map(f32::ln),
+            Self::Activated => s,
+        }
+    }
+
+    // Inverse of activate_opacity; 0 / 1 are clamped so the logit stays finite
+    pub fn deactivate_opacity(self, o: f32) -> f32 {
+        match self {
+            Self::PreActivation => {
+                let o = o.clamp(1e-6, 1.0 - 1e-6);
+                (o / (1.0 - o)).ln()
+            }
+            Self::Activated => o,
+        }
+    }
+}
+
+impl std::str::FromStr for ValueDomain {
+    type Err = GsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "preactivation" | "raw" => Ok(Self::PreActivation),
+            "activated" => Ok(Self::Activated),
+            _ => Err(GsError::invalid(format!("Unknown value domain '{}' (expected 'preactivation' or 'activated')", s))),
+        }
+    }
+}
+
+// Options controlling how source attributes are interpreted at load time
+#[derive(Copy, Clone, Debug, Default)]
+pub struct LoadOptions {
+    pub quat_order: QuatOrder,
+    pub value_domain: ValueDomain,
+}
+
+// Splats plus the SH bands that do not fit in the 64-byte GaussianSplat
+#[derive(Clone, Debug, Default)]
+pub struct SplatData {
+    pub splats: Vec<GaussianSplat>,
+    pub sh: ShCoeffs,
 }
 
 // ============================================================================
@@ -67,22 +216,31 @@ struct RawSplat {
 
 // SH to RGB (0th order)
 fn sh_to_rgb_cpu(sh: [f32; 3]) -> [f32; 3] {
-    let c0 = 0.2820947917;
-    [
-        (0.5 + c0 * sh[0]).clamp(0.0, 1.0),
-        (0.5 + c0 * sh[1]).clamp(0.0, 1.0),
-        (0.5 + c0 * sh[2]).clamp(0.0, 1.0),
-    ]
+    sh::eval_sh_cpu(sh, &[], 0, [0.0; 3])
 }
 
-// Compute Normal from Rotation quaternion (x,y,z,w) and Scale
+// Normalizes an optional user-supplied view direction
+#[cfg(feature = "python")]
+fn normalize_view_dir(view_dir: Option<[f32; 3]>) -> GsResult<Option<[f32; 3]>> {
+    let Some(d) = view_dir else { return Ok(None) };
+    let len = (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt();
+    if !(len > 0.0 && len.is_finite()) {
+        return Err(GsError::invalid("view_dir must be a finite, non-zero vector"));
+    }
+    Ok(Some([d[0] / len, d[1] / len, d[2] / len]))
+}
+
+// Storage buffers may not be empty; upload at least one float
 #[cfg(any(feature = "python", feature = "wasm"))]
+fn sh_buffer_contents(sh: &ShCoeffs) -> &[u8] {
+    if sh.coeffs.is_empty() { bytemuck::bytes_of(&0.0f32) } else { bytemuck::cast_slice(&sh.coeffs) }
+}
+
+// Compute Normal from Rotation quaternion (x,y,z,w) and Scale
+#[cfg(feature = "wasm")]
 fn compute_normal_cpu(rot: [f32; 4], scale: [f32; 3]) -> [f32; 3] {
-    // rot is [x, y, z, w] stored in PLY/Struct
-    // nalgebra expects w last for Quaternion constructor: (w, i, j, k)
-    // Wait, typical PLY is w,x,y,z.
-    // Our loader stores it as rot[0]..rot[3].
-    // If input is (x,y,z,w), then q = new(w, x, y, z)
+    // rot is [x, y, z, w] (normalized by the loader, see QuatOrder)
+    // nalgebra's Quaternion constructor takes (w, i, j, k)
     let q = na::UnitQuaternion::new_normalize(na::Quaternion::new(rot[3], rot[0], rot[1], rot[2]));
     let r = q.to_rotation_matrix();
     
@@ -100,134 +258,121 @@ fn compute_normal_cpu(rot: [f32; 4], scale: [f32; 3]) -> [f32; 3] {
     [n.x, n.y, n.z]
 }
 
+// Footprint radius and curvature of the surfel derived from one splat.
+// curvature は PCL と同じ surface variation (λmin / Σλ) を共分散 (scale^2) に適用したもの
+fn surfel_shape_cpu(scale: [f32; 3]) -> (f32, f32) {
+    let s = scale.map(f32::abs);
+    let radius = s[0].max(s[1]).max(s[2]);
+    let var = s.map(|v| v * v);
+    let total = var[0] + var[1] + var[2];
+    let curvature = if total > 0.0 { var[0].min(var[1]).min(var[2]) / total } else { 0.0 };
+    (radius, curvature)
+}
+
 // ============================================================================
 //  3. GPU Logic (Headless for Python)
 // ============================================================================
 
-#[cfg(feature = "python")]
-async fn run_compute_shader_headless(splats: &[GaussianSplat]) -> Result<Vec<Surfel>, String> {
-    use wgpu::util::DeviceExt;
-
-    // 1. Instance
-    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-        backends: wgpu::Backends::all(),
-        flags: wgpu::InstanceFlags::default(),
-        dx12_shader_compiler: wgpu::Dx12Compiler::Fxc,
-        gles_minor_version: wgpu::Gles3MinorVersion::Automatic,
-    });
-
-    // 2. Adapter
-    let adapter = instance.request_adapter(&wgpu::RequestAdapterOptions {
-        power_preference: wgpu::PowerPreference::HighPerformance,
-        compatible_surface: None,
-        force_fallback_adapter: false,
-    }).await.ok_or("Failed to find GPU adapter")?;
-
-    // 3. Device
-    // WSL2対策: required_limitsを下げておく
-    let (device, queue) = adapter.request_device(&wgpu::DeviceDescriptor {
-        label: None,
-        required_features: wgpu::Features::empty(),
-        required_limits: wgpu::Limits::downlevel_defaults(), 
-        memory_hints: wgpu::MemoryHints::default(),
-    }, None).await.map_err(|e| format!("Failed to create device: {:?}", e))?;
-
-    // 4. Buffers
-    let input_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-        label: Some("Input Buffer"),
-        contents: bytemuck::cast_slice(splats),
-        usage: wgpu::BufferUsages::STORAGE,
-    });
-
-    let output_size = (splats.len() * std::mem::size_of::<Surfel>()) as u64;
-    let output_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-        label: Some("Output Buffer"),
-        size: output_size,
-        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
-        mapped_at_creation: false,
-    });
-    
-    let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-        label: Some("Staging Buffer"),
-        size: output_size,
-        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
-        mapped_at_creation: false,
-    });
-
-    // 5. Pipeline
-    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-        label: Some("Shader"),
-        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("shader.wgsl"))),
-    });
-
-    let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-        label: None,
-        entries: &[
-            wgpu::BindGroupLayoutEntry { binding: 0, visibility: wgpu::ShaderStages::COMPUTE, ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Storage { read_only: true }, has_dynamic_offset: false, min_binding_size: None }, count: None },
-            wgpu::BindGroupLayoutEntry { binding: 1, visibility: wgpu::ShaderStages::COMPUTE, ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Storage { read_only: false }, has_dynamic_offset: false, min_binding_size: None }, count: None },
-        ],
-    });
-
-    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-        label: None, bind_group_layouts: &[&bind_group_layout], push_constant_ranges: &[],
-    });
-
-    let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-        label: None, layout: Some(&pipeline_layout), module: &shader, entry_point: Some("compute_main"),
-        compilation_options: Default::default(), cache: None,
-    });
-
-    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-        label: None, layout: &bind_group_layout,
-        entries: &[
-            wgpu::BindGroupEntry { binding: 0, resource: input_buffer.as_entire_binding() },
-            wgpu::BindGroupEntry { binding: 1, resource: output_buffer.as_entire_binding() },
-        ],
-    });
-
-    // 6. Execute
-    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
-    {
-        let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
-        cpass.set_pipeline(&pipeline);
-        cpass.set_bind_group(0, &bind_group, &[]);
-        let workgroups = (splats.len() as u32 + 63) / 64;
-        cpass.dispatch_workgroups(workgroups, 1, 1);
-    }
-    encoder.copy_buffer_to_buffer(&output_buffer, 0, &staging_buffer, 0, output_size);
-    queue.submit(Some(encoder.finish()));
-
-    // 7. Readback
-    let buffer_slice = staging_buffer.slice(..);
-    let (sender, receiver) = futures_intrusive::channel::shared::oneshot_channel();
-    buffer_slice.map_async(wgpu::MapMode::Read, move |v| sender.send(v).unwrap());
-    device.poll(wgpu::Maintain::Wait);
-
-    if let Some(Ok(())) = receiver.receive().await {
-        let data = buffer_slice.get_mapped_range();
-        let result: Vec<Surfel> = bytemuck::cast_slice(&data).to_vec();
-        drop(data);
-        staging_buffer.unmap();
-        Ok(result)
-    } else {
-        Err("Failed to map buffer".to_string())
+// Largest number of items per dispatch such that every per-item buffer fits in one storage binding
+// and the dispatch stays within max_compute_workgroups_per_dimension (64 threads per workgroup).
+// Rounded down to a multiple of 64 so that chunk offsets satisfy the 256-byte binding alignment.
+#[cfg(any(feature = "python", feature = "wasm"))]
+fn max_chunk_len(limits: &wgpu::Limits, bytes_per_item: &[u64], threads_per_item: u64) -> GsResult<usize> {
+    let max_bytes = (limits.max_storage_buffer_binding_size as u64).min(limits.max_buffer_size);
+    let mut len = limits.max_compute_workgroups_per_dimension as u64 * 64 / threads_per_item.max(1);
+    for &bytes in bytes_per_item.iter().filter(|&&b| b > 0) {
+        len = len.min(max_bytes / bytes);
+    }
+    let len = len / 64 * 64;
+    if len == 0 {
+        return Err(GsError::BufferTooLarge(format!(
+            "A single splat exceeds the device limits ({} bytes per buffer binding, {} workgroups per dispatch)",
+            max_bytes, limits.max_compute_workgroups_per_dimension,
+        )));
     }
+    Ok(len as usize)
 }
 
-// ----------------------------------------------------------------------------
-//  Headless SR (for Python)
-// ----------------------------------------------------------------------------
-#[cfg(feature = "python")]
-async fn run_sr_headless(splats: &[GaussianSplat], factor: u32) -> Result<Vec<Surfel>, String> {
-    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
-    let adapter = instance.request_adapter(&wgpu::RequestAdapterOptions::default()).await.ok_or("No adapter")?;
-    let (device, queue) = adapter.request_device(&wgpu::DeviceDescriptor {
-        required_limits: wgpu::Limits::downlevel_defaults(),
-        ..Default::default()
-    }, None).await.map_err(|e| format!("{:?}", e))?;
-
-    let sr_pipeline = sr::SuperResolutionPipeline::new(&device);
-    sr_pipeline.run_and_readback(&device, &queue, splats, factor).await
+// Rejects per-item buffers that cannot be allocated in one piece (count × bytes > max_buffer_size).
+// チャンク分割はバインディング単位なので、バッファ全体は max_buffer_size に収まる必要がある
+#[cfg(feature = "wasm")]
+fn check_buffer_size(limits: &wgpu::Limits, label: &str, count: usize, bytes_per_item: u64) -> GsResult<()> {
+    match (count as u64).checked_mul(bytes_per_item) {
+        Some(size) if size <= limits.max_buffer_size => Ok(()),
+        size => Err(GsError::BufferTooLarge(format!(
+            "{} buffer for {} items ({} bytes) exceeds max_buffer_size ({} bytes)",
+            label, count, size.map_or_else(|| "overflowing".to_string(), |s| s.to_string()), limits.max_buffer_size,
+        ))),
+    }
+}
+
+// Binds `size` bytes at `offset` so that arrayLength() in WGSL sees the live element count
+#[cfg(any(feature = "python", feature = "wasm"))]
+fn buffer_range(buffer: &wgpu::Buffer, offset: u64, size: u64) -> wgpu::BindingResource<'_> {
+    wgpu::BindingResource::Buffer(wgpu::BufferBinding { buffer, offset, size: std::num::NonZeroU64::new(size) })
+}
+
+#[cfg(all(test, any(feature = "python", feature = "wasm")))]
+mod chunk_tests {
+    use super::*;
+
+    fn limits(binding: u32, buffer: u64, workgroups: u32) -> wgpu::Limits {
+        wgpu::Limits {
+            max_storage_buffer_binding_size: binding,
+            max_buffer_size: buffer,
+            max_compute_workgroups_per_dimension: workgroups,
+            ..wgpu::Limits::downlevel_defaults()
+        }
+    }
+
+    #[test]
+    fn limited_by_binding_size() {
+        // 64 MiB / 64 B = 1 Mi items (ディスパッチ上限 65535 * 64 より小さい)
+        let limits = limits(64 << 20, 256 << 20, 65535);
+        assert_eq!(max_chunk_len(&limits, &[64, 48], 1).unwrap(), 1 << 20);
+    }
+
+    #[test]
+    fn limited_by_max_buffer_size() {
+        let limits = limits(64 << 20, 1 << 20, 65535);
+        assert_eq!(max_chunk_len(&limits, &[64], 1).unwrap(), 1 << 14);
+    }
+
+    #[test]
+    fn limited_by_workgroups() {
+        let limits = limits(1 << 30, 1 << 30, 1000);
+        assert_eq!(max_chunk_len(&limits, &[16], 1).unwrap(), 64_000);
+        // 1 アイテムあたり 3 スレッド: 64000 / 3 = 21333 -> 64 の倍数に切り捨て
+        assert_eq!(max_chunk_len(&limits, &[16], 3).unwrap(), 21_312);
+    }
+
+    #[test]
+    fn rounded_down_to_binding_alignment() {
+        // 48 B の Surfel は 256 B のオフセット境界に揃うよう 64 個単位になる
+        let limits = limits(1 << 20, 1 << 30, 65535);
+        let len = max_chunk_len(&limits, &[48], 1).unwrap();
+        assert_eq!(len % 64, 0);
+        assert_eq!(len, (1 << 20) / 48 / 64 * 64);
+        assert_eq!(len as u64 * 48 % 256, 0);
+    }
+
+    #[test]
+    fn zero_sized_items_are_ignored() {
+        let limits = limits(1 << 20, 1 << 30, 65535);
+        assert_eq!(max_chunk_len(&limits, &[0, 64], 1).unwrap(), max_chunk_len(&limits, &[64], 1).unwrap());
+    }
+
+    #[test]
+    fn error_when_nothing_fits() {
+        let limits = limits(1 << 20, 1 << 30, 65535);
+        // 1 アイテムがバインディングを超える
+        assert!(matches!(max_chunk_len(&limits, &[(1 << 20) + 1], 1), Err(GsError::BufferTooLarge(_))));
+        // 入るのは 63 個以下で、64 の倍数に切り捨てると 0
+        assert!(matches!(max_chunk_len(&limits, &[(1 << 20) / 63], 1), Err(GsError::BufferTooLarge(_))));
+        // ディスパッチ 1 回に 1 アイテム分のスレッドも入らない
+        let limits = self::limits(1 << 20, 1 << 30, 1);
+        assert!(matches!(max_chunk_len(&limits, &[16], 128), Err(GsError::BufferTooLarge(_))));
+    }
 }
 
 // ============================================================================
@@ -241,103 +386,411 @@ use pyo3::prelude::*;
 #[pyclass]
 struct SplatManager {
     splats: Vec<GaussianSplat>,
+    sh: ShCoeffs,
     surfels: Vec<Surfel>,
+    // compute_geometry が求めた法線の信頼度 (Surfel ごと, 0-1)。Super Resolution 後は空
+    normal_confidence: Vec<f32>,
+    // backend 設定と GpuContext (src/backend.rs)
+    compute: backend::Dispatcher,
+
+    // Live NumPy views into `splats` / `surfels` (see array_view.rs)
+    splat_exports: array_view::ExportGuard,
+    surfel_exports: array_view::ExportGuard,
+}
+
+// (N, 3) / (3,) array-like of points (None gives no points)
+#[cfg(feature = "python")]
+fn read_points(obj: Option<&Bound<'_, PyAny>>, name: &str) -> PyResult<Vec<[f32; 3]>> {
+    let Some(obj) = obj else { return Ok(Vec::new()) };
+    Ok(array_view::read_f32(obj, name, None, 3)?.chunks_exact(3).map(|p| [p[0], p[1], p[2]]).collect())
+}
+
+// Buffered output file; open errors surface as GsIOError
+#[cfg(feature = "python")]
+fn create_file(path: &str) -> GsResult<std::io::BufWriter<std::fs::File>> {
+    Ok(std::io::BufWriter::new(std::fs::File::create(path)?))
+}
+
+#[cfg(feature = "python")]
+impl SplatManager {
+    fn from_data(data: SplatData) -> Self {
+        SplatManager {
+            splats: data.splats,
+            sh: data.sh,
+            surfels: Vec::new(),
+            normal_confidence: Vec::new(),
+            compute: Default::default(),
+            splat_exports: Default::default(),
+            surfel_exports: Default::default(),
+        }
+    }
+
+    // compute_geometry の後処理: normal_mode / orient に従って法線を置き換え、信頼度を求める。
+    // 近傍探索は backend に従い (cpu_only なら CPU)、PCA と向きの伝播は CPU で行う
+    fn refine_normals(&mut self, params: &normals::NormalParams, viewpoints: &[[f32; 3]], cpu_only: bool) -> GsResult<()> {
+        let positions: Vec<[f32; 3]> = self.surfels.iter().map(|s| s.pos).collect();
+        let neighbors = match params.neighbor_query()? {
+            Some(query) if cpu_only => Some(knn::search_cpu(self.compute.cpu(), &positions, &positions, &query)),
+            Some(query) => Some(self.compute.run(
+                |gpu| pollster::block_on(gpu.knn(&positions, &positions, query)),
+                |pool| Ok(knn::search_cpu(pool, &positions, &positions, &query)),
+            )?),
+            None => None,
+        };
+        let scales: Vec<[f32; 3]> = self.splats.iter().map(|s| s.scale).collect();
+        self.normal_confidence = normals::estimate(self.compute.cpu(), &mut self.surfels, &scales, neighbors.as_ref(), viewpoints, params);
+        Ok(())
+    }
+
+    // 近傍探索の共通部分: (indices int64 (M, k), distances float32 (M, k))。見つからない分は -1 / inf
+    fn neighbors<'py>(&mut self, points: &Bound<'py, PyAny>, query: knn::KnnQuery, source: &str) -> PyResult<(Bound<'py, PyAny>, Bound<'py, PyAny>)> {
+        let py = points.py();
+        let targets: Vec<[f32; 3]> = match source.parse()? {
+            knn::PointSet::Splats => self.splats.iter().map(|s| s.pos).collect(),
+            knn::PointSet::Surfels => {
+                if self.surfels.is_empty() {
+                    return Err(GsError::invalid("No geometry computed. Run compute_geometry() first.").into());
+                }
+                self.surfels.iter().map(|s| s.pos).collect()
+            }
+        };
+        let queries = read_points(Some(points), "points")?;
+
+        let result = self.compute.run(
+            |gpu| pollster::block_on(gpu.knn(&targets, &queries, query)),
+            |pool| Ok(knn::search_cpu(pool, &targets, &queries, &query)),
+        )?;
+        let indices: Vec<i64> = result.indices.iter().map(|&i| if i == knn::NONE { -1 } else { i as i64 }).collect();
+        Ok((
+            array_view::to_numpy(py, &indices, "int64", queries.len(), result.k)?,
+            array_view::to_numpy(py, &result.distances, "float32", queries.len(), result.k)?,
+        ))
+    }
+
+    // 外れ値判定の共通部分: スプラット中心の近傍探索 (backend に従って GPU / CPU) → 残す点が True の (N,) bool 配列
+    fn outlier_mask<'py>(&mut self, py: Python<'py>, filter: outlier::OutlierFilter) -> PyResult<Bound<'py, PyAny>> {
+        let query = filter.query()?;
+        let positions: Vec<[f32; 3]> = self.splats.iter().map(|s| s.pos).collect();
+        let neighbors = self.compute.run(
+            |gpu| pollster::block_on(gpu.knn(&positions, &positions, query)),
+            |pool| Ok(knn::search_cpu(pool, &positions, &positions, &query)),
+        )?;
+        array_view::mask_to_numpy(py, &filter.keep_mask(self.compute.cpu(), &neighbors))
+    }
 }
 
 #[cfg(feature = "python")]
 #[pymethods]
 impl SplatManager {
+    // 3DGS の PLY / SPZ / .ksplat / .splat を内容から判別して読み込む
+    // quat_order: PLY の rot_0..3 の並び ("wxyz": 公式3DGS学習器 / "xyzw")
+    // value_domain: scale / opacity の保存形式 ("preactivation": log / logit / "activated": 線形)
+    // (quat_order / value_domain は PLY のみに適用。SPZ / .ksplat / .splat は形式で決まっている)
+    // backend: 計算バックエンド (後から manager.backend = "cpu" のように変更可能)
     #[new]
-    fn new(ply_path: String) -> PyResult<Self> {
+    #[pyo3(signature = (ply_path, quat_order="wxyz", value_domain="preactivation", backend="auto"))]
+    fn new(ply_path: String, quat_order: &str, value_domain: &str, backend: &str) -> PyResult<Self> {
+        let options = LoadOptions {
+            quat_order: quat_order.parse()?,
+            value_domain: value_domain.parse()?,
+        };
+        let backend: backend::Backend = backend.parse()?;
         let path = std::path::Path::new(&ply_path);
-        let file = std::fs::File::open(path).map_err(|e| pyo3::exceptions::PyFileNotFoundError::new_err(e.to_string()))?;
-        let mmap = unsafe { memmap2::MmapOptions::new().map(&file).map_err(|e| pyo3::exceptions::PyIOError::new_err(e.to_string()))? };
-
-        let header_end = b"end_header";
-        let offset = mmap.windows(header_end.len())
-            .position(|w| w == header_end)
-            .map(|i| i + header_end.len())
-            .ok_or_else(|| pyo3::exceptions::PyValueError::new_err("Invalid PLY header"))?;
-        
-        let mut cursor = offset;
-        while cursor < mmap.len() && (mmap[cursor] == b'\r' || mmap[cursor] == b'\n') { cursor += 1; }
-        
-        let raw_data = &mmap[cursor..];
-        let struct_size = std::mem::size_of::<RawSplat>();
-        let count = raw_data.len() / struct_size;
-        
-        let raw_splats: &[RawSplat] = bytemuck::cast_slice(&raw_data[..count * struct_size]);
-        let mut splats = Vec::with_capacity(count);
-
-        for raw in raw_splats {
-            splats.push(GaussianSplat {
-                pos: [raw.x, raw.y, raw.z],
-                opacity: raw.opacity,
-                scale: [raw.scale_0, raw.scale_1, raw.scale_2],
-                _pad1: 0.0,
-                rot: [raw.rot_0, raw.rot_1, raw.rot_2, raw.rot_3],
-                sh_dc: [raw.f_dc_0, raw.f_dc_1, raw.f_dc_2],
-                _pad2: 0.0,
-            });
-        }
-        
-        Ok(SplatManager { splats, surfels: Vec::new() })
+        let file = std::fs::File::open(path).map_err(GsError::from)?;
+        let mmap = unsafe { memmap2::MmapOptions::new().map(&file).map_err(GsError::from)? };
+
+        let data = splat::read_splats(&mmap, &options).map_err(|e| {
+            // 3DGS 属性を持たない点群には from_point_cloud を案内する
+            match (e, ply::PlyHeader::parse(&mmap)) {
+                (GsError::PlyFormat { message, property, line }, Ok(header)) if !ply::is_gaussian_ply(&header) => GsError::PlyFormat {
+                    message: format!("{}. Plain point clouds can be loaded with SplatManager.from_point_cloud()", message),
+                    property,
+                    line,
+                },
+                (e, _) => e,
+            }
+        })?;
+        let mut manager = SplatManager::from_data(data);
+        manager.compute.set_backend(backend);
+        Ok(manager)
+    }
+
+    // 3DGS ではない点群 (PCD / PLY, binary・ascii) から生成
+    // xyz / rgb / normal を読み込み、各点に GaussianSplat を合成する
+    // shape: "auto" (法線があれば flat) / "isotropic" (球) / "flat" (法線方向に薄い円盤)
+    // radius: スプラット半径。未指定ならバウンディングボックスから平均点間隔を推定
+    #[staticmethod]
+    #[pyo3(signature = (path, shape="auto", radius=None, opacity=1.0))]
+    fn from_point_cloud(path: String, shape: &str, radius: Option<f32>, opacity: f32) -> PyResult<Self> {
+        let options = pointcloud::SynthesisOptions {
+            shape: shape.parse()?,
+            radius,
+            opacity,
+        };
+        let data = std::fs::read(&path).map_err(GsError::from)?;
+        let cloud = pointcloud::read_point_cloud(&data)?;
+        let data = pointcloud::synthesize_splats(&cloud, &options)?;
+        Ok(SplatManager::from_data(data))
+    }
+
+    // SPZ (gzip 圧縮 + 固定小数点) から生成。SH の高次係数も読み込む
+    #[staticmethod]
+    fn load_spz(path: String) -> PyResult<Self> {
+        let data = std::fs::read(&path).map_err(GsError::from)?;
+        let data = spz::read_spz(&data)?;
+        Ok(SplatManager::from_data(data))
+    }
+
+    // NumPy 配列から直接生成 (PLY 不要)
+    // 既定値は positions / scales / rotations / opacities プロパティの形式 (xyzw, 活性化済み) に合わせてあり、
+    // from_arrays(m.positions, m.scales, m.rotations, m.opacities, sh_dc=m.sh_dc) で往復できる
+    // sh_dc: (N, 3) の SH DC 係数 / colors: (N, 3) の RGB (0-1)。どちらも未指定ならグレー
+    // sh_rest: (N, K, 3) もしくは (N, K*3) の高次SH係数 (K = 3, 8, 15)
+    #[staticmethod]
+    #[pyo3(signature = (positions, scales, rotations, opacities, sh_dc=None, colors=None, sh_rest=None, quat_order="xyzw", value_domain="activated"))]
+    #[allow(clippy::too_many_arguments)]
+    fn from_arrays(
+        positions: &Bound<'_, PyAny>,
+        scales: &Bound<'_, PyAny>,
+        rotations: &Bound<'_, PyAny>,
+        opacities: &Bound<'_, PyAny>,
+        sh_dc: Option<&Bound<'_, PyAny>>,
+        colors: Option<&Bound<'_, PyAny>>,
+        sh_rest: Option<&Bound<'_, PyAny>>,
+        quat_order: &str,
+        value_domain: &str,
+    ) -> PyResult<Self> {
+        let quat_order: QuatOrder = quat_order.parse()?;
+        let value_domain: ValueDomain = value_domain.parse()?;
+
+        let pos = array_view::read_f32(positions, "positions", None, 3)?;
+        let n = pos.len() / 3;
+        let scale = array_view::read_f32(scales, "scales", Some(n), 3)?;
+        let rot = array_view::read_f32(rotations, "rotations", Some(n), 4)?;
+        let opacity = array_view::read_f32(opacities, "opacities", Some(n), 1)?;
+        let dc = match (sh_dc, colors) {
+            (Some(_), Some(_)) => return Err(GsError::invalid("Pass either sh_dc or colors, not both").into()),
+            (Some(dc), None) => array_view::read_f32(dc, "sh_dc", Some(n), 3)?,
+            (None, Some(rgb)) => array_view::read_f32(rgb, "colors", Some(n), 3)?.iter().map(|c| (c - 0.5) / sh::SH_C0).collect(),
+            (None, None) => vec![0.0; n * 3],
+        };
+
+        let sh = match sh_rest {
+            Some(rest) if n > 0 => {
+                let coeffs = array_view::read_f32(rest, "sh_rest", None, n * 3)?;
+                let degree = sh::degree_for_rest_count(coeffs.len() / (n * 3)).ok_or_else(|| GsError::invalid(format!(
+                    "sh_rest must hold 3, 8 or 15 coefficients per channel, got {} values for {} splats", coeffs.len(), n,
+                )))?;
+                ShCoeffs { degree, coeffs }
+            },
+            _ => ShCoeffs::default(),
+        };
+
+        let splats = (0..n).map(|i| GaussianSplat {
+            pos: [pos[i * 3], pos[i * 3 + 1], pos[i * 3 + 2]],
+            opacity: value_domain.activate_opacity(opacity[i]),
+            scale: value_domain.activate_scale([scale[i * 3], scale[i * 3 + 1], scale[i * 3 + 2]]),
+            rot: quat_order.to_xyzw([rot[i * 4], rot[i * 4 + 1], rot[i * 4 + 2], rot[i * 4 + 3]]),
+            sh_dc: [dc[i * 3], dc[i * 3 + 1], dc[i * 3 + 2]],
+            ..Default::default()
+        }).collect();
+
+        Ok(SplatManager::from_data(SplatData { splats, sh }))
     }
 
     // 基本情報
     fn count(&self) -> usize { self.splats.len() }
+    fn sh_degree(&self) -> u32 { self.sh.degree }
 
     // データアクセサ (テスト用)
     fn get_splat_pos(&self, idx: usize) -> PyResult<[f32; 3]> {
         self.splats.get(idx).map(|s| s.pos).ok_or_else(|| pyo3::exceptions::PyIndexError::new_err("Index out of bounds"))
     }
+    fn get_splat_scale(&self, idx: usize) -> PyResult<[f32; 3]> {
+        self.splats.get(idx).map(|s| s.scale).ok_or_else(|| pyo3::exceptions::PyIndexError::new_err("Index out of bounds"))
+    }
+    fn get_splat_opacity(&self, idx: usize) -> PyResult<f32> {
+        self.splats.get(idx).map(|s| s.opacity).ok_or_else(|| pyo3::exceptions::PyIndexError::new_err("Index out of bounds"))
+    }
     fn get_splat_rot(&self, idx: usize) -> PyResult<[f32; 4]> {
         self.splats.get(idx).map(|s| s.rot).ok_or_else(|| pyo3::exceptions::PyIndexError::new_err("Index out of bounds"))
     }
     fn get_splat_sh(&self, idx: usize) -> PyResult<[f32; 3]> {
         self.splats.get(idx).map(|s| s.sh_dc).ok_or_else(|| pyo3::exceptions::PyIndexError::new_err("Index out of bounds"))
     }
+    // 高次SH係数 (係数ごとの RGB インターリーブ)
+    fn get_splat_sh_rest(&self, idx: usize) -> PyResult<Vec<f32>> {
+        if idx >= self.splats.len() { return Err(pyo3::exceptions::PyIndexError::new_err("Index out of bounds")); }
+        Ok(self.sh.splat(idx).to_vec())
+    }
+
+    // Zero-copy NumPy ビュー (読み取り専用, float32)
+    // ビューが残っている間は再計算できない (BufferError)。保持する場合は np.array(...) でコピーする
+    #[getter]
+    fn positions<'py>(slf: &Bound<'py, Self>) -> PyResult<Bound<'py, PyAny>> {
+        let m = slf.borrow();
+        array_view::field_view(slf.as_any(), &m.splats, &m.splat_exports, std::mem::offset_of!(GaussianSplat, pos), 3)
+    }
+    #[getter]
+    fn scales<'py>(slf: &Bound<'py, Self>) -> PyResult<Bound<'py, PyAny>> {
+        let m = slf.borrow();
+        array_view::field_view(slf.as_any(), &m.splats, &m.splat_exports, std::mem::offset_of!(GaussianSplat, scale), 3)
+    }
+    #[getter]
+    fn rotations<'py>(slf: &Bound<'py, Self>) -> PyResult<Bound<'py, PyAny>> {
+        let m = slf.borrow();
+        array_view::field_view(slf.as_any(), &m.splats, &m.splat_exports, std::mem::offset_of!(GaussianSplat, rot), 4)
+    }
+    #[getter]
+    fn opacities<'py>(slf: &Bound<'py, Self>) -> PyResult<Bound<'py, PyAny>> {
+        let m = slf.borrow();
+        array_view::field_view(slf.as_any(), &m.splats, &m.splat_exports, std::mem::offset_of!(GaussianSplat, opacity), 1)
+    }
+    #[getter]
+    fn sh_dc<'py>(slf: &Bound<'py, Self>) -> PyResult<Bound<'py, PyAny>> {
+        let m = slf.borrow();
+        array_view::field_view(slf.as_any(), &m.splats, &m.splat_exports, std::mem::offset_of!(GaussianSplat, sh_dc), 3)
+    }
+    #[getter]
+    fn surfel_positions<'py>(slf: &Bound<'py, Self>) -> PyResult<Bound<'py, PyAny>> {
+        let m = slf.borrow();
+        array_view::field_view(slf.as_any(), &m.surfels, &m.surfel_exports, std::mem::offset_of!(Surfel, pos), 3)
+    }
+    #[getter]
+    fn normals<'py>(slf: &Bound<'py, Self>) -> PyResult<Bound<'py, PyAny>> {
+        let m = slf.borrow();
+        array_view::field_view(slf.as_any(), &m.surfels, &m.surfel_exports, std::mem::offset_of!(Surfel, normal), 3)
+    }
+    #[getter]
+    fn colors<'py>(slf: &Bound<'py, Self>) -> PyResult<Bound<'py, PyAny>> {
+        let m = slf.borrow();
+        array_view::field_view(slf.as_any(), &m.surfels, &m.surfel_exports, std::mem::offset_of!(Surfel, color), 3)
+    }
+    #[getter]
+    fn normal_confidence<'py>(slf: &Bound<'py, Self>) -> PyResult<Bound<'py, PyAny>> {
+        let m = slf.borrow();
+        array_view::field_view(slf.as_any(), &m.normal_confidence, &m.surfel_exports, 0, 1)
+    }
 
-    // GPU計算
-    fn compute_geometry(&mut self) -> PyResult<usize> {
+    // 計算バックエンド: "auto" (既定: GPU が無い・デバイスロスト・GPU バッファに収まらない時は CPU で計算) / "gpu" / "cpu"
+    #[getter]
+    fn backend(&self) -> &'static str {
+        self.compute.backend().as_str()
+    }
+    #[setter]
+    fn set_backend(&mut self, backend: &str) -> PyResult<()> {
+        self.compute.set_backend(backend.parse()?);
+        Ok(())
+    }
+    // CPU バックエンドのスレッド数。None (既定) は全コア
+    #[getter]
+    fn cpu_threads(&self) -> Option<usize> {
+        self.compute.cpu().threads()
+    }
+    #[setter]
+    fn set_cpu_threads(&mut self, threads: Option<usize>) -> PyResult<()> {
+        self.compute.cpu_mut().set_threads(threads)?;
+        Ok(())
+    }
+    // 直前の compute_* を実際に実行したバックエンド ("gpu" / "cpu")。未実行なら None
+    #[getter]
+    fn last_backend(&self) -> Option<&'static str> {
+        self.compute.last_backend().map(backend::Backend::as_str)
+    }
+
+    // 幾何計算 (backend に従って GPU / CPU)
+    // view_dir: カメラから見た視線方向。指定時は高次SHを評価し、未指定時はDC項のみ
+    // normal_mode: "axis" (スプラットの最も薄い軸) / "pca" (k 近傍の PCA を異方性で重み付けして混ぜる)
+    // orient: 法線の符号の揃え方。"none" / "viewpoint" (最も近い viewpoints の方を向く) / "mst" (近傍グラフで伝播)
+    // viewpoints: (3,) または (M, 3) のカメラ位置 (軌跡)。"mst" では各連結成分の開始点の向きに使う
+    #[pyo3(signature = (view_dir=None, normal_mode="axis", k=normals::DEFAULT_K, orient="none", viewpoints=None))]
+    fn compute_geometry(
+        &mut self,
+        view_dir: Option<[f32; 3]>,
+        normal_mode: &str,
+        k: usize,
+        orient: &str,
+        viewpoints: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<usize> {
+        self.surfel_exports.check("surfels")?;
+        let viewpoints = read_points(viewpoints, "viewpoints")?;
+        let normal_params = normals::NormalParams::new(normal_mode.parse()?, k, orient.parse()?, !viewpoints.is_empty())?;
         if self.splats.is_empty() { return Ok(0); }
-        match pollster::block_on(run_compute_shader_headless(&self.splats)) {
-            Ok(res) => {
-                self.surfels = res;
-                Ok(self.surfels.len())
-            },
-            Err(e) => Err(pyo3::exceptions::PyRuntimeError::new_err(e)),
-        }
+        let view_dir = normalize_view_dir(view_dir)?;
+        let params = match view_dir {
+            Some(d) => GeometryParams { view_dir: d, sh_degree: self.sh.degree, view_mode: VIEW_MODE_DIRECTION, ..Default::default() },
+            None => GeometryParams { view_mode: VIEW_MODE_DC, ..Default::default() },
+        };
+        self.surfels = self.compute.run(
+            |gpu| pollster::block_on(gpu.compute_geometry(&self.splats, &self.sh, params)),
+            |pool| Ok(cpu::geometry(pool, &self.splats, &self.sh, view_dir)),
+        )?;
+        self.refine_normals(&normal_params, &viewpoints, false)?;
+        Ok(self.surfels.len())
     }
 
-    // CPU計算 (Fallback)
-    fn compute_geometry_cpu(&mut self) -> PyResult<usize> {
-        self.surfels.clear();
-        for s in &self.splats {
-            let rgb = sh_to_rgb_cpu(s.sh_dc);
-            let normal = compute_normal_cpu(s.rot, s.scale);
-            
-            self.surfels.push(Surfel {
-                pos: s.pos, _pad0: 0.0,
-                color: rgb, _pad1: 0.0,
-                normal, _pad2: 0.0,
-            });
-        }
+    // CPU計算 (backend 設定に関係なく CPU で実行)
+    #[pyo3(signature = (view_dir=None, normal_mode="axis", k=normals::DEFAULT_K, orient="none", viewpoints=None))]
+    fn compute_geometry_cpu(
+        &mut self,
+        view_dir: Option<[f32; 3]>,
+        normal_mode: &str,
+        k: usize,
+        orient: &str,
+        viewpoints: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<usize> {
+        self.surfel_exports.check("surfels")?;
+        let viewpoints = read_points(viewpoints, "viewpoints")?;
+        let normal_params = normals::NormalParams::new(normal_mode.parse()?, k, orient.parse()?, !viewpoints.is_empty())?;
+        let view_dir = normalize_view_dir(view_dir)?;
+        self.surfels = cpu::geometry(self.compute.cpu(), &self.splats, &self.sh, view_dir);
+        self.refine_normals(&normal_params, &viewpoints, true)?;
+        self.compute.record(backend::Backend::Cpu);
         Ok(self.surfels.len())
     }
 
-    // Super Resolution
-    fn compute_super_resolution(&mut self, factor: u32) -> PyResult<usize> {
+    // Super Resolution (backend に従って GPU / CPU)
+    // 有効なスプラット 1 つにつき接平面上に factor 個の Surfel を生成し、実際の Surfel 数を返す
+    // min_opacity: これ未満のスプラットを除く / max_aspect_ratio: 最小/最大スケール比がこれを超える (球体に近い) ものを除く
+    // max_scale: 最大スケールがこれを超えるものを除く (None: 無制限) / sample_radius: 子を置く円盤の半径 (sigma 単位)
+    // density: 目標の面密度 (点 / m^2)。指定時は子の数を接平面の面積に比例させ、factor はスプラットあたりの上限になる
+    // sampler: "spiral" (決定的) / "gaussian" (スプラットの 2D ガウシアンから乱数、seed で再現可能。truncate: 打ち切り半径 [sigma])
+    #[pyo3(signature = (
+        factor,
+        min_opacity=sr::DEFAULT_MIN_OPACITY,
+        max_aspect_ratio=sr::DEFAULT_MAX_ASPECT_RATIO,
+        max_scale=None,
+        sample_radius=sr::DEFAULT_SAMPLE_RADIUS,
+        density=None,
+        sampler="spiral",
+        seed=0,
+        truncate=None,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn compute_super_resolution(
+        &mut self,
+        factor: u32,
+        min_opacity: f32,
+        max_aspect_ratio: f32,
+        max_scale: Option<f32>,
+        sample_radius: f32,
+        density: Option<f32>,
+        sampler: &str,
+        seed: u32,
+        truncate: Option<f32>,
+    ) -> PyResult<usize> {
+        self.surfel_exports.check("surfels")?;
+        let params = sr::SrParams::new(factor, min_opacity, max_aspect_ratio, max_scale, sample_radius)?
+            .with_density(density)?
+            .with_sampler(sampler.parse()?, seed, truncate)?;
         if self.splats.is_empty() { return Ok(0); }
-        if factor < 1 { return Err(pyo3::exceptions::PyValueError::new_err("Factor must be >= 1")); }
-        
-        match pollster::block_on(run_sr_headless(&self.splats, factor)) {
-             Ok(res) => {
-                self.surfels = res;
-                Ok(self.surfels.len())
-            },
-            Err(e) => Err(pyo3::exceptions::PyRuntimeError::new_err(e)),
-        }
+
+        self.surfels = self.compute.run(
+            |gpu| pollster::block_on(gpu.super_resolution(&self.splats, params)),
+            |pool| sr::run_cpu(pool, &self.splats, &params),
+        )?;
+        self.normal_confidence.clear();
+        Ok(self.surfels.len())
     }
 
     // 結果アクセサ
@@ -347,94 +800,236 @@ impl SplatManager {
     fn get_surfel_normal(&self, idx: usize) -> PyResult<[f32; 3]> {
         self.surfels.get(idx).map(|s| s.normal).ok_or_else(|| pyo3::exceptions::PyIndexError::new_err("Index out of bounds"))
     }
+    fn get_surfel_confidence(&self, idx: usize) -> PyResult<f32> {
+        self.normal_confidence.get(idx).copied().ok_or_else(|| pyo3::exceptions::PyIndexError::new_err("Index out of bounds"))
+    }
+
+    // ------------------------------------------------------------------------
+    // 法線を観測したカメラの方へ向ける (backend に従って GPU / CPU)。戻り値は反転した法線の数
+    // camera_positions: (M, 3) キーフレームのカメラ位置
+    // observers: Surfel ごとの観測キーフレームのインデックス (N,) または (N, K)。-1 は不明 (K 列の埋め草にも使う)。
+    //            行の中で最も多く現れるキーフレーム、1 つも無ければ最も近いカメラを向く
+    #[pyo3(signature = (camera_positions, observers=None))]
+    fn orient_normals(&mut self, camera_positions: &Bound<'_, PyAny>, observers: Option<&Bound<'_, PyAny>>) -> PyResult<usize> {
+        self.surfel_exports.check("surfels")?;
+        if self.surfels.is_empty() {
+            return Err(GsError::invalid("No geometry computed. Run compute_geometry() first.").into());
+        }
+        let cameras = read_points(Some(camera_positions), "camera_positions")?;
+        if cameras.is_empty() {
+            return Err(GsError::invalid("camera_positions must contain at least one camera").into());
+        }
+        let observers = match observers {
+            Some(obj) => {
+                let (values, width) = array_view::read_index_rows(obj, "observers", self.surfels.len())?;
+                normals::Observers::new(&values, width, cameras.len())?
+            }
+            None => normals::Observers::default(),
+        };
+        let oriented = self.compute.run(
+            |gpu| pollster::block_on(gpu.orient_normals(&self.surfels, &cameras, &observers)),
+            |pool| {
+                let mut surfels = self.surfels.clone();
+                normals::orient_to_cameras(pool, &mut surfels, &cameras, &observers);
+                Ok(surfels)
+            },
+        )?;
+        let flipped = self.surfels.iter().zip(&oriented).filter(|(a, b)| a.normal != b.normal).count();
+        self.surfels = oriented;
+        Ok(flipped)
+    }
+
+    // ボクセルグリッドで間引いた新しい SplatManager を返す (backend に従って GPU ハッシュ表 / CPU)
+    // スプラットと (計算済みなら) Surfel をそれぞれ voxel_size の格子ごとに 1 点へまとめる。順序は各ボクセルの最初の点の順
+    // mode: "centroid" (平均) / "opacity_weighted" (不透明度で重み付けした平均) / "nearest" (平均位置に最も近い点を残す)
+    //   平均は位置・色 (SH)・不透明度・半径・曲率、法線は向きを揃えて平均し正規化。回転・スケールは平均位置に最も近い点から取る
+    #[pyo3(signature = (voxel_size, mode="centroid"))]
+    fn voxel_downsample(&mut self, voxel_size: f32, mode: &str) -> PyResult<SplatManager> {
+        let mode: voxel::VoxelMode = mode.parse()?;
+        let inv_size = voxel::inverse_size(voxel_size)?;
+
+        let splat_table = voxel::VoxelTable::splats(&self.splats, &self.sh, mode);
+        let surfel_table = voxel::VoxelTable::surfels(&self.surfels, mode);
+        let (splat_parts, surfel_parts) = self.compute.run(
+            |gpu| pollster::block_on(async {
+                Ok((gpu.voxel_merge(&splat_table, inv_size).await?, gpu.voxel_merge(&surfel_table, inv_size).await?))
+            }),
+            |pool| Ok((
+                vec![(voxel::merge_cpu(pool, &splat_table, inv_size), 0)],
+                vec![(voxel::merge_cpu(pool, &surfel_table, inv_size), 0)],
+            )),
+        )?;
+        let pool = self.compute.cpu();
+        let splat_merged = voxel::Merged::combine(pool, splat_parts, &splat_table, inv_size);
+        let surfel_merged = voxel::Merged::combine(pool, surfel_parts, &surfel_table, inv_size);
+
+        let (splats, sh) = voxel::merged_splats(&self.splats, &self.sh, &splat_merged, mode);
+        let mut downsampled = SplatManager::from_data(SplatData { splats, sh });
+        downsampled.surfels = voxel::merged_surfels(&self.surfels, &surfel_merged, mode);
+        downsampled.compute.set_backend(self.compute.backend());
+        downsampled.compute.cpu_mut().set_threads(self.compute.cpu().threads())?;
+        Ok(downsampled)
+    }
+
+    // ------------------------------------------------------------------------
+    // 外れ値 (floater) の除去。*_mask は残すスプラットが True の bool 配列 (N,) を返し、filter_splats で適用する
+    // (compute_geometry の前に使う想定。近傍探索は backend に従って GPU / CPU)
+    // ------------------------------------------------------------------------
+
+    // PCL の StatisticalOutlierRemoval: 自身を除く k 近傍までの平均距離が 全体の平均 + std_ratio * 標準偏差 を超えるものを除く
+    #[pyo3(signature = (k=outlier::DEFAULT_K, std_ratio=outlier::DEFAULT_STD_RATIO))]
+    fn statistical_outlier_mask<'py>(&mut self, py: Python<'py>, k: usize, std_ratio: f32) -> PyResult<Bound<'py, PyAny>> {
+        self.outlier_mask(py, outlier::OutlierFilter::statistical(k, std_ratio)?)
+    }
+
+    // PCL の RadiusOutlierRemoval: 半径 radius 以内に自身以外のスプラットが min_neighbors 個未満のものを除く
+    fn radius_outlier_mask<'py>(&mut self, py: Python<'py>, radius: f32, min_neighbors: usize) -> PyResult<Bound<'py, PyAny>> {
+        self.outlier_mask(py, outlier::OutlierFilter::radius(radius, min_neighbors)?)
+    }
+
+    // mask (N,) が True のスプラット (と高次SH) だけを順序を保って残し、取り除いた数を返す。
+    // Surfel と法線の信頼度はスプラットと対応しなくなるので破棄する (compute_geometry で再計算)
+    fn filter_splats(&mut self, mask: &Bound<'_, PyAny>) -> PyResult<usize> {
+        let keep = array_view::read_mask(mask, "mask", self.splats.len())?;
+        self.splat_exports.check("splats")?;
+        self.surfel_exports.check("surfels")?;
+
+        let stride = self.sh.stride();
+        let mut coeffs = Vec::with_capacity(self.sh.coeffs.len());
+        for (i, _) in keep.iter().enumerate().filter(|&(_, &k)| k) {
+            if stride > 0 { coeffs.extend_from_slice(self.sh.splat(i)); }
+        }
+        let before = self.splats.len();
+        let mut flags = keep.iter();
+        self.splats.retain(|_| *flags.next().unwrap());
+        self.sh.coeffs = coeffs;
+        self.surfels.clear();
+        self.normal_confidence.clear();
+        Ok(before - self.splats.len())
+    }
+
+    // ------------------------------------------------------------------------
+    // 近傍探索 (backend に従って GPU ハッシュグリッド / CPU KD-tree)
+    // ------------------------------------------------------------------------
+
+    // points (M, 3) それぞれの k 近傍。source: "splats" (スプラット中心) / "surfels" (Surfel の位置)
+    // 戻り値は (indices, distances)。距離順で、点が k 個に満たない分は -1 / inf。クエリと同じ位置の点自身も含む
+    #[pyo3(signature = (points, k, source="splats"))]
+    fn knn<'py>(&mut self, points: &Bound<'py, PyAny>, k: usize, source: &str) -> PyResult<(Bound<'py, PyAny>, Bound<'py, PyAny>)> {
+        self.neighbors(points, knn::KnnQuery::nearest(k)?, source)
+    }
+
+    // points (M, 3) それぞれから radius 以内の点を近い順に最大 max_neighbors 個 (形は (M, max_neighbors)、足りない分は -1 / inf)
+    #[pyo3(signature = (points, radius, max_neighbors=64, source="splats"))]
+    fn radius_search<'py>(&mut self, points: &Bound<'py, PyAny>, radius: f32, max_neighbors: usize, source: &str) -> PyResult<(Bound<'py, PyAny>, Bound<'py, PyAny>)> {
+        self.neighbors(points, knn::KnnQuery::within(radius, max_neighbors)?, source)
+    }
 
     // ------------------------------------------------------------------------
     // Export 機能 (Python)
     // ------------------------------------------------------------------------
 
-    fn save_ply(&self, path: String) -> PyResult<()> {
-        use std::io::Write;
-        
+    // Surfel 点群 (xyz / rgb / normal)。encoding: "binary" (binary_little_endian) / "ascii"
+    #[pyo3(signature = (path, encoding="binary"))]
+    fn save_ply(&self, path: String, encoding: &str) -> PyResult<()> {
+        let encoding: ply::PlyEncoding = encoding.parse()?;
         if self.surfels.is_empty() {
-            return Err(pyo3::exceptions::PyValueError::new_err("No geometry computed. Run compute_geometry() first."));
+            return Err(GsError::invalid("No geometry computed. Run compute_geometry() first.").into());
         }
 
-        let mut file = std::fs::File::create(&path).map_err(|e| pyo3::exceptions::PyIOError::new_err(e.to_string()))?;
-        
-        // Header
-        writeln!(file, "ply").unwrap();
-        writeln!(file, "format ascii 1.0").unwrap();
-        writeln!(file, "element vertex {}", self.surfels.len()).unwrap();
-        writeln!(file, "property float x").unwrap();
-        writeln!(file, "property float y").unwrap();
-        writeln!(file, "property float z").unwrap();
-        writeln!(file, "property uchar red").unwrap();
-        writeln!(file, "property uchar green").unwrap();
-        writeln!(file, "property uchar blue").unwrap();
-        writeln!(file, "property float nx").unwrap();
-        writeln!(file, "property float ny").unwrap();
-        writeln!(file, "property float nz").unwrap();
-        writeln!(file, "end_header").unwrap();
-
-        // Data
-        for s in &self.surfels {
-            let r = (s.color[0] * 255.0) as u8;
-            let g = (s.color[1] * 255.0) as u8;
-            let b = (s.color[2] * 255.0) as u8;
-            
-            writeln!(file, "{} {} {} {} {} {} {} {} {}", 
-                s.pos[0], s.pos[1], s.pos[2],
-                r, g, b,
-                s.normal[0], s.normal[1], s.normal[2]
-            ).unwrap();
-        }
+        ply::write_surfel_ply(&mut create_file(&path)?, &self.surfels, encoding).map_err(GsError::from)?;
+        Ok(())
+    }
 
+    // 3DGS 形式 (学習器と同じプロパティ名: log scale / logit opacity / wxyz)。
+    // SplatManager(path) や一般的な 3DGS ビューアでそのまま開ける
+    #[pyo3(signature = (path, encoding="binary"))]
+    fn save_splat_ply(&self, path: String, encoding: &str) -> PyResult<()> {
+        let encoding: ply::PlyEncoding = encoding.parse()?;
+        ply::write_gaussian_ply(&mut create_file(&path)?, &self.splats, &self.sh, encoding).map_err(GsError::from)?;
         Ok(())
     }
 
-    fn save_pcd(&self, path: String) -> PyResult<()> {
-        use std::io::Write;
+    // SPZ v3 (PLY の約 1/10)。fractional_bits: 位置の固定小数点の小数部ビット数 (0 - 22)
+    // 既定の 12 bit は約 0.24 mm 刻み・±2048 の範囲。広いシーンではビット数を減らす
+    #[pyo3(signature = (path, fractional_bits=spz::DEFAULT_FRACTIONAL_BITS))]
+    fn save_spz(&self, path: String, fractional_bits: u8) -> PyResult<()> {
+        // 範囲外の座標はファイルを作る前に検出する
+        let mut buffer = Vec::new();
+        spz::write_spz(&mut buffer, &self.splats, &self.sh, fractional_bits)?;
+        std::fs::write(&path, buffer).map_err(GsError::from)?;
+        Ok(())
+    }
 
-        if self.surfels.is_empty() {
-            return Err(pyo3::exceptions::PyValueError::new_err("No geometry computed. Run compute_geometry() first."));
+    // antimatter15 .splat (32 バイト / スプラット)。高次SHは保存されない
+    fn save_splat(&self, path: String) -> PyResult<()> {
+        splat::write_splat(&mut create_file(&path)?, &self.splats).map_err(GsError::from)?;
+        Ok(())
+    }
+
+    // GaussianSplats3D .ksplat。compression_level: 0 (f32) / 1 (f16, 既定) / 2 (f16 + 8bit SH)
+    // level 1 以上はバケット単位に並べ替えるため、読み戻したスプラットの順序は変わる
+    #[pyo3(signature = (path, compression_level=1))]
+    fn save_ksplat(&self, path: String, compression_level: u32) -> PyResult<()> {
+        if compression_level > ksplat::MAX_COMPRESSION_LEVEL {
+            return Err(GsError::invalid(format!(
+                "compression_level must be 0 - {}, got {}", ksplat::MAX_COMPRESSION_LEVEL, compression_level,
+            )).into());
         }
+        ksplat::write_ksplat(&mut create_file(&path)?, &self.splats, &self.sh, compression_level)?;
+        Ok(())
+    }
 
-        let mut file = std::fs::File::create(&path).map_err(|e| pyo3::exceptions::PyIOError::new_err(e.to_string()))?;
-
-        // PCD Header (ASCII v0.7)
-        // Note: Using separate r g b fields for simplicity in ASCII
-        writeln!(file, "# .PCD v0.7 - Data generated by gs-slam-core").unwrap();
-        writeln!(file, "VERSION 0.7").unwrap();
-        writeln!(file, "FIELDS x y z r g b normal_x normal_y normal_z").unwrap();
-        writeln!(file, "SIZE 4 4 4 1 1 1 4 4 4").unwrap();
-        writeln!(file, "TYPE F F F U U U F F F").unwrap();
-        writeln!(file, "COUNT 1 1 1 1 1 1 1 1 1").unwrap();
-        writeln!(file, "WIDTH {}", self.surfels.len()).unwrap();
-        writeln!(file, "HEIGHT 1").unwrap();
-        writeln!(file, "VIEWPOINT 0 0 0 1 0 0 0").unwrap();
-        writeln!(file, "POINTS {}", self.surfels.len()).unwrap();
-        writeln!(file, "DATA ascii").unwrap();
-
-        for s in &self.surfels {
-            let r = (s.color[0] * 255.0) as u8;
-            let g = (s.color[1] * 255.0) as u8;
-            let b = (s.color[2] * 255.0) as u8;
-
-            writeln!(file, "{} {} {} {} {} {} {} {} {}", 
-                s.pos[0], s.pos[1], s.pos[2],
-                r, g, b,
-                s.normal[0], s.normal[1], s.normal[2]
-            ).unwrap();
+    // PCL 形式 (packed rgb)。encoding: "binary" / "binary_compressed" (LZF) / "ascii"
+    // extra_fields: ["opacity", "curvature", "radius"] の任意の組み合わせ
+    #[pyo3(signature = (path, encoding="binary", extra_fields=None))]
+    fn save_pcd(&self, path: String, encoding: &str, extra_fields: Option<Vec<String>>) -> PyResult<()> {
+        let encoding: pcd::PcdEncoding = encoding.parse()?;
+        let extras = extra_fields.unwrap_or_default().iter()
+            .map(|f| f.parse::<pcd::PcdExtraField>())
+            .collect::<GsResult<Vec<_>>>()?;
+        if self.surfels.is_empty() {
+            return Err(GsError::invalid("No geometry computed. Run compute_geometry() first.").into());
         }
 
+        pcd::write_surfel_pcd(&mut create_file(&path)?, &self.surfels, &extras, encoding).map_err(GsError::from)?;
         Ok(())
     }
 }
 
+// PCD ファイルをフィールド名 → 値のリストの dict として読み込む (検証用)
+// packed rgb / rgba は [r, g, b] (0-255) に展開する
+#[cfg(feature = "python")]
+#[pyfunction]
+fn read_pcd(py: Python<'_>, path: String) -> PyResult<Py<pyo3::types::PyDict>> {
+    let data = std::fs::read(&path).map_err(GsError::from)?;
+    let cloud = pcd::read_pcd(&data)?;
+
+    let dict = pyo3::types::PyDict::new(py);
+    let colors = cloud.rgb();
+    for f in &cloud.fields {
+        match &colors {
+            Some(rgb) if f.name == "rgb" || f.name == "rgba" => dict.set_item(&f.name, rgb)?,
+            _ => {
+                let values = cloud.field(&f.name).unwrap_or_default();
+                if f.count == 1 {
+                    dict.set_item(&f.name, values)?;
+                } else {
+                    let rows: Vec<&[f32]> = values.chunks(f.count).collect();
+                    dict.set_item(&f.name, rows)?;
+                }
+            }
+        }
+    }
+    Ok(dict.unbind())
+}
+
 #[cfg(feature = "python")]
 #[pymodule]
 fn gs_slam_core(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<SplatManager>()?;
+    m.add_function(wrap_pyfunction!(read_pcd, m)?)?;
+    error::register_exceptions(m)?;
     Ok(())
 }
 
@@ -490,11 +1085,15 @@ impl CameraController {
         self.distance = self.distance.clamp(0.1, 100.0);
     }
 
-    fn build_matrices(&self) -> (na::Matrix4<f32>, na::Matrix4<f32>) {
+    fn eye(&self) -> na::Point3<f32> {
         let x = self.distance * self.yaw.cos() * self.pitch.cos();
         let y = self.distance * self.pitch.sin();
         let z = self.distance * self.yaw.sin() * self.pitch.cos();
-        let eye = self.target + na::Vector3::new(x, y, z);
+        self.target + na::Vector3::new(x, y, z)
+    }
+
+    fn build_matrices(&self) -> (na::Matrix4<f32>, na::Matrix4<f32>) {
+        let eye = self.eye();
         let up = na::Vector3::y();
         let view = na::Matrix4::look_at_rh(&eye, &self.target, &up);
         let aspect = self.width / self.height;
@@ -545,20 +1144,28 @@ pub struct WasmViewer {
     config: wgpu::SurfaceConfiguration,
     render_pipeline: wgpu::RenderPipeline,
 
-    compute_pipeline: wgpu::ComputePipeline,
+    geometry_pipeline: geometry::GeometryPipeline,
     sr_pipeline: Option<sr::SuperResolutionPipeline>, 
 
-    bg_compute: Option<wgpu::BindGroup>,
+    // One bind group per chunk (binding ranges over the shared input / output buffers)
+    bg_compute: Vec<(wgpu::BindGroup, u32)>,
+    geometry_param_buffer: wgpu::Buffer,
+    view_dependent: bool,
+    // 視点依存カラーを無効にした直後、次の render で DC カラーに一度だけ戻す
+    restore_dc_colors: bool,
+    sr_active: bool,
     bg_render: Option<wgpu::BindGroup>,
-    bgl_compute: wgpu::BindGroupLayout,
     bgl_render: wgpu::BindGroupLayout,
     uniform_buffer: wgpu::Buffer,
     vertex_buffer: Option<wgpu::Buffer>,
     num_vertices: u32,
+    // SR 結果を描くときの DrawIndirectArgs バッファとその数 (None なら num_vertices を描く)
+    sr_draw_args: Option<(wgpu::Buffer, u32)>,
     camera: Rc<RefCell<CameraController>>,
     display_mode: u32,
     _closures: Vec<wasm_bindgen::JsValue>,
     splats: Vec<GaussianSplat>, // Added for Export
+    sh: ShCoeffs,
 }
 
 #[cfg(feature = "wasm")]
@@ -571,30 +1178,32 @@ impl WasmViewer {
         let window = web_sys::window().unwrap();
         let document = window.document().unwrap();
         let canvas = document.get_element_by_id(canvas_id)
-            .ok_or("Canvas not found")?
-            .dyn_into::<HtmlCanvasElement>()?;
+            .ok_or_else(|| GsError::invalid(format!("Canvas '{}' not found", canvas_id)))?
+            .dyn_into::<HtmlCanvasElement>()
+            .map_err(|_| GsError::invalid(format!("Element '{}' is not a canvas", canvas_id)))?;
 
+        // WebGPU / WebGL2 が使えない環境は GpuUnavailableError で reject する
         let instance = wgpu::Instance::default();
         let surface = instance.create_surface(wgpu::SurfaceTarget::Canvas(canvas.clone()))
-            .map_err(|e| e.to_string())?;
+            .map_err(|e| GsError::GpuAdapterUnavailable(format!("Failed to create surface: {}", e)))?;
         
         let adapter = instance.request_adapter(&wgpu::RequestAdapterOptions {
             power_preference: wgpu::PowerPreference::HighPerformance,
             compatible_surface: Some(&surface),
             force_fallback_adapter: false,
-        }).await.ok_or("No GPU adapter")?;
+        }).await.ok_or_else(|| GsError::GpuAdapterUnavailable("No GPU adapter found".to_string()))?;
 
         let (device, queue) = adapter.request_device(&wgpu::DeviceDescriptor {
             label: None,
             required_features: wgpu::Features::empty(),
             required_limits: wgpu::Limits::downlevel_webgl2_defaults(),
             memory_hints: wgpu::MemoryHints::default(),
-        }, None).await.map_err(|e| e.to_string())?;
+        }, None).await.map_err(|e| GsError::GpuAdapterUnavailable(format!("Failed to create device: {}", e)))?;
 
         let width = canvas.width();
         let height = canvas.height();
         let config = surface.get_default_config(&adapter, width, height)
-            .ok_or("Surface config failed")?;
+            .ok_or_else(|| GsError::GpuAdapterUnavailable("The adapter cannot present to this canvas".to_string()))?;
         surface.configure(&device, &config);
 
         let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
@@ -602,19 +1211,10 @@ impl WasmViewer {
             source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("shader.wgsl"))),
         });
 
-        let bgl_compute = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("Compute Layout"),
-            entries: &[
-                wgpu::BindGroupLayoutEntry { binding: 0, visibility: wgpu::ShaderStages::COMPUTE, ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Storage { read_only: true }, has_dynamic_offset: false, min_binding_size: None }, count: None },
-                wgpu::BindGroupLayoutEntry { binding: 1, visibility: wgpu::ShaderStages::COMPUTE, ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Storage { read_only: false }, has_dynamic_offset: false, min_binding_size: None }, count: None },
-            ],
-        });
-        let pl_compute = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Compute PL"), bind_group_layouts: &[&bgl_compute], push_constant_ranges: &[],
-        });
-        let compute_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-            label: Some("Compute Pipeline"), layout: Some(&pl_compute), module: &shader, entry_point: Some("compute_main"),
-            compilation_options: Default::default(), cache: None,
+        let geometry_pipeline = geometry::GeometryPipeline::new(&device);
+        let geometry_param_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Geometry Params"), size: std::mem::size_of::<GeometryParams>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST, mapped_at_creation: false,
         });
 
         // Setup SR Pipeline
@@ -721,80 +1321,144 @@ impl WasmViewer {
         }
 
         Ok(Self {
-            device, queue, surface, config, render_pipeline, compute_pipeline,
+            device, queue, surface, config, render_pipeline, geometry_pipeline,
             sr_pipeline, 
-            bg_compute: None, bg_render, bgl_compute, bgl_render,
-            uniform_buffer, vertex_buffer: None, num_vertices: 0,
+            bg_compute: Vec::new(), geometry_param_buffer, view_dependent: false, restore_dc_colors: false, sr_active: false,
+            bg_render, bgl_render,
+            uniform_buffer, vertex_buffer: None, num_vertices: 0, sr_draw_args: None,
             camera, display_mode: 0, _closures: closures,
             splats: Vec::new(),
+            sh: ShCoeffs::default(),
         })
     }
 
-    pub fn load_data(&mut self, data: &[u8]) {
-        let header_end = b"end_header";
-        let offset = data.windows(header_end.len()).position(|w| w == header_end).map(|i| i + header_end.len());
-        if let Some(mut cursor) = offset {
-            while cursor < data.len() && (data[cursor] == b'\r' || data[cursor] == b'\n') { cursor += 1; }
-            let raw_data = &data[cursor..];
-            let struct_size = std::mem::size_of::<RawSplat>();
-            let count = raw_data.len() / struct_size;
-            if count == 0 { return; }
-            
-            let raw_splats: &[RawSplat] = bytemuck::cast_slice(&raw_data[..count*struct_size]);
-            let mut splats = Vec::with_capacity(count);
-            for raw in raw_splats {
-                splats.push(GaussianSplat {
-                    pos: [raw.x, raw.y, raw.z], opacity: raw.opacity,
-                    scale: [raw.scale_0, raw.scale_1, raw.scale_2], _pad1: 0.0,
-                    rot: [raw.rot_0, raw.rot_1, raw.rot_2, raw.rot_3],
-                    sh_dc: [raw.f_dc_0, raw.f_dc_1, raw.f_dc_2], _pad2: 0.0,
-                });
-            }
+    // quat_order: "wxyz" (default) or "xyzw"
+    // value_domain: "preactivation" (default, log-scale / logit-opacity) or "activated"
+    // 失敗時は name 付きの Error を投げる (PlyFormatError / FormatError / InvalidParameterError など)
+    pub fn load_data(&mut self, data: &[u8], quat_order: Option<String>, value_domain: Option<String>) -> Result<(), JsValue> {
+        let options = LoadOptions {
+            quat_order: quat_order.as_deref().map(str::parse).transpose()?.unwrap_or_default(),
+            value_domain: value_domain.as_deref().map(str::parse).transpose()?.unwrap_or_default(),
+        };
+        // 3DGS の PLY / SPZ / .ksplat / .splat 以外 (PCD / 点群 PLY) は点ごとにスプラットを合成する
+        let SplatData { splats, sh } = pointcloud::load_splats(data, &options, &Default::default())?;
+        let count = splats.len();
+        if count == 0 {
+            log::warn!("The file contains no splats.");
+            return Ok(());
+        }
 
-            // Save splats for export functionality
-            self.splats = splats.clone();
+        let limits = self.device.limits();
+        let sh_stride = sh.stride() as u64;
+        check_buffer_size(&limits, "Input", count, std::mem::size_of::<GaussianSplat>() as u64)?;
+        check_buffer_size(&limits, "Output", count, std::mem::size_of::<Surfel>() as u64)?;
+        check_buffer_size(&limits, "SH", count, sh_stride * 4)?;
+        let chunk_len = max_chunk_len(&limits, &[64, 48, sh_stride * 4], 1)?;
 
-            let input_buf = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Input"), contents: bytemuck::cast_slice(&splats), usage: wgpu::BufferUsages::STORAGE,
-            });
-            let output_size = (count * std::mem::size_of::<Surfel>()) as u64;
-            let output_buf = self.device.create_buffer(&wgpu::BufferDescriptor {
-                label: Some("Output"), size: output_size, usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX, mapped_at_creation: false,
-            });
+        // Save splats for export functionality
+        self.splats = splats.clone();
 
-            let bg_compute = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
-                label: Some("Compute BG"), layout: &self.bgl_compute,
-                entries: &[
-                    wgpu::BindGroupEntry { binding: 0, resource: input_buf.as_entire_binding() },
-                    wgpu::BindGroupEntry { binding: 1, resource: output_buf.as_entire_binding() },
-                ],
-            });
+        let input_buf = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Input"), contents: bytemuck::cast_slice(&splats), usage: wgpu::BufferUsages::STORAGE,
+        });
+        let sh_buf = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("SH"), contents: sh_buffer_contents(&sh), usage: wgpu::BufferUsages::STORAGE,
+        });
+        let output_size = (count * std::mem::size_of::<Surfel>()) as u64;
+        let output_buf = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Output"), size: output_size, usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX, mapped_at_creation: false,
+        });
+        let params = self.geometry_params(sh.degree);
+        self.queue.write_buffer(&self.geometry_param_buffer, 0, bytemuck::bytes_of(&params));
 
-            let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
-            {
-                let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
-                pass.set_pipeline(&self.compute_pipeline);
-                pass.set_bind_group(0, &bg_compute, &[]);
-                pass.dispatch_workgroups((count as u32 + 63) / 64, 1, 1);
+        let mut bg_compute = Vec::new();
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        for start in (0..count).step_by(chunk_len) {
+            let len = chunk_len.min(count - start) as u64;
+            let start = start as u64;
+            let sh_range = match sh_stride {
+                0 => buffer_range(&sh_buf, 0, 4),
+                _ => buffer_range(&sh_buf, start * sh_stride * 4, len * sh_stride * 4),
+            };
+            let bg = self.geometry_pipeline.bind_group(
+                &self.device,
+                buffer_range(&input_buf, start * 64, len * 64),
+                buffer_range(&output_buf, start * 48, len * 48),
+                sh_range,
+                self.geometry_param_buffer.as_entire_binding(),
+            );
+            self.geometry_pipeline.dispatch(&mut encoder, &bg, len as u32);
+            bg_compute.push((bg, len as u32));
+        }
+        self.queue.submit(Some(encoder.finish()));
+
+        self.vertex_buffer = Some(output_buf);
+        self.num_vertices = count as u32;
+        self.sr_draw_args = None;
+        self.bg_compute = bg_compute;
+        self.sr_active = false;
+        log::info!("Loaded {} splats (SH degree {}).", count, sh.degree);
+        self.sh = sh;
+        Ok(())
+    }
+
+    // Surfels of the loaded splats computed on the CPU (DC color), used by the exporters
+    fn cpu_surfels(&self) -> Vec<Surfel> {
+        self.splats.iter().map(|splat| {
+            let (radius, curvature) = surfel_shape_cpu(splat.scale);
+            Surfel {
+                pos: splat.pos, radius,
+                color: sh_to_rgb_cpu(splat.sh_dc), opacity: splat.opacity,
+                normal: compute_normal_cpu(splat.rot, splat.scale), curvature,
             }
-            self.queue.submit(Some(encoder.finish()));
+        }).collect()
+    }
 
-            self.vertex_buffer = Some(output_buf);
-            self.num_vertices = count as u32;
-            self.bg_compute = Some(bg_compute);
-            log::info!("Loaded {} splats.", count);
-        }
+    fn geometry_params(&self, sh_degree: u32) -> GeometryParams {
+        if !self.view_dependent { return GeometryParams { view_mode: VIEW_MODE_DC, ..Default::default() }; }
+        let eye = self.camera.borrow().eye();
+        GeometryParams { camera_pos: [eye.x, eye.y, eye.z], sh_degree, view_mode: VIEW_MODE_CAMERA, ..Default::default() }
+    }
+
+    // 視点依存カラー: 有効時は毎フレーム現在のカメラ位置から高次SHを再評価する
+    // 無効化すると最後に評価した視点の色が残るので、次の render で DC カラーを再計算する
+    pub fn set_view_dependent_color(&mut self, enabled: bool) {
+        if self.view_dependent && !enabled { self.restore_dc_colors = true; }
+        self.view_dependent = enabled;
     }
 
     // --- SR Execution ---
-    pub fn compute_super_resolution(&mut self, factor: u32) {
-        if self.splats.is_empty() || factor < 1 { return; }
+    // 省略した引数 (undefined) は既定値 (SplatManager.compute_super_resolution と同じ)
+    #[allow(clippy::too_many_arguments)]
+    pub fn compute_super_resolution(
+        &mut self,
+        factor: u32,
+        min_opacity: Option<f32>,
+        max_aspect_ratio: Option<f32>,
+        max_scale: Option<f32>,
+        sample_radius: Option<f32>,
+        density: Option<f32>,
+        sampler: Option<String>,
+        seed: Option<u32>,
+        truncate: Option<f32>,
+    ) -> Result<(), JsValue> {
+        let sampler: sr::SrSampler = sampler.as_deref().unwrap_or("spiral").parse()?;
+        let params = sr::SrParams::new(
+            factor,
+            min_opacity.unwrap_or(sr::DEFAULT_MIN_OPACITY),
+            max_aspect_ratio.unwrap_or(sr::DEFAULT_MAX_ASPECT_RATIO),
+            max_scale,
+            sample_radius.unwrap_or(sr::DEFAULT_SAMPLE_RADIUS),
+        )?.with_density(density)?.with_sampler(sampler, seed.unwrap_or(0), truncate)?;
+        if self.splats.is_empty() { return Err(GsError::invalid("No splats loaded. Load a file first.").into()); }
         if let Some(sr) = &self.sr_pipeline {
-             let (output_buf, count) = sr.run(&self.device, &self.queue, &self.splats, factor);
-             self.vertex_buffer = Some(output_buf);
-             self.num_vertices = count;
-             log::info!("Super Resolution Complete: {} surfels generated (Factor: {})", count, factor);
+             let output = sr.run(&self.device, &self.queue, &self.splats, params)?;
+             log::info!("Super Resolution Complete: up to {} surfels generated (Factor: {})", output.max_count, factor);
+             self.vertex_buffer = Some(output.surfels);
+             self.sr_draw_args = Some((output.draw_args, output.draw_count));
+             self.sr_active = true;
         }
+        Ok(())
     }
 
     pub fn set_display_mode(&mut self, mode: u32) { self.display_mode = mode; }
@@ -814,6 +1478,14 @@ impl WasmViewer {
         self.queue.write_buffer(&self.uniform_buffer, 0, &uniform);
 
         let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        if (self.view_dependent || self.restore_dc_colors) && !self.sr_active && !self.bg_compute.is_empty() {
+            self.restore_dc_colors = false;
+            let params = self.geometry_params(self.sh.degree);
+            self.queue.write_buffer(&self.geometry_param_buffer, 0, bytemuck::bytes_of(&params));
+            for (bg, len) in &self.bg_compute {
+                self.geometry_pipeline.dispatch(&mut encoder, bg, *len);
+            }
+        }
         {
             let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: None,
@@ -827,7 +1499,13 @@ impl WasmViewer {
                 pass.set_pipeline(&self.render_pipeline);
                 if let Some(bg) = &self.bg_render { pass.set_bind_group(0, bg, &[]); }
                 pass.set_vertex_buffer(0, vb.slice(..));
-                pass.draw(0..self.num_vertices, 0..1);
+                match &self.sr_draw_args {
+                    // SR 結果はチャンクごとに GPU 上の有効数だけ描く
+                    Some((args, count)) => for chunk in 0..*count as u64 {
+                        pass.draw_indirect(args, chunk * std::mem::size_of::<wgpu::util::DrawIndirectArgs>() as u64);
+                    },
+                    None => pass.draw(0..self.num_vertices, 0..1),
+                }
             }
         }
         self.queue.submit(Some(encoder.finish()));
@@ -835,40 +1513,46 @@ impl WasmViewer {
     }
 
     // Export Functionality (XYZ + RGB + Normal to PLY)
-    pub fn export_ply(&self) -> Vec<u8> {
-        let mut buffer = String::new();
-        
-        // Header
-        buffer.push_str("ply\n");
-        buffer.push_str("format ascii 1.0\n");
-        buffer.push_str(&format!("element vertex {}\n", self.splats.len()));
-        buffer.push_str("property float x\n");
-        buffer.push_str("property float y\n");
-        buffer.push_str("property float z\n");
-        buffer.push_str("property uchar red\n");
-        buffer.push_str("property uchar green\n");
-        buffer.push_str("property uchar blue\n");
-        buffer.push_str("property float nx\n");
-        buffer.push_str("property float ny\n");
-        buffer.push_str("property float nz\n");
-        buffer.push_str("end_header\n");
-
-        for splat in &self.splats {
-            let rgb = sh_to_rgb_cpu(splat.sh_dc);
-            let normal = compute_normal_cpu(splat.rot, splat.scale);
-            
-            let r = (rgb[0] * 255.0) as u8;
-            let g = (rgb[1] * 255.0) as u8;
-            let b = (rgb[2] * 255.0) as u8;
-
-            buffer.push_str(&format!(
-                "{} {} {} {} {} {} {} {} {}\n",
-                splat.pos[0], splat.pos[1], splat.pos[2],
-                r, g, b,
-                normal[0], normal[1], normal[2]
-            ));
-        }
+    pub fn export_ply(&self) -> Result<Vec<u8>, JsValue> {
+        // 現在の点群を Surfel として書き出す (binary_little_endian)
+        let mut buffer = Vec::new();
+        ply::write_surfel_ply(&mut buffer, &self.cpu_surfels(), ply::PlyEncoding::BinaryLittleEndian).map_err(GsError::from)?;
+        Ok(buffer)
+    }
+
+    // PCL 形式 (binary_compressed) で書き出す
+    pub fn export_pcd(&self) -> Result<Vec<u8>, JsValue> {
+        let mut buffer = Vec::new();
+        pcd::write_surfel_pcd(&mut buffer, &self.cpu_surfels(), &[], pcd::PcdEncoding::BinaryCompressed).map_err(GsError::from)?;
+        Ok(buffer)
+    }
+
+    // 3DGS 形式 (学習器互換) で書き出す
+    pub fn export_splat_ply(&self) -> Result<Vec<u8>, JsValue> {
+        let mut buffer = Vec::new();
+        ply::write_gaussian_ply(&mut buffer, &self.splats, &self.sh, ply::PlyEncoding::BinaryLittleEndian).map_err(GsError::from)?;
+        Ok(buffer)
+    }
+
+    // SPZ 形式で書き出す (fractional_bits 省略時は 12)
+    pub fn export_spz(&self, fractional_bits: Option<u8>) -> Result<Vec<u8>, JsValue> {
+        let mut buffer = Vec::new();
+        let bits = fractional_bits.unwrap_or(spz::DEFAULT_FRACTIONAL_BITS);
+        spz::write_spz(&mut buffer, &self.splats, &self.sh, bits)?;
+        Ok(buffer)
+    }
+
+    // antimatter15 .splat 形式で書き出す (DC のみ)
+    pub fn export_splat(&self) -> Result<Vec<u8>, JsValue> {
+        let mut buffer = Vec::new();
+        splat::write_splat(&mut buffer, &self.splats).map_err(GsError::from)?;
+        Ok(buffer)
+    }
 
-        buffer.into_bytes()
+    // GaussianSplats3D .ksplat 形式で書き出す (compression_level: 0 - 2)
+    pub fn export_ksplat(&self, compression_level: u32) -> Result<Vec<u8>, JsValue> {
+        let mut buffer = Vec::new();
+        ksplat::write_ksplat(&mut buffer, &self.splats, &self.sh, compression_level)?;
+        Ok(buffer)
     }
 }
@@ -0,0 +1,114 @@
+use crate::error::{GsError, GsResult};
+
+// ============================================================================
+//  LZF (liblzf compatible, used by PCD "DATA binary_compressed")
+// ============================================================================
+//
+// 制御バイト c:
+//   c < 32  : 続く c + 1 バイトがリテラル
+//   c >= 32 : 後方参照。長さ = (c >> 5) + 2 (7 の場合は次のバイトを加算)、
+//             距離 = ((c & 0x1f) << 8) + 次のバイト + 1
+
+const HASH_LOG: u32 = 14;
+const MAX_LITERAL: usize = 32;
+const MAX_OFFSET: usize = 1 << 13;
+const MAX_MATCH: usize = (1 << 8) + (1 << 3);
+
+fn hash(b: &[u8]) -> usize {
+    let v = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+    (v.wrapping_mul(2_654_435_761) >> (32 - HASH_LOG)) as usize
+}
+
+fn push_literals(out: &mut Vec<u8>, literals: &[u8]) {
+    for run in literals.chunks(MAX_LITERAL) {
+        out.push((run.len() - 1) as u8);
+        out.extend_from_slice(run);
+    }
+}
+
+pub fn compress(input: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(input.len() + input.len() / MAX_LITERAL + 1);
+    // 直近に現れた 3 バイト列の位置 + 1 (0 は未登録)
+    let mut table = vec![0usize; 1 << HASH_LOG];
+    let mut literal_start = 0;
+    let mut i = 0;
+
+    while i + 2 < input.len() {
+        let h = hash(&input[i..]);
+        let candidate = table[h];
+        table[h] = i + 1;
+
+        if candidate > 0 && i - candidate < MAX_OFFSET && input[candidate - 1..candidate + 2] == input[i..i + 3] {
+            let r = candidate - 1;
+            let max_len = (input.len() - i).min(MAX_MATCH);
+            let mut len = 3;
+            while len < max_len && input[r + len] == input[i + len] {
+                len += 1;
+            }
+
+            push_literals(&mut out, &input[literal_start..i]);
+            let offset = i - r - 1;
+            let code = len - 2;
+            if code < 7 {
+                out.push((code << 5 | offset >> 8) as u8);
+            } else {
+                out.push((7 << 5 | offset >> 8) as u8);
+                out.push((code - 7) as u8);
+            }
+            out.push(offset as u8);
+
+            i += len;
+            literal_start = i;
+        } else {
+            i += 1;
+        }
+    }
+    push_literals(&mut out, &input[literal_start..]);
+    out
+}
+
+// LZF はいまのところ PCD (binary_compressed) の中でしか使わない
+pub fn decompress(input: &[u8], expected_len: usize) -> GsResult<Vec<u8>> {
+    let bad = |msg: String| GsError::format("PCD", msg);
+    let truncated = || bad("LZF data is truncated".to_string());
+    // 1 つの後方参照 (3 バイト) が展開するのは最大 MAX_MATCH バイトなので、宣言サイズはそれで上限を抑えてから確保する
+    if expected_len > input.len().saturating_mul(MAX_MATCH) {
+        return Err(bad(format!("{} bytes of LZF data cannot expand to the declared {} bytes", input.len(), expected_len)));
+    }
+    let mut out = Vec::with_capacity(expected_len);
+    let mut i = 0;
+
+    while i < input.len() {
+        let ctrl = input[i] as usize;
+        i += 1;
+        if ctrl < 32 {
+            let literals = input.get(i..i + ctrl + 1).ok_or_else(truncated)?;
+            out.extend_from_slice(literals);
+            i += ctrl + 1;
+        } else {
+            let mut len = ctrl >> 5;
+            if len == 7 {
+                len += *input.get(i).ok_or_else(truncated)? as usize;
+                i += 1;
+            }
+            let offset = ((ctrl & 0x1f) << 8) + *input.get(i).ok_or_else(truncated)? as usize + 1;
+            i += 1;
+            if offset > out.len() {
+                return Err(bad(format!("LZF back-reference points {} bytes before the start of the output", offset - out.len())));
+            }
+            // 参照範囲が出力の末尾と重なることがあるので 1 バイトずつコピーする
+            let start = out.len() - offset;
+            for k in 0..len + 2 {
+                out.push(out[start + k]);
+            }
+        }
+        if out.len() > expected_len {
+            return Err(bad(format!("LZF data expands beyond the declared {} bytes", expected_len)));
+        }
+    }
+
+    if out.len() != expected_len {
+        return Err(bad(format!("LZF data expands to {} bytes, expected {}", out.len(), expected_len)));
+    }
+    Ok(out)
+}
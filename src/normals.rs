@@ -0,0 +1,419 @@
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use rayon::prelude::*;
+
+use crate::cpu::CpuPool;
+use crate::error::{GsError, GsResult};
+use crate::knn::{self, KnnQuery, Neighbors};
+use crate::Surfel;
+
+// ============================================================================
+//  Neighborhood Normal Estimation & Orientation
+// ============================================================================
+//
+// axis: スプラットの最も薄い軸 (compute_main / cpu::geometry の結果そのまま)
+// pca:  k 近傍の中心の共分散の最小固有ベクトルを、スプラットの異方性で重み付けして最も薄い軸と混ぜる。
+//       等方的なスプラットは近傍の面の向きに、薄い円盤は自身の向きに従う。
+// 向き (符号) は orient で揃える: 視点 (カメラ位置) を向く / 近傍グラフの最小全域木で伝播 (Hoppe et al. 1992)
+
+// Default neighborhood size (the point itself included)
+pub const DEFAULT_K: usize = 16;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum NormalMode {
+    #[default]
+    Axis,
+    Pca,
+}
+
+impl std::str::FromStr for NormalMode {
+    type Err = GsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "axis" => Ok(Self::Axis),
+            "pca" => Ok(Self::Pca),
+            _ => Err(GsError::invalid(format!("Unknown normal_mode '{}' (expected 'axis' or 'pca')", s))),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Orientation {
+    // スプラットの回転で決まる符号のまま
+    #[default]
+    None,
+    // 最も近い視点の方を向く
+    Viewpoint,
+    // 最小全域木に沿って隣同士の向きを揃える (連結成分ごとに、最も高い点を上向き / 視点向きにして開始)
+    Mst,
+}
+
+impl std::str::FromStr for Orientation {
+    type Err = GsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(Self::None),
+            "viewpoint" => Ok(Self::Viewpoint),
+            "mst" => Ok(Self::Mst),
+            _ => Err(GsError::invalid(format!("Unknown orient '{}' (expected 'none', 'viewpoint' or 'mst')", s))),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct NormalParams {
+    mode: NormalMode,
+    k: usize,
+    orient: Orientation,
+}
+
+impl NormalParams {
+    pub fn new(mode: NormalMode, k: usize, orient: Orientation, has_viewpoints: bool) -> GsResult<Self> {
+        if k < 3 {
+            return Err(GsError::invalid(format!("k must be >= 3 to fit a plane, got {}", k)));
+        }
+        if orient == Orientation::Viewpoint && !has_viewpoints {
+            return Err(GsError::invalid("orient='viewpoint' requires viewpoints"));
+        }
+        Ok(Self { mode, k, orient })
+    }
+
+    // k 近傍が要るか (PCA / MST)
+    pub fn neighbor_query(&self) -> GsResult<Option<KnnQuery>> {
+        if self.mode == NormalMode::Pca || self.orient == Orientation::Mst {
+            Ok(Some(KnnQuery::nearest(self.k)?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+// Anisotropy of a splat: 1 - thinnest / middle scale (1 = flat disc, 0 = no preferred thin axis)
+fn anisotropy(scale: [f32; 3]) -> f32 {
+    let mut s = scale.map(f32::abs);
+    s.sort_by(f32::total_cmp);
+    if s[1] > 0.0 { 1.0 - s[0] / s[1] } else { 0.0 }
+}
+
+// Eigenvalues (ascending) and eigenvectors (columns in the same order) of a symmetric 3x3 matrix (cyclic Jacobi)
+fn symmetric_eigen(mut a: [[f64; 3]; 3]) -> ([f64; 3], [[f64; 3]; 3]) {
+    let mut v = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+    for _ in 0..32 {
+        let off = a[0][1] * a[0][1] + a[0][2] * a[0][2] + a[1][2] * a[1][2];
+        if off <= 1e-30 * (a[0][0] * a[0][0] + a[1][1] * a[1][1] + a[2][2] * a[2][2]) { break; }
+        for (p, q) in [(0, 1), (0, 2), (1, 2)] {
+            if a[p][q] == 0.0 { continue; }
+            let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+            let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+            let c = 1.0 / (t * t + 1.0).sqrt();
+            let s = t * c;
+            // A' = J^T A J (p, q 行列を回転)
+            for row in a.iter_mut() {
+                let (ap, aq) = (row[p], row[q]);
+                row[p] = c * ap - s * aq;
+                row[q] = s * ap + c * aq;
+            }
+            let (rp, rq) = (a[p], a[q]);
+            a[p] = std::array::from_fn(|k| c * rp[k] - s * rq[k]);
+            a[q] = std::array::from_fn(|k| s * rp[k] + c * rq[k]);
+            for row in v.iter_mut() {
+                let (vp, vq) = (row[p], row[q]);
+                row[p] = c * vp - s * vq;
+                row[q] = s * vp + c * vq;
+            }
+        }
+    }
+    let mut order = [0, 1, 2];
+    order.sort_by(|&i, &j| a[i][i].total_cmp(&a[j][j]));
+    let values = order.map(|i| a[i][i]);
+    let vectors = order.map(|i| [v[0][i], v[1][i], v[2][i]]);
+    (values, vectors)
+}
+
+// Smallest-variance direction of the neighborhood and its planarity (1 - 3 λmin / Σλ, 0 for < 3 points)
+fn fit_plane(surfels: &[Surfel], indices: &[u32]) -> Option<([f32; 3], f32)> {
+    let points: Vec<[f64; 3]> = indices.iter().filter(|&&i| i != knn::NONE)
+        .map(|&i| surfels[i as usize].pos.map(f64::from))
+        .collect();
+    if points.len() < 3 { return None; }
+    let n = points.len() as f64;
+    let mean = [0, 1, 2].map(|a| points.iter().map(|p| p[a]).sum::<f64>() / n);
+    let mut cov = [[0.0; 3]; 3];
+    for p in &points {
+        let d = [p[0] - mean[0], p[1] - mean[1], p[2] - mean[2]];
+        for (r, row) in cov.iter_mut().enumerate() {
+            for (c, v) in row.iter_mut().enumerate() { *v += d[r] * d[c] / n; }
+        }
+    }
+    let (values, vectors) = symmetric_eigen(cov);
+    let total = values[0].max(0.0) + values[1].max(0.0) + values[2].max(0.0);
+    if total <= 0.0 { return None; }
+    let planarity = (1.0 - 3.0 * values[0].max(0.0) / total).clamp(0.0, 1.0);
+    Some((vectors[0].map(|v| v as f32), planarity as f32))
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn normalize(v: [f32; 3]) -> Option<[f32; 3]> {
+    let len = dot(v, v).sqrt();
+    (len > 0.0 && len.is_finite()).then(|| v.map(|x| x / len))
+}
+
+// Replaces the axis normals of `surfels` (one per splat) according to `params` and returns the per-surfel confidence.
+// confidence: axis はスプラットの異方性、pca は 1 - (1 - 異方性)(1 - 近傍の平面度) (どちらかが信頼できれば高い)
+pub fn estimate(
+    pool: &CpuPool,
+    surfels: &mut [Surfel],
+    scales: &[[f32; 3]],
+    neighbors: Option<&Neighbors>,
+    viewpoints: &[[f32; 3]],
+    params: &NormalParams,
+) -> Vec<f32> {
+    let mut confidence: Vec<f32> = scales.iter().map(|&s| anisotropy(s)).collect();
+
+    if params.mode == NormalMode::Pca {
+        let neighbors = neighbors.expect("PCA normals need the k nearest neighbors");
+        let fitted: Vec<Option<([f32; 3], f32)>> = pool.install(|| {
+            (0..surfels.len()).into_par_iter().map(|i| fit_plane(surfels, neighbors.row(i).0)).collect()
+        });
+        pool.install(|| {
+            surfels.par_iter_mut().zip(confidence.par_iter_mut()).zip(fitted).for_each(|((surfel, conf), fit)| {
+                let Some((plane_n, planarity)) = fit else { return };
+                let axis_n = surfel.normal;
+                // 最も薄い軸の符号に合わせてから混ぜる
+                let plane_n = if dot(plane_n, axis_n) < 0.0 { plane_n.map(|v| -v) } else { plane_n };
+                let w_axis = *conf;
+                let blended = [0, 1, 2].map(|a| w_axis * axis_n[a] + (1.0 - w_axis) * plane_n[a]);
+                surfel.normal = normalize(blended).unwrap_or(axis_n);
+                *conf = 1.0 - (1.0 - w_axis) * (1.0 - planarity);
+            });
+        });
+    }
+
+    match params.orient {
+        Orientation::None => {}
+        Orientation::Viewpoint => orient_to_viewpoints(pool, surfels, viewpoints),
+        Orientation::Mst => orient_mst(pool, surfels, neighbors.expect("MST orientation needs the k nearest neighbors"), viewpoints),
+    }
+    confidence
+}
+
+// 各法線を最も近い視点の方へ向ける
+fn orient_to_viewpoints(pool: &CpuPool, surfels: &mut [Surfel], viewpoints: &[[f32; 3]]) {
+    orient_to_cameras(pool, surfels, viewpoints, &Observers::default());
+}
+
+fn face(surfel: &mut Surfel, viewpoint: [f32; 3]) {
+    let to_view = [0, 1, 2].map(|a| viewpoint[a] - surfel.pos[a]);
+    if dot(surfel.normal, to_view) < 0.0 {
+        surfel.normal = surfel.normal.map(|v| -v);
+    }
+}
+
+// 近傍グラフ (辺の重み 1 - |n_i . n_j|) の最小全域木を Prim 法でたどり、親と逆向きの子を反転する。
+// 平らな所から先に伝播するので、折れ目をまたぐ反転が起きにくい
+fn orient_mst(pool: &CpuPool, surfels: &mut [Surfel], neighbors: &Neighbors, viewpoints: &[[f32; 3]]) {
+    let n = surfels.len();
+    // 対称な隣接リスト (CSR)
+    let edges = || (0..n).flat_map(|i| neighbors.row(i).0.iter().filter(move |&&j| j != knn::NONE && j as usize != i).map(move |&j| (i, j as usize)));
+    let mut start = vec![0usize; n + 1];
+    for (i, j) in edges() {
+        start[i + 1] += 1;
+        start[j + 1] += 1;
+    }
+    for i in 0..n { start[i + 1] += start[i]; }
+    let mut cursor = start.clone();
+    let mut adjacency = vec![0u32; start[n]];
+    for (i, j) in edges() {
+        adjacency[cursor[i]] = j as u32;
+        cursor[i] += 1;
+        adjacency[cursor[j]] = i as u32;
+        cursor[j] += 1;
+    }
+
+    let seed_view = (!viewpoints.is_empty()).then(|| {
+        let positions: Vec<[f32; 3]> = surfels.iter().map(|s| s.pos).collect();
+        knn::search_cpu(pool, viewpoints, &positions, &KnnQuery::nearest(1).unwrap()).indices
+    });
+
+    // 連結成分ごとに最も高い (z 最大) 点から始める
+    let mut order: Vec<u32> = (0..n as u32).collect();
+    order.sort_by(|&a, &b| surfels[b as usize].pos[2].total_cmp(&surfels[a as usize].pos[2]).then(a.cmp(&b)));
+    let mut visited = vec![false; n];
+    let mut heap = BinaryHeap::new();
+    for seed in order {
+        let seed = seed as usize;
+        if visited[seed] { continue; }
+        match &seed_view {
+            Some(nearest) => face(&mut surfels[seed], viewpoints[nearest[seed] as usize]),
+            None => if surfels[seed].normal[2] < 0.0 { surfels[seed].normal = surfels[seed].normal.map(|v| -v) },
+        }
+        // (重み, 子, 親)。重みは非負なので to_bits の順序が値の順序と一致する
+        heap.push(Reverse((0u32, seed as u32, seed as u32)));
+        while let Some(Reverse((_, child, parent))) = heap.pop() {
+            let child = child as usize;
+            if visited[child] { continue; }
+            visited[child] = true;
+            let parent_n = surfels[parent as usize].normal;
+            if dot(surfels[child].normal, parent_n) < 0.0 {
+                surfels[child].normal = surfels[child].normal.map(|v| -v);
+            }
+            let child_n = surfels[child].normal;
+            for &next in &adjacency[start[child]..start[child + 1]] {
+                if visited[next as usize] { continue; }
+                let weight = (1.0 - dot(child_n, surfels[next as usize].normal).abs()).max(0.0);
+                heap.push(Reverse((weight.to_bits(), next, child as u32)));
+            }
+        }
+    }
+}
+
+// ============================================================================
+//  Orientation Toward Observing Cameras (orient_normals)
+// ============================================================================
+//
+// SLAM の軌跡 (キーフレームのカメラ位置) を使って、壁の内側を向いた法線を観測した側へ反転する。
+// Surfel ごとの観測キーフレーム (observers) があれば最も多く現れるもの、無ければ最も近いカメラを向く。
+// GPU は shader.wgsl の orient_main、CPU は orient_to_cameras (同じ規則)
+
+// Observing keyframes per surfel: `width` camera indices per row, NONE for unknown / padding
+#[derive(Clone, Debug, Default)]
+pub struct Observers {
+    pub width: usize,
+    pub indices: Vec<u32>,
+}
+
+impl Observers {
+    // Row-major keyframe indices, `width` per surfel; negative values mean "not observed"
+    pub fn new(values: &[i64], width: usize, camera_count: usize) -> GsResult<Self> {
+        let indices = values.iter().map(|&v| match v {
+            v if v < 0 => Ok(knn::NONE),
+            v if (v as u64) < camera_count as u64 => Ok(v as u32),
+            v => Err(GsError::invalid(format!("observer index {} is out of range for {} cameras", v, camera_count))),
+        }).collect::<GsResult<Vec<u32>>>()?;
+        Ok(Self { width, indices })
+    }
+
+    // 行の中で最も多く現れるカメラ (同数なら先に現れた方)
+    fn most_frequent(&self, row: usize) -> Option<u32> {
+        let row = self.indices.get(row * self.width..(row + 1) * self.width)?;
+        let mut best = None;
+        let mut best_count = 0;
+        for &cam in row.iter().filter(|&&c| c != knn::NONE) {
+            let count = row.iter().filter(|&&c| c == cam).count();
+            if count > best_count {
+                best = Some(cam);
+                best_count = count;
+            }
+        }
+        best
+    }
+}
+
+// Faces each normal toward its observing camera (see Observers)
+pub fn orient_to_cameras(pool: &CpuPool, surfels: &mut [Surfel], cameras: &[[f32; 3]], observers: &Observers) {
+    let positions: Vec<[f32; 3]> = surfels.iter().map(|s| s.pos).collect();
+    let nearest = knn::search_cpu(pool, cameras, &positions, &KnnQuery::nearest(1).unwrap());
+    pool.install(|| {
+        surfels.par_iter_mut().zip(nearest.indices.par_iter()).enumerate().for_each(|(i, (surfel, &v))| {
+            let cam = observers.most_frequent(i).unwrap_or(v);
+            if cam != knn::NONE { face(surfel, cameras[cam as usize]); }
+        });
+    });
+}
+
+// Uniform of orient_main (OrientParams in shader.wgsl)
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct OrientParams {
+    pub camera_count: u32,
+    pub observer_width: u32,
+    pub _pad: [u32; 2],
+}
+
+// orient_main in shader.wgsl: surfels (read_write), cameras, observers, params
+pub struct OrientPipeline {
+    pipeline: wgpu::ComputePipeline,
+    layout: wgpu::BindGroupLayout,
+}
+
+impl OrientPipeline {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Orient Shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("shader.wgsl"))),
+        });
+
+        let storage = |binding, read_only| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Storage { read_only }, has_dynamic_offset: false, min_binding_size: None },
+            count: None,
+        };
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Orient Bind Group Layout"),
+            entries: &[
+                // Surfels (in place)
+                storage(0, false),
+                // Cameras
+                storage(1, true),
+                // Observers
+                storage(2, true),
+                // Params
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None },
+                    count: None,
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Orient Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Orient Compute Pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: Some("orient_main"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+        Self { pipeline, layout }
+    }
+
+    pub fn bind_group(
+        &self,
+        device: &wgpu::Device,
+        surfels: wgpu::BindingResource,
+        cameras: wgpu::BindingResource,
+        observers: wgpu::BindingResource,
+        params: wgpu::BindingResource,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Orient Bind Group"),
+            layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: surfels },
+                wgpu::BindGroupEntry { binding: 1, resource: cameras },
+                wgpu::BindGroupEntry { binding: 2, resource: observers },
+                wgpu::BindGroupEntry { binding: 3, resource: params },
+            ],
+        })
+    }
+
+    pub fn dispatch(&self, encoder: &mut wgpu::CommandEncoder, bind_group: &wgpu::BindGroup, count: u32) {
+        let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+        cpass.set_pipeline(&self.pipeline);
+        cpass.set_bind_group(0, bind_group, &[]);
+        cpass.dispatch_workgroups(count.div_ceil(64), 1, 1);
+    }
+}
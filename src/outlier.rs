@@ -0,0 +1,93 @@
+use rayon::prelude::*;
+
+use crate::cpu::CpuPool;
+use crate::error::{GsError, GsResult};
+use crate::knn::{self, KnnQuery, Neighbors};
+
+// ============================================================================
+//  Outlier Removal (floaters)
+// ============================================================================
+//
+// PCL の StatisticalOutlierRemoval / RadiusOutlierRemoval と同じ判定をスプラット中心に対して行う。
+// 近傍探索は knn.rs (backend に従い GPU / CPU) で、判定は近傍の行から CPU で求める。
+// statistical: 自身を除く k 近傍までの平均距離が、全点の平均 + std_ratio * 標準偏差 を超える点を除く
+// radius:      半径 radius 以内の近傍 (自身を除く) が min_neighbors 個未満の点を除く
+
+// Default neighborhood size of the statistical filter (the point itself excluded)
+pub const DEFAULT_K: usize = 20;
+pub const DEFAULT_STD_RATIO: f32 = 2.0;
+
+#[derive(Copy, Clone, Debug)]
+pub enum OutlierFilter {
+    Statistical { k: usize, std_ratio: f32 },
+    Radius { radius: f32, min_neighbors: usize },
+}
+
+impl OutlierFilter {
+    pub fn statistical(k: usize, std_ratio: f32) -> GsResult<Self> {
+        if k < 1 {
+            return Err(GsError::invalid("k must be >= 1"));
+        }
+        if !(std_ratio >= 0.0 && std_ratio.is_finite()) {
+            return Err(GsError::invalid(format!("std_ratio must be a non-negative number, got {}", std_ratio)));
+        }
+        Ok(Self::Statistical { k, std_ratio })
+    }
+
+    pub fn radius(radius: f32, min_neighbors: usize) -> GsResult<Self> {
+        // radius の検証は KnnQuery::within と同じ
+        KnnQuery::within(radius, 1)?;
+        Ok(Self::Radius { radius, min_neighbors })
+    }
+
+    // 近傍探索の条件 (結果には点自身も含まれるので 1 つ多く探す)
+    pub fn query(&self) -> GsResult<KnnQuery> {
+        match *self {
+            Self::Statistical { k, .. } => KnnQuery::nearest(k + 1),
+            Self::Radius { radius, min_neighbors } => KnnQuery::within(radius, min_neighbors + 1),
+        }
+    }
+
+    // 残す点が true。neighbors は点自身に対して query() で探索した結果
+    pub fn keep_mask(&self, pool: &CpuPool, neighbors: &Neighbors) -> Vec<bool> {
+        let count = neighbors.indices.len() / neighbors.k.max(1);
+        match *self {
+            Self::Statistical { k, std_ratio } => {
+                let means: Vec<Option<f64>> = pool.install(|| {
+                    (0..count).into_par_iter().map(|i| mean_distance(neighbors, i, k)).collect()
+                });
+                let Some(threshold) = threshold(&means, std_ratio) else { return vec![true; count] };
+                // 近傍が 1 つも無い点は統計に含めず、残す (PCL と同じ)
+                means.iter().map(|m| m.is_none_or(|d| d <= threshold)).collect()
+            }
+            // 点自身は必ず半径内にあるので、行が埋まっていれば自身以外に min_neighbors 個ある
+            Self::Radius { min_neighbors, .. } => pool.install(|| {
+                (0..count).into_par_iter().map(|i| {
+                    neighbors.row(i).0.iter().filter(|&&j| j != knn::NONE).count() > min_neighbors
+                }).collect()
+            }),
+        }
+    }
+}
+
+// 自身を除く k 近傍までの平均距離。行に自身が無ければ (同じ位置の点が k + 1 個以上) 最も遠い 1 つを除く
+fn mean_distance(neighbors: &Neighbors, i: usize, k: usize) -> Option<f64> {
+    let (indices, distances) = neighbors.row(i);
+    let own = indices.iter().position(|&j| j == i as u32);
+    let others: Vec<f64> = indices.iter().zip(distances).enumerate()
+        .filter(|&(slot, (&j, _))| j != knn::NONE && Some(slot) != own)
+        .map(|(_, (_, &d))| d as f64)
+        .take(k)
+        .collect();
+    if others.is_empty() { None } else { Some(others.iter().sum::<f64>() / others.len() as f64) }
+}
+
+// 平均 + std_ratio * 標準偏差 (不偏分散)。近傍のある点が無ければ None
+fn threshold(means: &[Option<f64>], std_ratio: f32) -> Option<f64> {
+    let values: Vec<f64> = means.iter().flatten().copied().collect();
+    if values.is_empty() { return None; }
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let variance = if values.len() > 1 { values.iter().map(|d| (d - mean) * (d - mean)).sum::<f64>() / (n - 1.0) } else { 0.0 };
+    Some(mean + std_ratio as f64 * variance.sqrt())
+}
@@ -0,0 +1,389 @@
+use std::io::{self, Write};
+
+use crate::error::{GsError, GsResult};
+use crate::{lzf, Surfel};
+
+// ============================================================================
+//  PCD (Point Cloud Library) v0.7
+// ============================================================================
+//
+// 色は PCL の慣例どおり packed RGB (0x00RRGGBB の u32 を float として格納) の 'rgb' フィールド。
+// DATA ascii の場合、rgb は PCL と同様に u32 の整数として書き出す。
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum PcdEncoding {
+    Ascii,
+    #[default]
+    Binary,
+    // Field-major (SoA) records compressed with LZF
+    BinaryCompressed,
+}
+
+impl PcdEncoding {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Ascii => "ascii",
+            Self::Binary => "binary",
+            Self::BinaryCompressed => "binary_compressed",
+        }
+    }
+}
+
+impl std::str::FromStr for PcdEncoding {
+    type Err = GsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "ascii" => Ok(Self::Ascii),
+            "binary" => Ok(Self::Binary),
+            "binary_compressed" => Ok(Self::BinaryCompressed),
+            _ => Err(GsError::invalid(format!("Unknown PCD encoding '{}' (expected 'binary', 'binary_compressed' or 'ascii')", s))),
+        }
+    }
+}
+
+// Optional per-point fields appended after x y z rgb normal_*
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PcdExtraField {
+    Opacity,
+    Curvature,
+    Radius,
+}
+
+impl PcdExtraField {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Opacity => "opacity",
+            Self::Curvature => "curvature",
+            Self::Radius => "radius",
+        }
+    }
+
+    fn value(self, s: &Surfel) -> f32 {
+        match self {
+            Self::Opacity => s.opacity,
+            Self::Curvature => s.curvature,
+            Self::Radius => s.radius,
+        }
+    }
+}
+
+impl std::str::FromStr for PcdExtraField {
+    type Err = GsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "opacity" => Ok(Self::Opacity),
+            "curvature" => Ok(Self::Curvature),
+            "radius" => Ok(Self::Radius),
+            _ => Err(GsError::invalid(format!("Unknown PCD field '{}' (expected 'opacity', 'curvature' or 'radius')", s))),
+        }
+    }
+}
+
+pub fn pack_rgb(color: [f32; 3]) -> u32 {
+    let [r, g, b] = color.map(|c| (c * 255.0) as u8 as u32);
+    r << 16 | g << 8 | b
+}
+
+pub fn unpack_rgb(packed: u32) -> [u8; 3] {
+    [(packed >> 16) as u8, (packed >> 8) as u8, packed as u8]
+}
+
+// ============================================================================
+//  Writer
+// ============================================================================
+
+// x y z rgb normal_x normal_y normal_z [+ extras], every field a 4-byte float
+pub fn write_surfel_pcd<W: Write>(w: &mut W, surfels: &[Surfel], extras: &[PcdExtraField], encoding: PcdEncoding) -> io::Result<()> {
+    let mut names = vec!["x", "y", "z", "rgb", "normal_x", "normal_y", "normal_z"];
+    names.extend(extras.iter().map(|e| e.name()));
+    let n = names.len();
+    let repeat = |v: &str| vec![v; n].join(" ");
+
+    let mut header = String::new();
+    header.push_str("# .PCD v0.7 - Point Cloud Data file format (generated by gs-slam-core)\n");
+    header.push_str("VERSION 0.7\n");
+    header.push_str(&format!("FIELDS {}\n", names.join(" ")));
+    header.push_str(&format!("SIZE {}\n", repeat("4")));
+    header.push_str(&format!("TYPE {}\n", repeat("F")));
+    header.push_str(&format!("COUNT {}\n", repeat("1")));
+    header.push_str(&format!("WIDTH {}\n", surfels.len()));
+    header.push_str("HEIGHT 1\n");
+    header.push_str("VIEWPOINT 0 0 0 1 0 0 0\n");
+    header.push_str(&format!("POINTS {}\n", surfels.len()));
+    header.push_str(&format!("DATA {}\n", encoding.name()));
+    w.write_all(header.as_bytes())?;
+
+    // rgb はビット列のまま u32 で扱う
+    let point = |s: &Surfel| -> Vec<u32> {
+        let mut v = vec![s.pos[0].to_bits(), s.pos[1].to_bits(), s.pos[2].to_bits(), pack_rgb(s.color)];
+        v.extend(s.normal.map(f32::to_bits));
+        v.extend(extras.iter().map(|e| e.value(s).to_bits()));
+        v
+    };
+
+    match encoding {
+        PcdEncoding::Ascii => {
+            let mut body = String::new();
+            for s in surfels {
+                let values: Vec<String> = point(s).iter().enumerate()
+                    .map(|(i, &bits)| if i == 3 { bits.to_string() } else { f32::from_bits(bits).to_string() })
+                    .collect();
+                body.push_str(&values.join(" "));
+                body.push('\n');
+            }
+            w.write_all(body.as_bytes())
+        }
+        PcdEncoding::Binary => {
+            let mut body = Vec::with_capacity(surfels.len() * n * 4);
+            for s in surfels {
+                for bits in point(s) {
+                    body.extend_from_slice(&bits.to_le_bytes());
+                }
+            }
+            w.write_all(&body)
+        }
+        PcdEncoding::BinaryCompressed => {
+            // フィールドごとに全点を並べてから (SoA) 圧縮する
+            let mut body = vec![0u8; surfels.len() * n * 4];
+            for (i, s) in surfels.iter().enumerate() {
+                for (f, bits) in point(s).into_iter().enumerate() {
+                    let at = (f * surfels.len() + i) * 4;
+                    body[at..at + 4].copy_from_slice(&bits.to_le_bytes());
+                }
+            }
+            let compressed = lzf::compress(&body);
+            w.write_all(&(compressed.len() as u32).to_le_bytes())?;
+            w.write_all(&(body.len() as u32).to_le_bytes())?;
+            w.write_all(&compressed)
+        }
+    }
+}
+
+// ============================================================================
+//  Reader
+// ============================================================================
+
+#[derive(Clone, Debug)]
+pub struct PcdField {
+    pub name: String,
+    pub size: usize,
+    // 'F' (float), 'U' (unsigned), 'I' (signed)
+    pub ty: char,
+    pub count: usize,
+}
+
+impl PcdField {
+    // packed RGB(A) は数値変換せずビット列を保持する
+    fn is_packed_color(&self) -> bool {
+        (self.name == "rgb" || self.name == "rgba") && self.size == 4 && self.count == 1
+    }
+
+    fn read(&self, bytes: &[u8]) -> f32 {
+        macro_rules! num {
+            ($t:ty, $n:expr) => {{
+                let mut b = [0u8; $n];
+                b.copy_from_slice(&bytes[..$n]);
+                <$t>::from_le_bytes(b)
+            }};
+        }
+        if self.is_packed_color() {
+            return f32::from_bits(num!(u32, 4));
+        }
+        match (self.ty, self.size) {
+            ('F', 4) => num!(f32, 4),
+            ('F', 8) => num!(f64, 8) as f32,
+            ('U', 1) => bytes[0] as f32,
+            ('U', 2) => num!(u16, 2) as f32,
+            ('U', 4) => num!(u32, 4) as f32,
+            ('U', 8) => num!(u64, 8) as f32,
+            ('I', 1) => bytes[0] as i8 as f32,
+            ('I', 2) => num!(i16, 2) as f32,
+            ('I', 4) => num!(i32, 4) as f32,
+            ('I', 8) => num!(i64, 8) as f32,
+            _ => unreachable!("validated in read_pcd"),
+        }
+    }
+
+    fn parse(&self, token: &str) -> Option<f32> {
+        if self.is_packed_color() {
+            // PCL は ascii の rgb を u32 で書くが、古いファイルには float 表記のものもある
+            return token.parse::<u32>().ok().map(f32::from_bits).or_else(|| token.parse().ok());
+        }
+        match token {
+            "nan" | "NaN" => Some(f32::NAN),
+            _ => token.parse().ok(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PcdCloud {
+    pub fields: Vec<PcdField>,
+    // Row-major, one f32 per field element (sum of COUNT per point)
+    values: Vec<f32>,
+}
+
+impl PcdCloud {
+    fn stride(&self) -> usize {
+        self.fields.iter().map(|f| f.count).sum()
+    }
+
+    fn field_offset(&self, name: &str) -> Option<(usize, &PcdField)> {
+        let mut offset = 0;
+        for f in &self.fields {
+            if f.name == name { return Some((offset, f)); }
+            offset += f.count;
+        }
+        None
+    }
+
+    // All elements of one field, point by point (COUNT values per point)
+    pub fn field(&self, name: &str) -> Option<Vec<f32>> {
+        let (offset, field) = self.field_offset(name)?;
+        let stride = self.stride();
+        Some(self.values.chunks_exact(stride).flat_map(|p| p[offset..offset + field.count].iter().copied()).collect())
+    }
+
+    // Colors from the packed 'rgb' / 'rgba' field
+    pub fn rgb(&self) -> Option<Vec<[u8; 3]>> {
+        let (offset, _) = self.field_offset("rgb").or_else(|| self.field_offset("rgba"))
+            .filter(|(_, f)| f.is_packed_color())?;
+        let stride = self.stride();
+        Some(self.values.chunks_exact(stride).map(|p| unpack_rgb(p[offset].to_bits())).collect())
+    }
+}
+
+pub fn read_pcd(data: &[u8]) -> GsResult<PcdCloud> {
+    let bad = |msg: String| GsError::format("PCD", msg);
+    let mut names: Vec<String> = Vec::new();
+    let mut sizes: Vec<usize> = Vec::new();
+    let mut types: Vec<char> = Vec::new();
+    let mut counts: Vec<usize> = Vec::new();
+    let (mut width, mut height, mut points) = (None, 1usize, None);
+    let mut cursor = 0;
+
+    let encoding = loop {
+        let rest = &data[cursor..];
+        let len = rest.iter().position(|&b| b == b'\n').ok_or_else(|| bad("header has no DATA line".to_string()))?;
+        let line = std::str::from_utf8(&rest[..len]).map_err(|_| bad("header has non-text data before DATA".to_string()))?.trim();
+        cursor += len + 1;
+
+        let mut tokens = line.split_whitespace();
+        let Some(key) = tokens.next() else { continue };
+        let values: Vec<&str> = tokens.collect();
+        let numbers = |what: &str| -> GsResult<Vec<usize>> {
+            values.iter().map(|v| v.parse().map_err(|_| bad(format!("invalid {} value '{}'", what, v)))).collect()
+        };
+        let single = |what: &str| -> GsResult<usize> {
+            values.first().and_then(|v| v.parse().ok()).ok_or_else(|| bad(format!("invalid {} line", what)))
+        };
+        match key {
+            "FIELDS" => names = values.iter().map(|v| v.to_string()).collect(),
+            "SIZE" => sizes = numbers("SIZE")?,
+            "TYPE" => types = values.iter().map(|v| v.chars().next().unwrap_or('?')).collect(),
+            "COUNT" => counts = numbers("COUNT")?,
+            "WIDTH" => width = Some(single("WIDTH")?),
+            "HEIGHT" => height = single("HEIGHT")?,
+            "POINTS" => points = Some(single("POINTS")?),
+            "DATA" => break values.first().copied().unwrap_or("").parse::<PcdEncoding>().map_err(|e| bad(e.to_string()))?,
+            // コメント / VERSION / VIEWPOINT は無視
+            _ => {}
+        }
+    };
+
+    if counts.is_empty() { counts = vec![1; names.len()]; }
+    if names.is_empty() || sizes.len() != names.len() || types.len() != names.len() || counts.len() != names.len() {
+        return Err(bad(format!(
+            "FIELDS ({}), SIZE ({}), TYPE ({}) and COUNT ({}) must have the same length",
+            names.len(), sizes.len(), types.len(), counts.len(),
+        )));
+    }
+    let fields: Vec<PcdField> = names.into_iter().zip(sizes).zip(types).zip(counts)
+        .map(|(((name, size), ty), count)| PcdField { name, size, ty, count })
+        .collect();
+    for f in &fields {
+        let valid = matches!((f.ty, f.size), ('F', 4 | 8) | ('U' | 'I', 1 | 2 | 4 | 8));
+        if !valid { return Err(bad(format!("unsupported field '{}' (TYPE {} SIZE {})", f.name, f.ty, f.size))); }
+    }
+
+    let len = match (points, width) {
+        (Some(points), _) => points,
+        (None, Some(w)) => w.checked_mul(height).ok_or_else(|| bad(format!("WIDTH {} x HEIGHT {} overflows", w, height)))?,
+        (None, None) => return Err(bad("header has no POINTS / WIDTH".to_string())),
+    };
+    let mut cloud = PcdCloud { fields, values: Vec::new() };
+    let stride = cloud.stride();
+    let point_bytes: usize = cloud.fields.iter().map(|f| f.size * f.count).sum();
+    let body = &data[cursor..];
+
+    // ヘッダの点数を信用して確保しない: 本体に入り切らない点数はここで弾く
+    // (ascii は 1 値あたり最低 1 バイト、binary_compressed は展開後に検証する)
+    let (value_len, byte_len) = len.checked_mul(stride).zip(len.checked_mul(point_bytes))
+        .ok_or_else(|| bad(format!("POINTS {} overflows the point size", len)))?;
+    let min_body = match encoding {
+        PcdEncoding::Ascii => value_len,
+        PcdEncoding::Binary => byte_len,
+        PcdEncoding::BinaryCompressed => 0,
+    };
+    if body.len() < min_body {
+        return Err(bad(format!("body is truncated: {} points need at least {} bytes but only {} follow", len, min_body, body.len())));
+    }
+    if min_body > 0 { cloud.values.reserve(value_len); }
+
+    match encoding {
+        PcdEncoding::Ascii => {
+            let text = std::str::from_utf8(body).map_err(|_| bad("ascii body is not valid text".to_string()))?;
+            let mut lines = text.lines().filter(|l| !l.trim().is_empty());
+            for i in 0..len {
+                let line = lines.next().ok_or_else(|| bad(format!("body is truncated: {} of {} points", i, len)))?;
+                let mut tokens = line.split_whitespace();
+                for f in &cloud.fields {
+                    for _ in 0..f.count {
+                        let token = tokens.next().ok_or_else(|| bad(format!("point {} has too few values", i)))?;
+                        let value = f.parse(token).ok_or_else(|| bad(format!("invalid value '{}' for field '{}'", token, f.name)))?;
+                        cloud.values.push(value);
+                    }
+                }
+            }
+        }
+        PcdEncoding::Binary => {
+            let bytes = &body[..byte_len];
+            for record in bytes.chunks_exact(point_bytes.max(1)) {
+                let mut at = 0;
+                for f in &cloud.fields {
+                    for _ in 0..f.count {
+                        cloud.values.push(f.read(&record[at..]));
+                        at += f.size;
+                    }
+                }
+            }
+        }
+        PcdEncoding::BinaryCompressed => {
+            let word = |at: usize| body.get(at..at + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize);
+            let (compressed, raw) = word(0).zip(word(4)).ok_or_else(|| bad("body is truncated: missing compressed sizes".to_string()))?;
+            if raw != byte_len {
+                return Err(bad(format!("declares {} uncompressed bytes, expected {} for {} points", raw, byte_len, len)));
+            }
+            let input = body.get(8..8 + compressed).ok_or_else(|| bad("body is truncated inside the compressed data".to_string()))?;
+            let bytes = lzf::decompress(input, raw)?;
+
+            // フィールド優先 (SoA) → 点ごとの並びへ
+            cloud.values.resize(value_len, 0.0);
+            let (mut byte_offset, mut value_offset) = (0, 0);
+            for f in &cloud.fields {
+                for i in 0..len {
+                    for c in 0..f.count {
+                        let at = byte_offset + (i * f.count + c) * f.size;
+                        cloud.values[i * stride + value_offset + c] = f.read(&bytes[at..]);
+                    }
+                }
+                byte_offset += len * f.size * f.count;
+                value_offset += f.count;
+            }
+        }
+    }
+
+    Ok(cloud)
+}
@@ -0,0 +1,582 @@
+use std::io::{self, Write};
+
+use crate::error::{GsError, GsResult};
+use crate::sh::{self, ShCoeffs};
+use crate::pointcloud::PointCloud;
+use crate::{GaussianSplat, LoadOptions, QuatOrder, SplatData, Surfel, ValueDomain};
+
+// ============================================================================
+//  PLY Header
+// ============================================================================
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PlyFormat {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PlyScalar {
+    Char,
+    UChar,
+    Short,
+    UShort,
+    Int,
+    UInt,
+    Float,
+    Double,
+}
+
+impl PlyScalar {
+    fn parse(name: &str) -> Option<Self> {
+        // 旧来の名前 (uchar 等) とサイズ付きの名前 (uint8 等) の両方を受け付ける
+        Some(match name {
+            "char" | "int8" => Self::Char,
+            "uchar" | "uint8" => Self::UChar,
+            "short" | "int16" => Self::Short,
+            "ushort" | "uint16" => Self::UShort,
+            "int" | "int32" => Self::Int,
+            "uint" | "uint32" => Self::UInt,
+            "float" | "float32" => Self::Float,
+            "double" | "float64" => Self::Double,
+            _ => return None,
+        })
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Char => "char",
+            Self::UChar => "uchar",
+            Self::Short => "short",
+            Self::UShort => "ushort",
+            Self::Int => "int",
+            Self::UInt => "uint",
+            Self::Float => "float",
+            Self::Double => "double",
+        }
+    }
+
+    pub fn size(self) -> usize {
+        match self {
+            Self::Char | Self::UChar => 1,
+            Self::Short | Self::UShort => 2,
+            Self::Int | Self::UInt | Self::Float => 4,
+            Self::Double => 8,
+        }
+    }
+
+    fn read(self, bytes: &[u8], big_endian: bool) -> f32 {
+        macro_rules! num {
+            ($t:ty, $n:expr) => {{
+                let mut b = [0u8; $n];
+                b.copy_from_slice(&bytes[..$n]);
+                if big_endian { <$t>::from_be_bytes(b) } else { <$t>::from_le_bytes(b) }
+            }};
+        }
+        match self {
+            Self::Char => bytes[0] as i8 as f32,
+            Self::UChar => bytes[0] as f32,
+            Self::Short => num!(i16, 2) as f32,
+            Self::UShort => num!(u16, 2) as f32,
+            Self::Int => num!(i32, 4) as f32,
+            Self::UInt => num!(u32, 4) as f32,
+            Self::Float => num!(f32, 4),
+            Self::Double => num!(f64, 8) as f32,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum PlyPropertyKind {
+    Scalar(PlyScalar),
+    List { count: PlyScalar, item: PlyScalar },
+}
+
+#[derive(Clone, Debug)]
+pub struct PlyProperty {
+    pub name: String,
+    pub kind: PlyPropertyKind,
+}
+
+#[derive(Clone, Debug)]
+pub struct PlyElement {
+    pub name: String,
+    pub count: usize,
+    pub properties: Vec<PlyProperty>,
+}
+
+impl PlyElement {
+    // 固定長レコードのバイト数 (list プロパティを含む場合は None)
+    fn stride(&self) -> Option<usize> {
+        self.properties.iter().map(|p| match p.kind {
+            PlyPropertyKind::Scalar(s) => Some(s.size()),
+            PlyPropertyKind::List { .. } => None,
+        }).sum()
+    }
+
+    fn property_names(&self) -> Vec<&str> {
+        self.properties.iter().map(|p| p.name.as_str()).collect()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PlyHeader {
+    pub format: PlyFormat,
+    pub elements: Vec<PlyElement>,
+    // Byte offset of the first body byte (just after "end_header\n")
+    pub body_offset: usize,
+}
+
+impl PlyHeader {
+    pub fn parse(data: &[u8]) -> GsResult<Self> {
+        let mut format = None;
+        let mut elements: Vec<PlyElement> = Vec::new();
+        let mut cursor = 0;
+        let mut line_no = 0;
+
+        loop {
+            let rest = &data[cursor..];
+            let len = rest.iter().position(|&b| b == b'\n')
+                .ok_or_else(|| GsError::ply("Invalid PLY header: missing end_header"))?;
+            let line = std::str::from_utf8(&rest[..len])
+                .map_err(|_| GsError::ply_at_line(line_no + 1, "Invalid PLY header: non-text data"))?
+                .trim_end_matches('\r')
+                .trim();
+            cursor += len + 1;
+            line_no += 1;
+
+            if line_no == 1 {
+                if line != "ply" { return Err(GsError::ply_at_line(1, "Invalid PLY header: file does not start with 'ply'")); }
+                continue;
+            }
+
+            let mut tokens = line.split_whitespace();
+            match tokens.next() {
+                Some("format") => {
+                    format = Some(match tokens.next() {
+                        Some("ascii") => PlyFormat::Ascii,
+                        Some("binary_little_endian") => PlyFormat::BinaryLittleEndian,
+                        Some("binary_big_endian") => PlyFormat::BinaryBigEndian,
+                        other => return Err(GsError::ply_at_line(line_no, format!("Unsupported PLY format {:?}", other.unwrap_or("")))),
+                    });
+                }
+                Some("element") => {
+                    let name = tokens.next().ok_or_else(|| GsError::ply_at_line(line_no, "Malformed element"))?;
+                    let count = tokens.next().and_then(|c| c.parse().ok())
+                        .ok_or_else(|| GsError::ply_at_line(line_no, format!("Malformed count of element '{}'", name)))?;
+                    elements.push(PlyElement { name: name.to_string(), count, properties: Vec::new() });
+                }
+                Some("property") => {
+                    let element = elements.last_mut()
+                        .ok_or_else(|| GsError::ply_at_line(line_no, "Property declared before any element"))?;
+                    let bad_type = |t: &str| GsError::ply_at_line(line_no, format!("Unknown property type '{}'", t));
+                    let kind = match tokens.next() {
+                        Some("list") => {
+                            let count = tokens.next().unwrap_or("");
+                            let item = tokens.next().unwrap_or("");
+                            PlyPropertyKind::List {
+                                count: PlyScalar::parse(count).ok_or_else(|| bad_type(count))?,
+                                item: PlyScalar::parse(item).ok_or_else(|| bad_type(item))?,
+                            }
+                        }
+                        Some(t) => PlyPropertyKind::Scalar(PlyScalar::parse(t).ok_or_else(|| bad_type(t))?),
+                        None => return Err(GsError::ply_at_line(line_no, "Malformed property")),
+                    };
+                    let name = tokens.next().ok_or_else(|| GsError::ply_at_line(line_no, "Property without a name"))?;
+                    element.properties.push(PlyProperty { name: name.to_string(), kind });
+                }
+                Some("end_header") => break,
+                // comment / obj_info / 空行 は無視
+                _ => {}
+            }
+        }
+
+        let format = format.ok_or_else(|| GsError::ply("Invalid PLY header: missing format line"))?;
+        Ok(Self { format, elements, body_offset: cursor })
+    }
+
+    pub fn element(&self, name: &str) -> Option<&PlyElement> {
+        self.elements.iter().find(|e| e.name == name)
+    }
+
+    // 指定要素の本体内オフセット (バイナリ形式のみ; list を含む先行要素はレコードを走査してスキップ)
+    fn element_offset(&self, data: &[u8], name: &str) -> GsResult<usize> {
+        let big_endian = self.format == PlyFormat::BinaryBigEndian;
+        let mut offset = self.body_offset;
+        for e in &self.elements {
+            if e.name == name { return Ok(offset); }
+            // 宣言されたレコード数は信用しない (オーバーフローは壊れたヘッダ)
+            let overflow = || GsError::ply(format!("PLY element '{}' declares too many records ({})", e.name, e.count));
+            if let Some(stride) = e.stride() {
+                offset = stride.checked_mul(e.count).and_then(|bytes| offset.checked_add(bytes)).ok_or_else(overflow)?;
+                continue;
+            }
+            for _ in 0..e.count {
+                for p in &e.properties {
+                    let size = match p.kind {
+                        PlyPropertyKind::Scalar(s) => s.size(),
+                        PlyPropertyKind::List { count, item } => {
+                            let bytes = data.get(offset..offset + count.size())
+                                .ok_or_else(|| GsError::ply(format!("PLY body is truncated inside element '{}'", e.name)))?;
+                            count.size() + count.read(bytes, big_endian) as usize * item.size()
+                        }
+                    };
+                    offset = offset.checked_add(size).ok_or_else(overflow)?;
+                }
+            }
+        }
+        Err(GsError::ply(format!("PLY has no '{}' element", name)))
+    }
+}
+
+// ============================================================================
+//  Gaussian Splat Loader
+// ============================================================================
+
+const REQUIRED_SPLAT_PROPERTIES: [&str; 14] = [
+    "x", "y", "z",
+    "f_dc_0", "f_dc_1", "f_dc_2",
+    "opacity",
+    "scale_0", "scale_1", "scale_2",
+    "rot_0", "rot_1", "rot_2", "rot_3",
+];
+
+// One vertex property: column index (ASCII) and byte offset / type (binary)
+#[derive(Copy, Clone, Debug)]
+struct Field {
+    index: usize,
+    offset: usize,
+    ty: PlyScalar,
+}
+
+// Resolves property names to fields within one vertex record
+struct VertexLayout<'a> {
+    element: &'a PlyElement,
+    stride: usize,
+}
+
+impl<'a> VertexLayout<'a> {
+    fn new(element: &'a PlyElement) -> GsResult<Self> {
+        let stride = element.stride().ok_or_else(|| {
+            let list = element.properties.iter().find(|p| matches!(p.kind, PlyPropertyKind::List { .. }));
+            GsError::ply_property(
+                list.map_or("", |p| &p.name),
+                format!("List properties are not supported in element '{}'", element.name),
+            )
+        })?;
+        Ok(Self { element, stride })
+    }
+
+    fn find(&self, name: &str) -> Option<Field> {
+        let mut offset = 0;
+        for (index, p) in self.element.properties.iter().enumerate() {
+            let PlyPropertyKind::Scalar(ty) = p.kind else { unreachable!() };
+            if p.name == name { return Some(Field { index, offset, ty }); }
+            offset += ty.size();
+        }
+        None
+    }
+
+    fn require(&self, names: &[&str]) -> GsResult<Vec<Field>> {
+        let missing: Vec<&str> = names.iter().copied().filter(|n| self.find(n).is_none()).collect();
+        if !missing.is_empty() {
+            return Err(GsError::ply_property(missing[0], format!(
+                "PLY element '{}' is missing required properties [{}]; found [{}]",
+                self.element.name, missing.join(", "), self.element.property_names().join(", "),
+            )));
+        }
+        Ok(names.iter().map(|n| self.find(n).unwrap()).collect())
+    }
+}
+
+// Body of the 'vertex' element in either encoding
+enum VertexRecords<'a> {
+    Binary { records: &'a [u8], stride: usize, big_endian: bool },
+    // ASCII は数値に変換した表 (行優先)
+    Ascii { values: Vec<f32>, columns: usize },
+}
+
+impl<'a> VertexRecords<'a> {
+    fn new(header: &PlyHeader, data: &'a [u8], layout: &VertexLayout) -> GsResult<Self> {
+        let vertex = layout.element;
+        match header.format {
+            PlyFormat::Ascii => {
+                let columns = vertex.properties.len();
+                // ヘッダのレコード数を信用して確保しない (1 値あたり最低 1 バイト)
+                let value_len = vertex.count.checked_mul(columns)
+                    .ok_or_else(|| GsError::ply(format!("PLY vertex count {} overflows the record size", vertex.count)))?;
+                let body_len = data.len() - header.body_offset;
+                if body_len < value_len {
+                    return Err(GsError::ply(format!(
+                        "PLY body is truncated: header declares {} vertices ({} values) but only {} bytes follow",
+                        vertex.count, value_len, body_len,
+                    )));
+                }
+                let body = std::str::from_utf8(&data[header.body_offset..]).map_err(|_| GsError::ply("ASCII PLY body is not valid text"))?;
+                let mut lines = body.lines().filter(|l| !l.trim().is_empty());
+                // 先行する要素は 1 レコード 1 行
+                for e in header.elements.iter().take_while(|e| e.name != vertex.name) {
+                    for _ in 0..e.count { lines.next(); }
+                }
+                let mut values = Vec::with_capacity(value_len);
+                for i in 0..vertex.count {
+                    let line = lines.next().ok_or_else(|| GsError::ply(format!(
+                        "PLY body is truncated: header declares {} vertices but only {} lines follow", vertex.count, i,
+                    )))?;
+                    let row_start = values.len();
+                    for (column, token) in line.split_whitespace().take(columns).enumerate() {
+                        values.push(token.parse::<f32>().map_err(|_| GsError::ply_property(
+                            &vertex.properties[column].name,
+                            format!("Invalid number '{}' in vertex {}", token, i),
+                        ))?);
+                    }
+                    if values.len() - row_start != columns {
+                        return Err(GsError::ply(format!("Vertex {} has {} values, expected {}", i, values.len() - row_start, columns)));
+                    }
+                }
+                Ok(Self::Ascii { values, columns })
+            }
+            PlyFormat::BinaryLittleEndian | PlyFormat::BinaryBigEndian => {
+                let start = header.element_offset(data, &vertex.name)?;
+                let byte_len = layout.stride.checked_mul(vertex.count)
+                    .ok_or_else(|| GsError::ply(format!("PLY vertex count {} overflows the record size", vertex.count)))?;
+                let end = start.checked_add(byte_len).filter(|&end| end <= data.len()).ok_or_else(|| GsError::ply(format!(
+                    "PLY body is truncated: header declares {} vertices ({} bytes) but only {} bytes follow",
+                    vertex.count, byte_len, data.len().saturating_sub(start),
+                )))?;
+                Ok(Self::Binary {
+                    records: &data[start..end],
+                    stride: layout.stride,
+                    big_endian: header.format == PlyFormat::BinaryBigEndian,
+                })
+            }
+        }
+    }
+
+    fn read(&self, i: usize, field: Field) -> f32 {
+        match self {
+            Self::Binary { records, stride, big_endian } => field.ty.read(&records[i * stride + field.offset..], *big_endian),
+            Self::Ascii { values, columns } => values[i * columns + field.index],
+        }
+    }
+}
+
+// Counts the contiguous f_rest_0.. properties and converts them to an SH degree.
+// 完全なバンド (9 / 24 / 45 個) にならない f_rest_* は余分なプロパティとして無視し、DC のみ読み込む
+fn sh_rest_layout(layout: &VertexLayout) -> (u32, Vec<Field>) {
+    let fields: Vec<_> = (0..).map_while(|i| layout.find(&format!("f_rest_{}", i))).collect();
+    let degree = match fields.len() % 3 {
+        0 => sh::degree_for_rest_count(fields.len() / 3),
+        _ => None,
+    };
+    match degree {
+        Some(degree) => (degree, fields),
+        None => {
+            let names: Vec<String> = (0..fields.len()).map(|i| format!("f_rest_{}", i)).collect();
+            log::warn!(
+                "Ignoring {} f_rest_* properties that do not form complete SH bands (expected 9, 24 or 45): {}. Loading DC color only.",
+                fields.len(), names.join(", "),
+            );
+            (0, Vec::new())
+        }
+    }
+}
+
+// True if the vertex element carries the trained 3DGS attributes (as opposed to a plain point cloud)
+pub fn is_gaussian_ply(header: &PlyHeader) -> bool {
+    header.element("vertex").is_some_and(|v| {
+        let names = v.property_names();
+        REQUIRED_SPLAT_PROPERTIES.iter().all(|p| names.contains(p))
+    })
+}
+
+// Parses a trained 3DGS PLY (binary or ASCII), mapping vertex properties by name
+pub fn read_gaussian_ply(data: &[u8], options: &LoadOptions) -> GsResult<SplatData> {
+    let header = PlyHeader::parse(data)?;
+    let vertex = header.element("vertex").ok_or_else(|| GsError::ply("PLY has no 'vertex' element"))?;
+    let layout = VertexLayout::new(vertex)?;
+    let fields = layout.require(&REQUIRED_SPLAT_PROPERTIES)?;
+    let (sh_degree, rest_fields) = sh_rest_layout(&layout);
+    let records = VertexRecords::new(&header, data, &layout)?;
+
+    let splats = (0..vertex.count).map(|i| {
+        let v = |f: usize| records.read(i, fields[f]);
+        GaussianSplat {
+            pos: [v(0), v(1), v(2)],
+            opacity: options.value_domain.activate_opacity(v(6)),
+            scale: options.value_domain.activate_scale([v(7), v(8), v(9)]),
+            _pad1: 0.0,
+            rot: options.quat_order.to_xyzw([v(10), v(11), v(12), v(13)]),
+            sh_dc: [v(3), v(4), v(5)],
+            _pad2: 0.0,
+        }
+    }).collect();
+
+    // f_rest_{c * K + k} (チャンネル優先) → 係数 k ごとの RGB インターリーブへ並べ替え
+    let per_channel = rest_fields.len() / 3;
+    let mut coeffs = Vec::with_capacity(vertex.count * rest_fields.len());
+    for i in 0..vertex.count {
+        for k in 0..per_channel {
+            for c in 0..3 {
+                coeffs.push(records.read(i, rest_fields[c * per_channel + k]));
+            }
+        }
+    }
+
+    Ok(SplatData { splats, sh: ShCoeffs { degree: sh_degree, coeffs } })
+}
+
+// ============================================================================
+//  Point Cloud Loader (xyz / rgb / normal)
+// ============================================================================
+
+// Plain point cloud PLY (LiDAR / RGB-D / this crate's surfel export), binary or ASCII
+pub fn read_point_cloud_ply(data: &[u8]) -> GsResult<PointCloud> {
+    let header = PlyHeader::parse(data)?;
+    let vertex = header.element("vertex").ok_or_else(|| GsError::ply("PLY has no 'vertex' element"))?;
+    let layout = VertexLayout::new(vertex)?;
+    let xyz = layout.require(&["x", "y", "z"])?;
+    let records = VertexRecords::new(&header, data, &layout)?;
+    let read3 = |i: usize, f: &[Field]| [records.read(i, f[0]), records.read(i, f[1]), records.read(i, f[2])];
+    let optional = |names: [&str; 3]| -> Option<Vec<Field>> { names.iter().map(|n| layout.find(n)).collect() };
+
+    let positions = (0..vertex.count).map(|i| read3(i, &xyz)).collect();
+    // 色は整数型なら 0-255、浮動小数なら 0-1 とみなす
+    let colors = optional(["red", "green", "blue"]).or_else(|| optional(["r", "g", "b"])).map(|f| {
+        let scale = if matches!(f[0].ty, PlyScalar::Float | PlyScalar::Double) { 1.0 } else { 1.0 / 255.0 };
+        (0..vertex.count).map(|i| read3(i, &f).map(|c| c * scale)).collect()
+    });
+    let normals = optional(["nx", "ny", "nz"]).or_else(|| optional(["normal_x", "normal_y", "normal_z"]))
+        .map(|f| (0..vertex.count).map(|i| read3(i, &f)).collect());
+
+    Ok(PointCloud { positions, colors, normals })
+}
+
+// ============================================================================
+//  Writers
+// ============================================================================
+
+// Body encoding of exported PLY files
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum PlyEncoding {
+    Ascii,
+    #[default]
+    BinaryLittleEndian,
+}
+
+impl std::str::FromStr for PlyEncoding {
+    type Err = GsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "ascii" => Ok(Self::Ascii),
+            "binary" | "binary_little_endian" => Ok(Self::BinaryLittleEndian),
+            _ => Err(GsError::invalid(format!("Unknown PLY encoding '{}' (expected 'binary' or 'ascii')", s))),
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+enum PlyValue {
+    Float(f32),
+    UChar(u8),
+}
+
+// Writes a single 'vertex' element; `row` fills the values of one vertex in property order
+fn write_vertex_ply<W: Write>(
+    w: &mut W,
+    encoding: PlyEncoding,
+    properties: &[(String, PlyScalar)],
+    count: usize,
+    mut row: impl FnMut(usize, &mut Vec<PlyValue>),
+) -> io::Result<()> {
+    let format = match encoding {
+        PlyEncoding::Ascii => "ascii",
+        PlyEncoding::BinaryLittleEndian => "binary_little_endian",
+    };
+    let mut header = format!("ply\nformat {} 1.0\ncomment generated by gs-slam-core\nelement vertex {}\n", format, count);
+    for (name, ty) in properties {
+        header.push_str(&format!("property {} {}\n", ty.name(), name));
+    }
+    header.push_str("end_header\n");
+    w.write_all(header.as_bytes())?;
+
+    let stride: usize = properties.iter().map(|(_, ty)| ty.size()).sum();
+    let mut values = Vec::with_capacity(properties.len());
+    let mut line = String::new();
+    // 1行ずつ write するとシステムコールが多すぎるので、ある程度まとめて書き出す
+    let mut buf = Vec::with_capacity(stride * 4096);
+    for i in 0..count {
+        values.clear();
+        row(i, &mut values);
+        match encoding {
+            PlyEncoding::BinaryLittleEndian => for v in &values {
+                match *v {
+                    PlyValue::Float(f) => buf.extend_from_slice(&f.to_le_bytes()),
+                    PlyValue::UChar(u) => buf.push(u),
+                }
+            },
+            PlyEncoding::Ascii => {
+                line.clear();
+                for (j, v) in values.iter().enumerate() {
+                    if j > 0 { line.push(' '); }
+                    match *v {
+                        PlyValue::Float(f) => line.push_str(&f.to_string()),
+                        PlyValue::UChar(u) => line.push_str(&u.to_string()),
+                    }
+                }
+                line.push('\n');
+                buf.extend_from_slice(line.as_bytes());
+            }
+        }
+        if buf.len() >= stride * 4096 {
+            w.write_all(&buf)?;
+            buf.clear();
+        }
+    }
+    w.write_all(&buf)
+}
+
+fn float_props(names: &[&str]) -> Vec<(String, PlyScalar)> {
+    names.iter().map(|n| (n.to_string(), PlyScalar::Float)).collect()
+}
+
+// Surfel cloud: x y z / red green blue (uchar) / nx ny nz
+pub fn write_surfel_ply<W: Write>(w: &mut W, surfels: &[Surfel], encoding: PlyEncoding) -> io::Result<()> {
+    let mut properties = float_props(&["x", "y", "z"]);
+    properties.extend(["red", "green", "blue"].map(|n| (n.to_string(), PlyScalar::UChar)));
+    properties.extend(float_props(&["nx", "ny", "nz"]));
+
+    write_vertex_ply(w, encoding, &properties, surfels.len(), |i, out| {
+        let s = &surfels[i];
+        out.extend(s.pos.map(PlyValue::Float));
+        out.extend(s.color.map(|c| PlyValue::UChar((c * 255.0) as u8)));
+        out.extend(s.normal.map(PlyValue::Float));
+    })
+}
+
+// 3DGS trainer layout (x y z nx ny nz f_dc_* f_rest_* opacity scale_* rot_*), i.e. what
+// read_gaussian_ply and standard viewers expect: log scale, logit opacity and (w, x, y, z) rotations
+pub fn write_gaussian_ply<W: Write>(w: &mut W, splats: &[GaussianSplat], sh: &ShCoeffs, encoding: PlyEncoding) -> io::Result<()> {
+    let per_channel = sh::rest_count(sh.degree);
+    let mut properties = float_props(&["x", "y", "z", "nx", "ny", "nz", "f_dc_0", "f_dc_1", "f_dc_2"]);
+    properties.extend((0..per_channel * 3).map(|i| (format!("f_rest_{}", i), PlyScalar::Float)));
+    properties.extend(float_props(&["opacity", "scale_0", "scale_1", "scale_2", "rot_0", "rot_1", "rot_2", "rot_3"]));
+
+    let domain = ValueDomain::PreActivation;
+    write_vertex_ply(w, encoding, &properties, splats.len(), |i, out| {
+        let s = &splats[i];
+        out.extend(s.pos.map(PlyValue::Float));
+        out.extend([0.0; 3].map(PlyValue::Float));
+        out.extend(s.sh_dc.map(PlyValue::Float));
+        // 係数ごとの RGB インターリーブ → f_rest_{c * K + k} (チャンネル優先)
+        let rest = sh.splat(i);
+        for c in 0..3 {
+            out.extend((0..per_channel).map(|k| PlyValue::Float(rest[k * 3 + c])));
+        }
+        out.push(PlyValue::Float(domain.deactivate_opacity(s.opacity)));
+        out.extend(domain.deactivate_scale(s.scale).map(PlyValue::Float));
+        out.extend(QuatOrder::Wxyz.from_xyzw(s.rot).map(PlyValue::Float));
+    })
+}
@@ -0,0 +1,166 @@
+use crate::error::{GsError, GsResult};
+use crate::pcd;
+use crate::ply;
+use crate::sh::{ShCoeffs, SH_C0};
+#[cfg(feature = "wasm")]
+use crate::splat::{self, SplatFormat};
+use crate::{GaussianSplat, SplatData};
+
+// ============================================================================
+//  Point Cloud Import (non-Gaussian sources)
+// ============================================================================
+//
+// LiDAR / RGB-D の点群 (PCD, PLY) から GaussianSplat を合成し、
+// 学習済み 3DGS と同じ幾何計算・SR・エクスポートを適用できるようにする。
+
+// Plain points with optional color (0.0 - 1.0) and normal
+#[derive(Clone, Debug, Default)]
+pub struct PointCloud {
+    pub positions: Vec<[f32; 3]>,
+    pub colors: Option<Vec<[f32; 3]>>,
+    pub normals: Option<Vec<[f32; 3]>>,
+}
+
+// Shape of the synthesized splats
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum SplatShape {
+    // Flat when the cloud has normals, isotropic otherwise
+    #[default]
+    Auto,
+    // Sphere of the given radius
+    Isotropic,
+    // Disc whose thinnest axis follows the point normal (requires normals)
+    Flat,
+}
+
+impl std::str::FromStr for SplatShape {
+    type Err = GsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "auto" => Ok(Self::Auto),
+            "isotropic" => Ok(Self::Isotropic),
+            "flat" => Ok(Self::Flat),
+            _ => Err(GsError::invalid(format!("Unknown splat shape '{}' (expected 'auto', 'isotropic' or 'flat')", s))),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct SynthesisOptions {
+    pub shape: SplatShape,
+    // Splat radius (linear scale). None estimates the mean point spacing from the bounding box.
+    pub radius: Option<f32>,
+    // Activated opacity (0.0 - 1.0)
+    pub opacity: f32,
+}
+
+impl Default for SynthesisOptions {
+    fn default() -> Self {
+        Self { shape: SplatShape::Auto, radius: None, opacity: 1.0 }
+    }
+}
+
+// Flat splat thickness relative to its radius (well below the SR aspect-ratio filter)
+const FLAT_THICKNESS: f32 = 0.01;
+
+// Reads a PCD or PLY point cloud, detected from the file magic
+pub fn read_point_cloud(data: &[u8]) -> GsResult<PointCloud> {
+    if data.starts_with(b"ply") {
+        ply::read_point_cloud_ply(data)
+    } else {
+        from_pcd(&pcd::read_pcd(data)?)
+    }
+}
+
+fn from_pcd(cloud: &pcd::PcdCloud) -> GsResult<PointCloud> {
+    let columns = |names: [&str; 3]| -> Option<Vec<[f32; 3]>> {
+        let [a, b, c] = names.map(|n| cloud.field(n));
+        Some(a?.into_iter().zip(b?).zip(c?).map(|((a, b), c)| [a, b, c]).collect())
+    };
+
+    let positions = columns(["x", "y", "z"]).ok_or_else(|| GsError::format("PCD", "no x / y / z fields"))?;
+    let colors = cloud.rgb()
+        .map(|rgb| rgb.into_iter().map(|c| c.map(|v| v as f32 / 255.0)).collect())
+        .or_else(|| columns(["r", "g", "b"]).map(|rgb| rgb.into_iter().map(|c| c.map(|v| v / 255.0)).collect()));
+    let normals = columns(["normal_x", "normal_y", "normal_z"]);
+
+    Ok(PointCloud { positions, colors, normals })
+}
+
+// Mean point spacing assuming the points cover their bounding box evenly.
+// 厚みのない軸 (平面・直線状の点群) は次元から除外する
+pub fn estimate_spacing(positions: &[[f32; 3]]) -> f32 {
+    if positions.len() < 2 { return 1.0; }
+    let mut min = [f32::MAX; 3];
+    let mut max = [f32::MIN; 3];
+    for p in positions {
+        for a in 0..3 {
+            min[a] = min[a].min(p[a]);
+            max[a] = max[a].max(p[a]);
+        }
+    }
+    let extents: Vec<f64> = (0..3).map(|a| (max[a] - min[a]) as f64).filter(|&e| e > 1e-6).collect();
+    if extents.is_empty() { return 1.0; }
+    let volume: f64 = extents.iter().product();
+    (volume / positions.len() as f64).powf(1.0 / extents.len() as f64) as f32
+}
+
+// Quaternion (x, y, z, w) rotating +Z onto `n`
+fn rotation_to_normal(n: [f32; 3]) -> Option<[f32; 4]> {
+    let len = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+    if !len.is_finite() || len < 1e-6 { return None; }
+    let [x, y, z] = n.map(|v| v / len);
+    if z < -0.999_999 {
+        // 真逆向きは X 軸回りに 180 度
+        return Some([1.0, 0.0, 0.0, 0.0]);
+    }
+    // q = (cross(z_axis, n), 1 + dot(z_axis, n)) を正規化
+    let q = [-y, x, 0.0, 1.0 + z];
+    let norm = (q[0] * q[0] + q[1] * q[1] + q[3] * q[3]).sqrt();
+    Some(q.map(|v| v / norm))
+}
+
+pub fn synthesize_splats(cloud: &PointCloud, options: &SynthesisOptions) -> GsResult<SplatData> {
+    let flat = match options.shape {
+        SplatShape::Auto => cloud.normals.is_some(),
+        SplatShape::Isotropic => false,
+        SplatShape::Flat if cloud.normals.is_some() => true,
+        SplatShape::Flat => return Err(GsError::invalid("shape='flat' requires a point cloud with normals")),
+    };
+    let radius = options.radius.unwrap_or_else(|| estimate_spacing(&cloud.positions));
+    if !(radius > 0.0 && radius.is_finite()) {
+        return Err(GsError::invalid(format!("Splat radius must be positive, got {}", radius)));
+    }
+
+    let splats = cloud.positions.iter().enumerate().map(|(i, &pos)| {
+        let rgb = cloud.colors.as_ref().map_or([0.5; 3], |c| c[i]);
+        // 法線が無効な点は球にする
+        let rot = cloud.normals.as_ref().filter(|_| flat).and_then(|n| rotation_to_normal(n[i]));
+        let (rot, scale) = match rot {
+            Some(q) => (q, [radius, radius, radius * FLAT_THICKNESS]),
+            None => ([0.0, 0.0, 0.0, 1.0], [radius; 3]),
+        };
+        GaussianSplat {
+            pos,
+            opacity: options.opacity,
+            scale,
+            rot,
+            sh_dc: rgb.map(|c| (c - 0.5) / SH_C0),
+            ..Default::default()
+        }
+    }).collect();
+
+    Ok(SplatData { splats, sh: ShCoeffs::default() })
+}
+
+// Trained splats (3DGS PLY, SPZ, .ksplat, .splat), otherwise a synthesized point cloud
+#[cfg(feature = "wasm")]
+pub fn load_splats(data: &[u8], load: &crate::LoadOptions, synthesis: &SynthesisOptions) -> GsResult<SplatData> {
+    match SplatFormat::sniff(data) {
+        Some(SplatFormat::Ply) if !ply::is_gaussian_ply(&ply::PlyHeader::parse(data)?) => {},
+        Some(_) => return splat::read_splats(data, load),
+        None => {},
+    }
+    synthesize_splats(&read_point_cloud(data)?, synthesis)
+}
@@ -0,0 +1,111 @@
+use std::borrow::Cow;
+
+// Elements scanned by one workgroup (workgroup_size in scan.wgsl)
+const BLOCK: u32 = 256;
+// Level offsets are rounded up to this many u32 so that every binding is 256-byte aligned
+const ALIGN: u64 = 64;
+
+// Start (in u32) and length of every level of a scan over `len` elements.
+// Level 0 is the input; level k + 1 holds the block sums of level k; the last level is the grand total.
+fn levels(len: u32) -> Vec<(u64, u32)> {
+    let mut levels = vec![(0, len)];
+    loop {
+        let (start, len) = *levels.last().unwrap();
+        let next = (start + len as u64).div_ceil(ALIGN) * ALIGN;
+        levels.push((next, len.div_ceil(BLOCK)));
+        if len <= BLOCK { return levels; }
+    }
+}
+
+// Number of u32 the scan buffer needs for `len` elements (input + block sums)
+pub fn buffer_len(len: u32) -> u64 {
+    let (start, len) = *levels(len).last().unwrap();
+    start + len as u64
+}
+
+// In-place exclusive prefix sum over a u32 buffer (scan_blocks / add_offsets in scan.wgsl)
+pub struct ScanPipeline {
+    scan: wgpu::ComputePipeline,
+    add: wgpu::ComputePipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+}
+
+impl ScanPipeline {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Scan Shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("scan.wgsl"))),
+        });
+
+        let storage = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Storage { read_only: false }, has_dynamic_offset: false, min_binding_size: None },
+            count: None,
+        };
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Scan Bind Group Layout"),
+            entries: &[
+                // Data (scanned in place)
+                storage(0),
+                // Block sums (next level)
+                storage(1),
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Scan Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = |label, entry_point| device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(label),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: Some(entry_point),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        Self {
+            scan: pipeline("Scan Blocks Pipeline", "scan_blocks"),
+            add: pipeline("Scan Add Offsets Pipeline", "add_offsets"),
+            bind_group_layout,
+        }
+    }
+
+    // Scans the first `len` u32 of `buffer` in place. The buffer must hold buffer_len(len) u32;
+    // the space after the input is used for the block sums.
+    pub fn encode(&self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, buffer: &wgpu::Buffer, len: u32) {
+        let levels = levels(len);
+        let bind_groups: Vec<wgpu::BindGroup> = levels.windows(2).map(|pair| {
+            let [(data_start, data_len), (sums_start, sums_len)] = [pair[0], pair[1]];
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Scan Bind Group"),
+                layout: &self.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry { binding: 0, resource: crate::buffer_range(buffer, data_start * 4, data_len as u64 * 4) },
+                    wgpu::BindGroupEntry { binding: 1, resource: crate::buffer_range(buffer, sums_start * 4, sums_len as u64 * 4) },
+                ],
+            })
+        }).collect();
+
+        // 下のレベルから順にブロック内を走査し、ブロック合計を上のレベルへ
+        for (bind_group, &(_, len)) in bind_groups.iter().zip(&levels) {
+            self.pass(encoder, &self.scan, bind_group, len);
+        }
+        // 走査済みのブロック合計を上のレベルから順に足し戻す (最上位は 1 ブロックなので不要)
+        for (bind_group, &(_, len)) in bind_groups.iter().zip(&levels).rev().skip(1) {
+            self.pass(encoder, &self.add, bind_group, len);
+        }
+    }
+
+    fn pass(&self, encoder: &mut wgpu::CommandEncoder, pipeline: &wgpu::ComputePipeline, bind_group: &wgpu::BindGroup, len: u32) {
+        let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+        cpass.set_pipeline(pipeline);
+        cpass.set_bind_group(0, bind_group, &[]);
+        cpass.dispatch_workgroups(len.div_ceil(BLOCK), 1, 1);
+    }
+}
@@ -0,0 +1,47 @@
+// src/scan.wgsl
+// Exclusive prefix sum (u32)。256 要素ごとにワークグループ内で走査し、
+// ブロック合計を上のレベルで走査してから add_offsets で足し戻す (src/scan.rs)
+
+@group(0) @binding(0) var<storage, read_write> data : array<u32>;
+@group(0) @binding(1) var<storage, read_write> block_sums : array<u32>;
+
+var<workgroup> temp : array<u32, 256>;
+
+@compute @workgroup_size(256)
+fn scan_blocks(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+    @builtin(local_invocation_id) local_id: vec3<u32>,
+    @builtin(workgroup_id) group_id: vec3<u32>,
+) {
+    let idx = global_id.x;
+    let lane = local_id.x;
+
+    var value = 0u;
+    if (idx < arrayLength(&data)) { value = data[idx]; }
+    temp[lane] = value;
+    workgroupBarrier();
+
+    // Hillis-Steele (inclusive)
+    for (var offset = 1u; offset < 256u; offset = offset * 2u) {
+        var add = 0u;
+        if (lane >= offset) { add = temp[lane - offset]; }
+        workgroupBarrier();
+        temp[lane] = temp[lane] + add;
+        workgroupBarrier();
+    }
+
+    if (idx < arrayLength(&data)) { data[idx] = temp[lane] - value; }
+    if (lane == 255u) { block_sums[group_id.x] = temp[255]; }
+}
+
+// block_sums は走査済み (各ブロックより前の合計)
+@compute @workgroup_size(256)
+fn add_offsets(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+    @builtin(workgroup_id) group_id: vec3<u32>,
+) {
+    let idx = global_id.x;
+    if (idx < arrayLength(&data)) {
+        data[idx] = data[idx] + block_sums[group_id.x];
+    }
+}
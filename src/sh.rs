@@ -0,0 +1,86 @@
+// ============================================================================
+//  Spherical Harmonics (degree 0-3)
+// ============================================================================
+//
+// 係数の並びは「スプラット → 係数 → チャンネル(RGB)」の順 (インターリーブ)。
+// PLY の f_rest_* はチャンネル優先 (R の全係数, G の全係数, B の全係数) なので
+// ローダー側で並べ替えてから格納する。
+
+pub const SH_C0: f32 = 0.282_094_8;
+const SH_C1: f32 = 0.488_602_5;
+const SH_C2: [f32; 5] = [1.092_548_5, -1.092_548_5, 0.315_391_57, -1.092_548_5, 0.546_274_24];
+const SH_C3: [f32; 7] = [
+    -0.590_043_6, 2.890_611_4, -0.457_045_8, 0.373_176_34, -0.457_045_8, 1.445_305_7, -0.590_043_6,
+];
+
+pub const MAX_SH_DEGREE: u32 = 3;
+
+// Number of non-DC coefficients per color channel for a given degree
+pub fn rest_count(degree: u32) -> usize {
+    ((degree + 1) * (degree + 1) - 1) as usize
+}
+
+// Inverse of rest_count for the values found in 3DGS files (0, 3, 8, 15)
+pub fn degree_for_rest_count(count: usize) -> Option<u32> {
+    (0..=MAX_SH_DEGREE).find(|&d| rest_count(d) == count)
+}
+
+// Higher-order SH coefficients stored alongside the splats (f_rest_*)
+#[derive(Clone, Debug, Default)]
+pub struct ShCoeffs {
+    pub degree: u32,
+    pub coeffs: Vec<f32>,
+}
+
+impl ShCoeffs {
+    // Floats per splat (coefficients x RGB)
+    pub fn stride(&self) -> usize {
+        rest_count(self.degree) * 3
+    }
+
+    pub fn splat(&self, idx: usize) -> &[f32] {
+        let stride = self.stride();
+        &self.coeffs[idx * stride..(idx + 1) * stride]
+    }
+}
+
+// View-dependent color for one splat. `dir` is the normalized direction from the camera to the splat.
+// degree 0 (or an empty `rest`) reduces to the DC-only decode.
+pub fn eval_sh_cpu(sh_dc: [f32; 3], rest: &[f32], degree: u32, dir: [f32; 3]) -> [f32; 3] {
+    let mut rgb = [SH_C0 * sh_dc[0], SH_C0 * sh_dc[1], SH_C0 * sh_dc[2]];
+
+    if degree > 0 && !rest.is_empty() {
+        let [x, y, z] = dir;
+        let (xx, yy, zz) = (x * x, y * y, z * z);
+        let (xy, yz, xz) = (x * y, y * z, x * z);
+
+        let mut basis = [0.0f32; 15];
+        basis[0] = -SH_C1 * y;
+        basis[1] = SH_C1 * z;
+        basis[2] = -SH_C1 * x;
+        if degree > 1 {
+            basis[3] = SH_C2[0] * xy;
+            basis[4] = SH_C2[1] * yz;
+            basis[5] = SH_C2[2] * (2.0 * zz - xx - yy);
+            basis[6] = SH_C2[3] * xz;
+            basis[7] = SH_C2[4] * (xx - yy);
+        }
+        if degree > 2 {
+            basis[8] = SH_C3[0] * y * (3.0 * xx - yy);
+            basis[9] = SH_C3[1] * xy * z;
+            basis[10] = SH_C3[2] * y * (4.0 * zz - xx - yy);
+            basis[11] = SH_C3[3] * z * (2.0 * zz - 3.0 * xx - 3.0 * yy);
+            basis[12] = SH_C3[4] * x * (4.0 * zz - xx - yy);
+            basis[13] = SH_C3[5] * z * (xx - yy);
+            basis[14] = SH_C3[6] * x * (xx - 3.0 * yy);
+        }
+
+        for (k, b) in basis.iter().enumerate().take(rest_count(degree)) {
+            for (c, out) in rgb.iter_mut().enumerate() {
+                *out += b * rest[k * 3 + c];
+            }
+        }
+    }
+
+    rgb.map(|v| (v + 0.5).clamp(0.0, 1.0))
+}
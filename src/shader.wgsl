@@ -10,11 +10,11 @@ struct GaussianSplat {
 
 struct Surfel {
     pos: vec3<f32>,
-    _pad0: f32,
+    radius: f32,
     color: vec3<f32>,
-    _pad1: f32,
+    opacity: f32,
     normal: vec3<f32>,
-    _pad2: f32,
+    curvature: f32,
 };
 
 struct CameraUniform {
@@ -25,10 +25,21 @@ struct CameraUniform {
     _pad3: vec3<u32>, // Pad to 16 bytes alignment
 };
 
+struct GeometryParams {
+    view_dir: vec3<f32>,
+    sh_degree: u32,
+    camera_pos: vec3<f32>,
+    // 0: DC only, 1: fixed view_dir, 2: per-splat direction from camera_pos
+    view_mode: u32,
+};
+
 // --- Compute Shader ---
 
 @group(0) @binding(0) var<storage, read> input_splats : array<GaussianSplat>;
 @group(0) @binding(1) var<storage, read_write> output_surfels : array<Surfel>;
+// Higher-order SH, per splat: ((degree+1)^2 - 1) coefficients x RGB (interleaved)
+@group(0) @binding(2) var<storage, read> sh_rest : array<f32>;
+@group(0) @binding(3) var<uniform> geometry_params : GeometryParams;
 
 fn quat_to_mat3(q: vec4<f32>) -> mat3x3<f32> {
     let x = q.x; let y = q.y; let z = q.z; let w = q.w;
@@ -42,6 +53,44 @@ fn quat_to_mat3(q: vec4<f32>) -> mat3x3<f32> {
     );
 }
 
+fn sh_coeff(base: u32, k: u32) -> vec3<f32> {
+    let i = base + k * 3u;
+    return vec3<f32>(sh_rest[i], sh_rest[i + 1u], sh_rest[i + 2u]);
+}
+
+// View-dependent color (same basis/ordering as sh::eval_sh_cpu)
+fn eval_sh(idx: u32, sh_dc: vec3<f32>, dir: vec3<f32>, degree: u32) -> vec3<f32> {
+    var rgb = 0.2820947917 * sh_dc;
+    if (degree == 0u) { return clamp(rgb + 0.5, vec3<f32>(0.0), vec3<f32>(1.0)); }
+
+    let rest = (degree + 1u) * (degree + 1u) - 1u;
+    let base = idx * rest * 3u;
+    let x = dir.x; let y = dir.y; let z = dir.z;
+    let xx = x*x; let yy = y*y; let zz = z*z;
+    let xy = x*y; let yz = y*z; let xz = x*z;
+
+    let C1 = 0.4886025119;
+    rgb += -C1 * y * sh_coeff(base, 0u) + C1 * z * sh_coeff(base, 1u) - C1 * x * sh_coeff(base, 2u);
+
+    if (degree > 1u) {
+        rgb += 1.0925484306 * xy * sh_coeff(base, 3u)
+             - 1.0925484306 * yz * sh_coeff(base, 4u)
+             + 0.3153915653 * (2.0 * zz - xx - yy) * sh_coeff(base, 5u)
+             - 1.0925484306 * xz * sh_coeff(base, 6u)
+             + 0.5462742153 * (xx - yy) * sh_coeff(base, 7u);
+    }
+    if (degree > 2u) {
+        rgb += -0.5900435899 * y * (3.0 * xx - yy) * sh_coeff(base, 8u)
+             + 2.8906114426 * xy * z * sh_coeff(base, 9u)
+             - 0.4570457995 * y * (4.0 * zz - xx - yy) * sh_coeff(base, 10u)
+             + 0.3731763326 * z * (2.0 * zz - 3.0 * xx - 3.0 * yy) * sh_coeff(base, 11u)
+             - 0.4570457995 * x * (4.0 * zz - xx - yy) * sh_coeff(base, 12u)
+             + 1.4453057213 * z * (xx - yy) * sh_coeff(base, 13u)
+             - 0.5900435899 * x * (xx - 3.0 * yy) * sh_coeff(base, 14u);
+    }
+    return clamp(rgb + 0.5, vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
 @compute @workgroup_size(64)
 fn compute_main(@builtin(global_invocation_id) global_id: vec3<u32>) {
     let idx = global_id.x;
@@ -49,25 +98,22 @@ fn compute_main(@builtin(global_invocation_id) global_id: vec3<u32>) {
 
     let splat = input_splats[idx];
 
-    // SH (DC) to Color
-    let C0 = 0.2820947917;
-    var rgb = 0.5 + C0 * splat.sh_dc;
-    rgb = clamp(rgb, vec3<f32>(0.0), vec3<f32>(1.0));
+    // SH to Color (DC only unless a view direction is supplied)
+    var degree = 0u;
+    var dir = geometry_params.view_dir;
+    if (geometry_params.view_mode == 1u) {
+        degree = geometry_params.sh_degree;
+    } else if (geometry_params.view_mode == 2u) {
+        degree = geometry_params.sh_degree;
+        dir = normalize(splat.pos - geometry_params.camera_pos);
+    }
+    let rgb = eval_sh(idx, splat.sh_dc, dir, degree);
 
     // Normal Estimation
-    // rot is stored as [x, y, z, w] in Rust/Struct, so directly usable here if assumed same convention.
-    // If PLY is [w, x, y, z], mapping would be needed. 
-    // Usually PLY from 3DGS is [r, x, y, z] (w,x,y,z).
-    // Rust loader just copies bytes.
-    // Let's assume standard quaternion math works.
-    
-    // Normalization is key.
-    let q = normalize(splat.rot); 
-    // Note: If data is (w, x, y, z), q.x=w, q.y=x... which is mixed up for standard math (x,y,z,w).
-    // Correct mapping for (w, x, y, z) input to (x, y, z, w) logic:
-    let q_fixed = vec4<f32>(q.y, q.z, q.w, q.x); // x, y, z, w
-    
-    let R = quat_to_mat3(q_fixed);
+    // rot は読み込み時に (x, y, z, w) へ正規化済み (QuatOrder)
+    let q = normalize(splat.rot);
+    let R = quat_to_mat3(q);
+    // scale は読み込み時に活性化済み (線形スケール, ValueDomain)
     let s = abs(splat.scale);
     
     var local_n = vec3<f32>(0.0, 0.0, 1.0);
@@ -76,19 +122,90 @@ fn compute_main(@builtin(global_invocation_id) global_id: vec3<u32>) {
     
     let normal = normalize(R * local_n);
 
+    // Footprint radius / curvature (same as surfel_shape_cpu)
+    let var_s = s * s;
+    let total = var_s.x + var_s.y + var_s.z;
+    var curvature = 0.0;
+    if (total > 0.0) { curvature = min(var_s.x, min(var_s.y, var_s.z)) / total; }
+
     var out: Surfel;
     out.pos = splat.pos;
     out.color = rgb;
     out.normal = normal;
-    
-    // Explicit padding init (good practice)
-    out._pad0 = 0.0;
-    out._pad1 = 0.0;
-    out._pad2 = 0.0;
+    out.radius = max(s.x, max(s.y, s.z));
+    out.opacity = splat.opacity;
+    out.curvature = curvature;
 
     output_surfels[idx] = out;
 }
 
+// --- Normal Orientation (orient_main, src/normals.rs) ---
+// 各 Surfel の法線を、観測したキーフレームのうち最も多く現れるもの (無ければ最も近いカメラ) の方へ向ける
+
+struct OrientParams {
+    camera_count: u32,
+    // observers の 1 行 (Surfel 1 個) あたりの数。0 は観測情報なし
+    observer_width: u32,
+    _pad0: u32,
+    _pad1: u32,
+};
+
+const NO_OBSERVER: u32 = 0xffffffffu;
+
+@group(0) @binding(0) var<storage, read_write> orient_surfels : array<Surfel>;
+@group(0) @binding(1) var<storage, read> cameras : array<vec4<f32>>;
+@group(0) @binding(2) var<storage, read> observers : array<u32>;
+@group(0) @binding(3) var<uniform> orient_params : OrientParams;
+
+// 行の中で最も多く現れるキーフレーム (同数なら先に現れた方)
+fn most_frequent_observer(idx: u32) -> u32 {
+    let width = orient_params.observer_width;
+    let base = idx * width;
+    var best = NO_OBSERVER;
+    var best_count = 0u;
+    for (var i = 0u; i < width; i = i + 1u) {
+        let cam = observers[base + i];
+        if (cam == NO_OBSERVER) { continue; }
+        var count = 0u;
+        for (var j = 0u; j < width; j = j + 1u) {
+            if (observers[base + j] == cam) { count = count + 1u; }
+        }
+        if (count > best_count) {
+            best = cam;
+            best_count = count;
+        }
+    }
+    return best;
+}
+
+// (距離の 2 乗, インデックス) が最小のカメラ (CPU の KD-tree と同じ順序)
+fn nearest_camera(p: vec3<f32>) -> u32 {
+    var best = 0u;
+    var best_d2 = 3.4e38;
+    for (var i = 0u; i < orient_params.camera_count; i = i + 1u) {
+        let d = p - cameras[i].xyz;
+        let d2 = d.x * d.x + d.y * d.y + d.z * d.z;
+        if (d2 < best_d2) {
+            best = i;
+            best_d2 = d2;
+        }
+    }
+    return best;
+}
+
+@compute @workgroup_size(64)
+fn orient_main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let idx = global_id.x;
+    if (idx >= arrayLength(&orient_surfels)) { return; }
+
+    let surfel = orient_surfels[idx];
+    var cam = most_frequent_observer(idx);
+    if (cam == NO_OBSERVER) { cam = nearest_camera(surfel.pos); }
+    if (dot(surfel.normal, cameras[cam].xyz - surfel.pos) < 0.0) {
+        orient_surfels[idx].normal = -surfel.normal;
+    }
+}
+
 // --- Render Shader ---
 
 @group(0) @binding(0) var<uniform> camera: CameraUniform;
@@ -0,0 +1,120 @@
+use std::io::{self, Write};
+
+use crate::error::{GsError, GsResult};
+use crate::sh::{ShCoeffs, SH_C0};
+use crate::{GaussianSplat, SplatData};
+
+// ============================================================================
+//  antimatter15 .splat (32 bytes / splat, little endian)
+// ============================================================================
+//
+//  0: position  3 x f32
+// 12: scale     3 x f32 (線形スケール)
+// 24: color     4 x u8  (RGB = 0.5 + C0 * f_dc, A = opacity)
+// 28: rotation  4 x u8  (w, x, y, z) を q * 128 + 128 で量子化
+//
+// 高次SHは保存されない (DC のみ)。
+
+pub const SPLAT_RECORD_SIZE: usize = 32;
+
+fn f32_at(bytes: &[u8], at: usize) -> f32 {
+    f32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+}
+
+// Shared with .ksplat: 8-bit RGBA color of one splat
+pub fn encode_color(s: &GaussianSplat) -> [u8; 4] {
+    let quantize = |v: f32| (v * 255.0).round().clamp(0.0, 255.0) as u8;
+    let rgb = s.sh_dc.map(|dc| quantize(0.5 + SH_C0 * dc));
+    [rgb[0], rgb[1], rgb[2], quantize(s.opacity)]
+}
+
+// Returns (sh_dc, opacity)
+pub fn decode_color(rgba: [u8; 4]) -> ([f32; 3], f32) {
+    let sh_dc = [rgba[0], rgba[1], rgba[2]].map(|c| (c as f32 / 255.0 - 0.5) / SH_C0);
+    (sh_dc, rgba[3] as f32 / 255.0)
+}
+
+// Normalized (x, y, z, w); a zero quaternion becomes the identity
+pub fn normalize_quat(q: [f32; 4]) -> [f32; 4] {
+    let len = q.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if len > 0.0 && len.is_finite() { q.map(|v| v / len) } else { [0.0, 0.0, 0.0, 1.0] }
+}
+
+pub fn read_splat(data: &[u8]) -> GsResult<SplatData> {
+    if !data.len().is_multiple_of(SPLAT_RECORD_SIZE) {
+        return Err(GsError::format(".splat", format!("size {} is not a multiple of {} bytes", data.len(), SPLAT_RECORD_SIZE)));
+    }
+
+    let splats = data.chunks_exact(SPLAT_RECORD_SIZE).map(|r| {
+        let (sh_dc, opacity) = decode_color([r[24], r[25], r[26], r[27]]);
+        let [w, x, y, z] = [r[28], r[29], r[30], r[31]].map(|b| (b as f32 - 128.0) / 128.0);
+        GaussianSplat {
+            pos: [f32_at(r, 0), f32_at(r, 4), f32_at(r, 8)],
+            opacity,
+            scale: [f32_at(r, 12), f32_at(r, 16), f32_at(r, 20)],
+            rot: normalize_quat([x, y, z, w]),
+            sh_dc,
+            ..Default::default()
+        }
+    }).collect();
+
+    Ok(SplatData { splats, sh: ShCoeffs::default() })
+}
+
+pub fn write_splat<W: Write>(w: &mut W, splats: &[GaussianSplat]) -> io::Result<()> {
+    let mut body = Vec::with_capacity(splats.len() * SPLAT_RECORD_SIZE);
+    for s in splats {
+        for v in s.pos.iter().chain(&s.scale) {
+            body.extend_from_slice(&v.to_le_bytes());
+        }
+        body.extend_from_slice(&encode_color(s));
+        let [x, y, z, w] = normalize_quat(s.rot);
+        body.extend([w, x, y, z].map(|v| (v * 128.0 + 128.0).round().clamp(0.0, 255.0) as u8));
+    }
+    w.write_all(&body)
+}
+
+// ============================================================================
+//  Format Sniffing
+// ============================================================================
+
+// Files that carry trained splats (as opposed to plain point clouds)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SplatFormat {
+    Ply,
+    Spz,
+    Ksplat,
+    Splat,
+}
+
+impl SplatFormat {
+    // .splat にはマジックが無いので、他の形式に当てはまらず 32 バイト単位のものを .splat とみなす
+    pub fn sniff(data: &[u8]) -> Option<Self> {
+        if data.starts_with(b"ply") {
+            Some(Self::Ply)
+        } else if crate::spz::is_gzip(data) {
+            Some(Self::Spz)
+        } else if crate::ksplat::is_ksplat(data) {
+            Some(Self::Ksplat)
+        } else if is_pcd(data) || data.is_empty() || !data.len().is_multiple_of(SPLAT_RECORD_SIZE) {
+            None
+        } else {
+            Some(Self::Splat)
+        }
+    }
+}
+
+fn is_pcd(data: &[u8]) -> bool {
+    data.starts_with(b"#") || data.starts_with(b"VERSION") || data.starts_with(b"FIELDS")
+}
+
+// Gaussian PLY, SPZ, .ksplat or .splat. `load` only applies to PLY (the compact formats have a fixed layout)
+pub fn read_splats(data: &[u8], load: &crate::LoadOptions) -> GsResult<SplatData> {
+    match SplatFormat::sniff(data) {
+        Some(SplatFormat::Ply) => crate::ply::read_gaussian_ply(data, load),
+        Some(SplatFormat::Spz) => crate::spz::read_spz(data),
+        Some(SplatFormat::Ksplat) => crate::ksplat::read_ksplat(data),
+        Some(SplatFormat::Splat) => read_splat(data),
+        None => Err(GsError::format("splat file", "unrecognized format (expected PLY, SPZ, .ksplat or .splat)")),
+    }
+}
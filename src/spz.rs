@@ -0,0 +1,200 @@
+use std::io::{Read, Write};
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+
+use crate::error::{GsError, GsResult};
+use crate::sh::{self, ShCoeffs};
+use crate::splat::normalize_quat;
+use crate::{GaussianSplat, SplatData};
+
+// ============================================================================
+//  SPZ (Niantic compressed Gaussian splats, v2 / v3)
+// ============================================================================
+//
+// gzip の中身: 16 バイトのヘッダー + 属性ごとの配列 (SoA)
+//   magic u32 "NGSP" / version u32 / numPoints u32 / shDegree u8 / fractionalBits u8 / flags u8 / reserved u8
+//
+//   positions : 3 x int24 固定小数点 (値 = fixed / 2^fractionalBits)
+//   alphas    : u8 (活性化済み opacity x 255)
+//   colors    : 3 x u8 (f_dc x 0.15 x 255 + 127.5)
+//   scales    : 3 x u8 ((log scale + 10) x 16)
+//   rotations : v2 は xyz の 3 x u8 (w >= 0 から復元), v3 は smallest-three の 4 バイト
+//   sh        : 係数 → RGB の u8 ((v x 128) + 128)。次数 1 は 5 bit、2 以上は 4 bit に丸める
+
+const MAGIC: u32 = 0x5053_474e;
+const HEADER_SIZE: usize = 16;
+const WRITE_VERSION: u32 = 3;
+const COLOR_SCALE: f32 = 0.15;
+const SH1_BITS: u32 = 5;
+const SH_REST_BITS: u32 = 4;
+const QUAT_MAG_MASK: u32 = (1 << 9) - 1;
+
+pub const DEFAULT_FRACTIONAL_BITS: u8 = 12;
+// int24 に 1 ビット以上の整数部を残す
+pub const MAX_FRACTIONAL_BITS: u8 = 22;
+
+// gzip magic, used for format sniffing
+pub fn is_gzip(data: &[u8]) -> bool {
+    data.starts_with(&[0x1f, 0x8b])
+}
+
+fn to_u8(v: f32) -> u8 {
+    v.round().clamp(0.0, 255.0) as u8
+}
+
+// Round to the nearest multiple of 2^(8 - bits) around 128, as the reference encoder does
+fn quantize_sh(v: f32, bits: u32) -> u8 {
+    let bucket = (1u32 << (8 - bits)) as f32;
+    let q = ((v * 128.0 + 128.0) / bucket).round() * bucket;
+    to_u8(q)
+}
+
+fn unpack_smallest_three(b: &[u8]) -> [f32; 4] {
+    let mut comp = u32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+    let largest = (comp >> 30) as usize;
+    let mut q = [0.0f32; 4];
+    let mut sum_squares = 0.0;
+    for i in (0..4).rev().filter(|&i| i != largest) {
+        let mag = (comp & QUAT_MAG_MASK) as f32 / QUAT_MAG_MASK as f32 * std::f32::consts::FRAC_1_SQRT_2;
+        q[i] = if (comp >> 9) & 1 == 1 { -mag } else { mag };
+        sum_squares += q[i] * q[i];
+        comp >>= 10;
+    }
+    q[largest] = (1.0 - sum_squares).max(0.0).sqrt();
+    q
+}
+
+fn pack_smallest_three(q: [f32; 4]) -> [u8; 4] {
+    let largest = (1..4).fold(0, |best, i| if q[i].abs() > q[best].abs() { i } else { best });
+    // 最大成分が正になるよう符号を揃える (q と -q は同じ回転)
+    let negate = q[largest] < 0.0;
+    let mut comp = largest as u32;
+    for (_, v) in q.iter().enumerate().filter(|&(i, _)| i != largest) {
+        let negbit = ((*v < 0.0) ^ negate) as u32;
+        let mag = (QUAT_MAG_MASK as f32 * (v.abs() / std::f32::consts::FRAC_1_SQRT_2)).round().min(QUAT_MAG_MASK as f32) as u32;
+        comp = comp << 10 | negbit << 9 | mag;
+    }
+    comp.to_le_bytes()
+}
+
+pub fn read_spz(data: &[u8]) -> GsResult<SplatData> {
+    let bad = |msg: String| GsError::format("SPZ", msg);
+    let mut raw = Vec::new();
+    GzDecoder::new(data).read_to_end(&mut raw).map_err(|e| bad(format!("gzip stream is corrupt ({})", e)))?;
+    if raw.len() < HEADER_SIZE {
+        return Err(bad(format!("header is truncated ({} bytes)", raw.len())));
+    }
+
+    let u32_at = |at: usize| u32::from_le_bytes([raw[at], raw[at + 1], raw[at + 2], raw[at + 3]]);
+    if u32_at(0) != MAGIC {
+        return Err(bad(format!("bad magic {:#010x}", u32_at(0))));
+    }
+    let version = u32_at(4);
+    if !(2..=3).contains(&version) {
+        return Err(bad(format!("unsupported version {} (expected 2 or 3)", version)));
+    }
+    let count = u32_at(8) as usize;
+    let degree = raw[12] as u32;
+    let fractional_bits = raw[13] as u32;
+    if degree > sh::MAX_SH_DEGREE {
+        return Err(bad(format!("unsupported SH degree {}", degree)));
+    }
+    if fractional_bits > 23 {
+        return Err(bad(format!("fractional bits {} out of range", fractional_bits)));
+    }
+
+    let rot_size = if version >= 3 { 4 } else { 3 };
+    let sh_values = sh::rest_count(degree) * 3;
+    let expected = HEADER_SIZE + count * (9 + 1 + 3 + 3 + rot_size + sh_values);
+    if raw.len() != expected {
+        return Err(bad(format!("payload is {} bytes, expected {} for {} points", raw.len(), expected, count)));
+    }
+
+    let positions = &raw[HEADER_SIZE..];
+    let alphas = &positions[count * 9..];
+    let colors = &alphas[count..];
+    let scales = &colors[count * 3..];
+    let rotations = &scales[count * 3..];
+    let sh_data = &rotations[count * rot_size..];
+
+    let position_scale = 1.0 / (1u32 << fractional_bits) as f32;
+    let splats = (0..count).map(|i| {
+        let pos = [0, 1, 2].map(|a| {
+            let b = &positions[i * 9 + a * 3..];
+            // 24 bit の符号拡張
+            let fixed = i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 8;
+            fixed as f32 * position_scale
+        });
+        let rot = if version >= 3 {
+            unpack_smallest_three(&rotations[i * 4..])
+        } else {
+            let [x, y, z] = [0, 1, 2].map(|a| rotations[i * 3 + a] as f32 / 127.5 - 1.0);
+            [x, y, z, (1.0 - (x * x + y * y + z * z)).max(0.0).sqrt()]
+        };
+        GaussianSplat {
+            pos,
+            opacity: alphas[i] as f32 / 255.0,
+            scale: [0, 1, 2].map(|a| (scales[i * 3 + a] as f32 / 16.0 - 10.0).exp()),
+            rot: normalize_quat(rot),
+            sh_dc: [0, 1, 2].map(|a| (colors[i * 3 + a] as f32 / 255.0 - 0.5) / COLOR_SCALE),
+            ..Default::default()
+        }
+    }).collect();
+    let coeffs = sh_data.iter().map(|&v| (v as f32 - 128.0) / 128.0).collect();
+
+    Ok(SplatData { splats, sh: ShCoeffs { degree, coeffs } })
+}
+
+// SPZ v3. Positions must fit in int24 at the given precision (|x| < 2^(23 - fractional_bits)).
+pub fn write_spz<W: Write>(w: W, splats: &[GaussianSplat], sh: &ShCoeffs, fractional_bits: u8) -> GsResult<()> {
+    if fractional_bits > MAX_FRACTIONAL_BITS {
+        return Err(GsError::invalid(format!("fractional_bits must be 0 - {}, got {}", MAX_FRACTIONAL_BITS, fractional_bits)));
+    }
+    let count = splats.len();
+    let sh_values = sh.stride();
+
+    let mut raw = Vec::with_capacity(HEADER_SIZE + count * (20 + sh_values));
+    raw.extend_from_slice(&MAGIC.to_le_bytes());
+    raw.extend_from_slice(&WRITE_VERSION.to_le_bytes());
+    raw.extend_from_slice(&(count as u32).to_le_bytes());
+    raw.extend_from_slice(&[sh.degree as u8, fractional_bits, 0, 0]);
+
+    let scale = (1u32 << fractional_bits) as f32;
+    let limit = (1 << 23) - 1;
+    for (i, s) in splats.iter().enumerate() {
+        for &v in &s.pos {
+            let fixed = (v * scale).round();
+            if !fixed.is_finite() || fixed.abs() > limit as f32 {
+                return Err(GsError::invalid(format!(
+                    "Splat {} position {} does not fit in 24-bit fixed point with {} fractional bits; use fewer fractional bits",
+                    i, v, fractional_bits,
+                )));
+            }
+            raw.extend_from_slice(&(fixed as i32).to_le_bytes()[..3]);
+        }
+    }
+    raw.extend(splats.iter().map(|s| to_u8(s.opacity * 255.0)));
+    for s in splats {
+        raw.extend(s.sh_dc.map(|dc| to_u8(dc * COLOR_SCALE * 255.0 + 127.5)));
+    }
+    for s in splats {
+        raw.extend(s.scale.map(|v| to_u8((v.ln() + 10.0) * 16.0)));
+    }
+    for s in splats {
+        raw.extend_from_slice(&pack_smallest_three(normalize_quat(s.rot)));
+    }
+    if sh_values > 0 {
+        for i in 0..count {
+            // 最初の 3 係数 (次数 1) は 5 bit、残りは 4 bit
+            raw.extend(sh.splat(i).iter().enumerate().map(|(k, &v)| {
+                quantize_sh(v, if k < 9 { SH1_BITS } else { SH_REST_BITS })
+            }));
+        }
+    }
+
+    let mut encoder = GzEncoder::new(w, flate2::Compression::default());
+    encoder.write_all(&raw)?;
+    encoder.finish()?;
+    Ok(())
+}
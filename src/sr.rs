@@ -1,18 +1,129 @@
 use std::borrow::Cow;
+
+use crate::scan::ScanPipeline;
+#[cfg(feature = "wasm")]
 use wgpu::util::DeviceExt;
+#[cfg(any(feature = "python", feature = "wasm"))]
 use crate::{GaussianSplat, Surfel};
+use crate::error::{GsError, GsResult};
+
+// フィルタとサンプリングの既定値 (屋内の SLAM マップ向け)
+pub const DEFAULT_MIN_OPACITY: f32 = 0.3;
+pub const DEFAULT_MAX_ASPECT_RATIO: f32 = 0.6;
+// 子 Surfel を置く円盤の半径 (接平面方向の各軸のスケール = 1 sigma 単位)。裾野の薄い部分を避けて中心寄りに置く
+pub const DEFAULT_SAMPLE_RADIUS: f32 = 0.8;
+
+// 子 Surfel の接平面上の配置
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum SrSampler {
+    // 黄金角スパイラル (決定的、sample_radius の円盤内)
+    #[default]
+    Spiral,
+    // スプラット自身の 2D ガウシアンから乱数で引く (seed で再現可能)
+    Gaussian,
+}
+
+impl std::str::FromStr for SrSampler {
+    type Err = GsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "spiral" => Ok(Self::Spiral),
+            "gaussian" => Ok(Self::Gaussian),
+            _ => Err(GsError::invalid(format!("Unknown sampler '{}' (expected 'spiral' or 'gaussian')", s))),
+        }
+    }
+}
 
+// Uniform of the SR kernels (Params in sr.wgsl)
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-struct SrParams {
+pub struct SrParams {
+    // factor, min_opacity, max_aspect_ratio, max_scale -> 16 bytes
     factor: u32,
-    _pad0: u32,
-    _pad1: u32,
-    _pad2: u32,
+    // opacity < min_opacity のスプラットを除く (薄い霧)
+    min_opacity: f32,
+    // min_scale / max_scale > max_aspect_ratio のスプラットを除く (球体に近い = 面ではない)
+    max_aspect_ratio: f32,
+    // max_scale を超えるスプラットを除く (背景の巨大な板など)。0 は無制限
+    max_scale: f32,
+
+    // sample_radius, density, sample_mode, seed -> 16 bytes
+    sample_radius: f32,
+    // 目標の面密度 (点 / m^2)。0 は全スプラット factor 個、正なら接平面の面積に比例 (factor が上限)
+    density: f32,
+    // 0: spiral / 1: gaussian
+    sample_mode: u32,
+    seed: u32,
+
+    // truncate -> 16 bytes
+    // ガウシアンサンプラの打ち切り半径 (sigma 単位)。0 は打ち切りなし
+    truncate: f32,
+    _pad: [u32; 3],
 }
 
+impl SrParams {
+    pub fn new(factor: u32, min_opacity: f32, max_aspect_ratio: f32, max_scale: Option<f32>, sample_radius: f32) -> GsResult<Self> {
+        if factor < 1 {
+            return Err(GsError::invalid("Factor must be >= 1"));
+        }
+        if !(0.0..=1.0).contains(&min_opacity) {
+            return Err(GsError::invalid(format!("min_opacity must be in [0, 1], got {}", min_opacity)));
+        }
+        if !(max_aspect_ratio > 0.0 && max_aspect_ratio <= 1.0) {
+            return Err(GsError::invalid(format!("max_aspect_ratio must be in (0, 1], got {}", max_aspect_ratio)));
+        }
+        if let Some(max_scale) = max_scale.filter(|&v| !(v > 0.0 && v.is_finite())) {
+            return Err(GsError::invalid(format!("max_scale must be a positive number, got {}", max_scale)));
+        }
+        if !(sample_radius >= 0.0 && sample_radius.is_finite()) {
+            return Err(GsError::invalid(format!("sample_radius must be >= 0, got {}", sample_radius)));
+        }
+        Ok(Self {
+            factor,
+            min_opacity,
+            max_aspect_ratio,
+            max_scale: max_scale.unwrap_or(0.0),
+            sample_radius,
+            density: 0.0,
+            sample_mode: 0,
+            seed: 0,
+            truncate: 0.0,
+            _pad: [0; 3],
+        })
+    }
+
+    // 面積適応モード: 子の数を density (点 / m^2) x 面積にする。factor はスプラットあたりの上限になる
+    pub fn with_density(self, density: Option<f32>) -> GsResult<Self> {
+        let Some(density) = density else { return Ok(self) };
+        if !(density > 0.0 && density.is_finite()) {
+            return Err(GsError::invalid(format!("density must be a positive number of points per square meter, got {}", density)));
+        }
+        Ok(Self { density, ..self })
+    }
+
+    // 配置の方法。gaussian の乱数列はスプラットの位置と seed だけで決まる (CPU / GPU・チャンク分割で同じ)
+    pub fn with_sampler(self, sampler: SrSampler, seed: u32, truncate: Option<f32>) -> GsResult<Self> {
+        if let Some(truncate) = truncate.filter(|&k| !(k > 0.0 && k.is_finite())) {
+            return Err(GsError::invalid(format!("truncate must be a positive number of sigmas, got {}", truncate)));
+        }
+        let sample_mode = match sampler {
+            SrSampler::Spiral => 0,
+            SrSampler::Gaussian => 1,
+        };
+        Ok(Self { sample_mode, seed, truncate: truncate.unwrap_or(0.0), ..self })
+    }
+
+    pub fn factor(&self) -> u32 {
+        self.factor
+    }
+}
+
+// count_surfels → prefix sum (scan.rs) → compute_sr: 有効なスプラットの Surfel だけを入力順に詰めて出力する
 pub struct SuperResolutionPipeline {
+    count: wgpu::ComputePipeline,
     pipeline: wgpu::ComputePipeline,
+    scan: ScanPipeline,
     bind_group_layout: wgpu::BindGroupLayout,
 }
 
@@ -47,6 +158,13 @@ impl SuperResolutionPipeline {
                     ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None },
                     count: None,
                 },
+                // Output offsets (per splat + total)
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Storage { read_only: false }, has_dynamic_offset: false, min_binding_size: None },
+                    count: None,
+                },
             ],
         });
 
@@ -56,28 +174,90 @@ impl SuperResolutionPipeline {
             push_constant_ranges: &[],
         });
 
-        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-            label: Some("SR Compute Pipeline"),
+        let pipeline = |label, entry_point| device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(label),
             layout: Some(&pipeline_layout),
             module: &shader,
-            entry_point: Some("compute_sr"),
+            entry_point: Some(entry_point),
             compilation_options: Default::default(),
             cache: None,
         });
 
-        Self { pipeline, bind_group_layout }
+        Self {
+            count: pipeline("SR Count Pipeline", "count_surfels"),
+            pipeline: pipeline("SR Compute Pipeline", "compute_sr"),
+            scan: ScanPipeline::new(device),
+            bind_group_layout,
+        }
+    }
+
+    pub fn bind_group(
+        &self,
+        device: &wgpu::Device,
+        input: wgpu::BindingResource,
+        output: wgpu::BindingResource,
+        params: wgpu::BindingResource,
+        offsets: wgpu::BindingResource,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("SR Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: input },
+                wgpu::BindGroupEntry { binding: 1, resource: output },
+                wgpu::BindGroupEntry { binding: 2, resource: params },
+                wgpu::BindGroupEntry { binding: 3, resource: offsets },
+            ],
+        })
+    }
+
+    // `offsets` is the buffer bound at binding 3, starting at offset 0 with splat_count + 1 elements bound and
+    // scan::buffer_len(splat_count + 1) u32 allocated. Afterwards offsets[splat_count] holds the number of surfels written.
+    pub fn dispatch(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        bind_group: &wgpu::BindGroup,
+        offsets: &wgpu::Buffer,
+        splat_count: u32,
+        factor: u32,
+    ) {
+        let pass = |encoder: &mut wgpu::CommandEncoder, pipeline, threads: u32| {
+            let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+            cpass.set_pipeline(pipeline);
+            cpass.set_bind_group(0, bind_group, &[]);
+            cpass.dispatch_workgroups(threads.div_ceil(64), 1, 1);
+        };
+        pass(encoder, &self.count, splat_count + 1);
+        self.scan.encode(device, encoder, offsets, splat_count + 1);
+        pass(encoder, &self.pipeline, splat_count * factor);
     }
 
+    // Viewer用: 出力バッファをそのまま Vertex Buffer として使う
+    // 入出力は単一バッファに置き、バインド範囲をずらしてチャンクごとにディスパッチする
+    // 有効数は読み戻さず、各チャンクの offsets の末尾を DrawIndirect の vertex_count にコピーする
+    // (チャンク内で詰めた後の空きスロットは描画しない)
+    #[cfg(feature = "wasm")]
     pub fn run(
         &self,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         input_splats: &[GaussianSplat],
-        factor: u32,
-    ) -> (wgpu::Buffer, u32) {
+        params: SrParams,
+    ) -> GsResult<SrOutput> {
+        let factor = params.factor();
         let input_count = input_splats.len() as u32;
-        let output_count = input_count * factor;
-        let output_size = (output_count as usize * std::mem::size_of::<Surfel>()) as u64;
+        let output_count = input_count.checked_mul(factor)
+            .ok_or_else(|| GsError::BufferTooLarge(format!("Super resolution output overflows: {} splats x factor {}", input_count, factor)))?;
+        let surfel_size = std::mem::size_of::<Surfel>() as u64;
+        let output_size = output_count as u64 * surfel_size;
+        let limits = device.limits();
+        if output_size > limits.max_buffer_size {
+            return Err(GsError::BufferTooLarge(format!(
+                "Super resolution output ({} bytes) exceeds max_buffer_size ({} bytes)", output_size, limits.max_buffer_size,
+            )));
+        }
+        let chunk_len = crate::max_chunk_len(&limits, &[std::mem::size_of::<GaussianSplat>() as u64, surfel_size * factor as u64], factor as u64)?;
 
         // 1. Create Buffers
         let input_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
@@ -94,73 +274,234 @@ impl SuperResolutionPipeline {
             mapped_at_creation: false,
         });
 
-        let params = SrParams { factor, _pad0: 0, _pad1: 0, _pad2: 0 };
         let param_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("SR Param Buffer"),
             contents: bytemuck::bytes_of(&params),
             usage: wgpu::BufferUsages::UNIFORM,
         });
 
-        // 2. Bind Group
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("SR Bind Group"),
-            layout: &self.bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry { binding: 0, resource: input_buffer.as_entire_binding() },
-                wgpu::BindGroupEntry { binding: 1, resource: output_buffer.as_entire_binding() },
-                wgpu::BindGroupEntry { binding: 2, resource: param_buffer.as_entire_binding() },
-            ],
+        let offset_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("SR Offset Buffer"),
+            size: crate::scan::buffer_len(chunk_len.min(input_splats.len()) as u32 + 1) * 4,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        // チャンクごとの描画範囲。vertex_count は GPU 上で書き込まれた数に置き換える
+        let chunk_starts: Vec<usize> = (0..input_splats.len()).step_by(chunk_len).collect();
+        let draw_args: Vec<u8> = chunk_starts.iter().flat_map(|&start| {
+            let args = wgpu::util::DrawIndirectArgs { vertex_count: 0, instance_count: 1, first_vertex: start as u32 * factor, first_instance: 0 };
+            args.as_bytes().to_vec()
+        }).collect();
+        let draw_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("SR Draw Args Buffer"),
+            contents: &draw_args,
+            usage: wgpu::BufferUsages::INDIRECT | wgpu::BufferUsages::COPY_DST,
         });
 
-        // 3. Dispatch
+        // 2. Bind Groups & Dispatch (per chunk)
         let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("SR Encoder") });
-        {
-            let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
-            cpass.set_pipeline(&self.pipeline);
-            cpass.set_bind_group(0, &bind_group, &[]);
-            let workgroups = (output_count + 63) / 64;
-            cpass.dispatch_workgroups(workgroups, 1, 1);
+        for (chunk, &start) in chunk_starts.iter().enumerate() {
+            let len = chunk_len.min(input_splats.len() - start) as u64;
+            let start = start as u64;
+            let splat_size = std::mem::size_of::<GaussianSplat>() as u64;
+            let out_per_splat = surfel_size * factor as u64;
+            let bind_group = self.bind_group(
+                device,
+                crate::buffer_range(&input_buffer, start * splat_size, len * splat_size),
+                crate::buffer_range(&output_buffer, start * out_per_splat, len * out_per_splat),
+                param_buffer.as_entire_binding(),
+                crate::buffer_range(&offset_buffer, 0, (len + 1) * 4),
+            );
+            self.dispatch(device, &mut encoder, &bind_group, &offset_buffer, len as u32, factor);
+            // offsets は次のチャンクで上書きされるので、ここで有効数を描画引数へ移す
+            let args_offset = (chunk * std::mem::size_of::<wgpu::util::DrawIndirectArgs>()) as u64;
+            encoder.copy_buffer_to_buffer(&offset_buffer, len * 4, &draw_buffer, args_offset, 4);
         }
         queue.submit(Some(encoder.finish()));
 
-        (output_buffer, output_count)
+        Ok(SrOutput { surfels: output_buffer, draw_args: draw_buffer, draw_count: chunk_starts.len() as u32, max_count: output_count })
     }
+}
 
-    // Python用: 結果をCPUに読み戻すヘルパー
-    pub async fn run_and_readback(
-        &self,
-        device: &wgpu::Device,
-        queue: &wgpu::Queue,
-        input_splats: &[GaussianSplat],
-        factor: u32,
-    ) -> Result<Vec<Surfel>, String> {
-        let (output_buffer, output_count) = self.run(device, queue, input_splats, factor);
-        let output_size = (output_count as usize * std::mem::size_of::<Surfel>()) as u64;
+// Viewer 用の SR 結果: チャンクごとに詰めた Surfel と、その範囲を描く DrawIndirectArgs の配列
+#[cfg(feature = "wasm")]
+pub struct SrOutput {
+    pub surfels: wgpu::Buffer,
+    pub draw_args: wgpu::Buffer,
+    // draw_args に並ぶ DrawIndirectArgs の数 (= チャンク数)
+    pub draw_count: u32,
+    // 有効数の上限 (splats x factor)
+    pub max_count: u32,
+}
 
-        let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Staging Buffer"),
-            size: output_size,
-            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
+// ============================================================================
+//  CPU Implementation (same filtering / sampling / color decode as sr.wgsl)
+// ============================================================================
 
-        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
-        encoder.copy_buffer_to_buffer(&output_buffer, 0, &staging_buffer, 0, output_size);
-        queue.submit(Some(encoder.finish()));
+#[cfg(feature = "python")]
+const GOLDEN_ANGLE: f32 = 2.3999632;
+
+// Rotation matrix columns of a normalized (x, y, z, w) quaternion (quat_to_mat3 in sr.wgsl)
+#[cfg(feature = "python")]
+fn quat_to_mat3(q: [f32; 4]) -> [[f32; 3]; 3] {
+    let [x, y, z, w] = q;
+    let (x2, y2, z2) = (x * x, y * y, z * z);
+    let (xy, xz, yz) = (x * y, x * z, y * z);
+    let (wx, wy, wz) = (w * x, w * y, w * z);
+    [
+        [1.0 - 2.0 * (y2 + z2), 2.0 * (xy + wz), 2.0 * (xz - wy)],
+        [2.0 * (xy - wz), 1.0 - 2.0 * (x2 + z2), 2.0 * (yz + wx)],
+        [2.0 * (xz + wy), 2.0 * (yz - wx), 1.0 - 2.0 * (x2 + y2)],
+    ]
+}
 
-        let buffer_slice = staging_buffer.slice(..);
-        let (sender, receiver) = futures_intrusive::channel::shared::oneshot_channel();
-        buffer_slice.map_async(wgpu::MapMode::Read, move |v| sender.send(v).unwrap());
-        device.poll(wgpu::Maintain::Wait);
-
-        if let Some(Ok(())) = receiver.receive().await {
-            let data = buffer_slice.get_mapped_range();
-            let result: Vec<Surfel> = bytemuck::cast_slice(&data).to_vec();
-            drop(data);
-            staging_buffer.unmap();
-            Ok(result)
-        } else {
-            Err("Failed to map buffer".to_string())
+// Golden-angle spiral on a disc of `radius`, index 0 at the center (concentric_sample in sr.wgsl)
+#[cfg(feature = "python")]
+fn concentric_sample(index: u32, total: u32, radius: f32) -> [f32; 2] {
+    if index == 0 { return [0.0, 0.0]; }
+    let theta = index as f32 * GOLDEN_ANGLE;
+    let r = (index as f32 / total as f32).sqrt() * radius;
+    [r * theta.cos(), r * theta.sin()]
+}
+
+// PCG hash (pcg_hash in sr.wgsl)
+#[cfg(feature = "python")]
+fn pcg_hash(input: u32) -> u32 {
+    let state = input.wrapping_mul(747796405).wrapping_add(2891336453);
+    let word = ((state >> ((state >> 28) + 4)) ^ state).wrapping_mul(277803737);
+    (word >> 22) ^ word
+}
+
+// [0, 1) の一様乱数 (next_uniform in sr.wgsl)
+#[cfg(feature = "python")]
+fn next_uniform(state: &mut u32) -> f32 {
+    *state = pcg_hash(*state);
+    (*state >> 8) as f32 * (1.0 / 16777216.0)
+}
+
+// Per-splat RNG seed from the position bits (splat_seed in sr.wgsl)
+#[cfg(feature = "python")]
+fn splat_seed(pos: [f32; 3], seed: u32) -> u32 {
+    let [x, y, z] = pos.map(f32::to_bits);
+    pcg_hash(seed ^ pcg_hash(x ^ pcg_hash(y ^ pcg_hash(z))))
+}
+
+// Standard 2D Gaussian, truncated at `truncate` sigmas when > 0 (gaussian_sample in sr.wgsl)
+#[cfg(feature = "python")]
+fn gaussian_sample(seed: u32, index: u32, truncate: f32) -> [f32; 2] {
+    let mut state = seed.wrapping_add(index);
+    let u = next_uniform(&mut state);
+    let theta = next_uniform(&mut state) * std::f32::consts::TAU - std::f32::consts::PI;
+    let tail = if truncate > 0.0 { 1.0 - (-0.5 * truncate * truncate).exp() } else { 1.0 };
+    let r = (-2.0 * (1.0 - u * tail).ln()).sqrt();
+    [r * theta.cos(), r * theta.sin()]
+}
+
+// 低オパシティ・球体に近い・大きすぎるスプラットを除く (is_valid in sr.wgsl)
+#[cfg(feature = "python")]
+fn is_valid(splat: &GaussianSplat, params: &SrParams) -> bool {
+    let s = splat.scale;
+    let max_s = s[0].max(s[1]).max(s[2]);
+    let min_s = s[0].min(s[1]).min(s[2]);
+    splat.opacity >= params.min_opacity
+        && min_s / max_s <= params.max_aspect_ratio
+        && (params.max_scale <= 0.0 || max_s <= params.max_scale)
+}
+
+// Number of children of a valid splat (child_count in sr.wgsl)
+#[cfg(feature = "python")]
+fn child_count(splat: &GaussianSplat, params: &SrParams) -> usize {
+    if params.density <= 0.0 { return params.factor as usize; }
+    let s = splat.scale;
+    let (su, sv) = if s[0] < s[1] && s[0] < s[2] {
+        (s[1], s[2])
+    } else if s[1] < s[2] {
+        (s[0], s[2])
+    } else {
+        (s[0], s[1])
+    };
+    let n = (params.density * std::f32::consts::PI * su * sv + 0.5).floor();
+    n.clamp(1.0, params.factor as f32) as usize
+}
+
+// Writes the child surfels of one valid splat (one per element of `out`)
+#[cfg(feature = "python")]
+fn upsample_splat(splat: &GaussianSplat, params: &SrParams, out: &mut [Surfel]) {
+    let count = out.len() as u32;
+    let s = splat.scale;
+    let r = quat_to_mat3(crate::splat::normalize_quat(splat.rot));
+    let rotate = |v: [f32; 3]| [0, 1, 2].map(|a| r[0][a] * v[0] + r[1][a] * v[1] + r[2][a] * v[2]);
+
+    // 最も薄い軸を法線、残り 2 軸を接平面とする
+    let (axis_n, (axis_u, axis_v)) = if s[0] < s[1] && s[0] < s[2] {
+        (0, (1, 2))
+    } else if s[1] < s[2] {
+        (1, (0, 2))
+    } else {
+        (2, (0, 1))
+    };
+    let unit = |a: usize| { let mut v = [0.0; 3]; v[a] = 1.0; v };
+    let n = rotate(unit(axis_n));
+    let len = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+    let normal = n.map(|v| v / len);
+
+    let color = crate::sh_to_rgb_cpu(splat.sh_dc);
+    let (max_scale, curvature) = crate::surfel_shape_cpu(s);
+    let radius = max_scale / (count as f32).sqrt();
+    let gaussian = params.sample_mode == 1;
+    let seed = splat_seed(splat.pos, params.seed);
+
+    for (child, surfel) in out.iter_mut().enumerate() {
+        let mut offset = [0.0; 3];
+        if gaussian || count > 1 {
+            // 接平面上のみに展開 (法線方向への移動はゼロ)
+            let [su, sv] = if gaussian {
+                gaussian_sample(seed, child as u32, params.truncate)
+            } else {
+                concentric_sample(child as u32, count, params.sample_radius)
+            };
+            offset[axis_u] = su * s[axis_u];
+            offset[axis_v] = sv * s[axis_v];
         }
+        let offset = rotate(offset);
+        *surfel = Surfel {
+            pos: [0, 1, 2].map(|a| splat.pos[a] + offset[a]),
+            radius,
+            color,
+            opacity: splat.opacity,
+            normal,
+            curvature,
+        };
     }
 }
+
+// CPU version of compute_sr: the children of every valid splat, in the same order as the GPU output
+#[cfg(feature = "python")]
+pub fn run_cpu(pool: &crate::cpu::CpuPool, splats: &[GaussianSplat], params: &SrParams) -> GsResult<Vec<Surfel>> {
+    use rayon::prelude::*;
+
+    pool.install(|| {
+        // collect は入力順を保つ
+        let kept: Vec<(&GaussianSplat, usize)> = splats.par_iter()
+            .filter(|s| is_valid(s, params))
+            .map(|s| (s, child_count(s, params)))
+            .collect();
+        let total = kept.iter().try_fold(0usize, |total, &(_, count)| total.checked_add(count))
+            .ok_or_else(|| GsError::BufferTooLarge(format!("Super resolution output overflows: {} splats x factor {}", kept.len(), params.factor)))?;
+
+        // 可変長の出力をスプラットごとの区間に切り分ける (GPU の prefix sum に相当)
+        let mut surfels = vec![Surfel::default(); total];
+        let mut rest = surfels.as_mut_slice();
+        let mut outputs = Vec::with_capacity(kept.len());
+        for &(_, count) in &kept {
+            let (head, tail) = rest.split_at_mut(count);
+            outputs.push(head);
+            rest = tail;
+        }
+        outputs.into_par_iter().zip(kept.par_iter()).for_each(|(out, &(splat, _))| {
+            upsample_splat(splat, params, out);
+        });
+        Ok(surfels)
+    })
+}
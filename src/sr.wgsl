@@ -12,15 +12,29 @@ struct GaussianSplat {
 
 struct Surfel {
     pos: vec3<f32>,
-    _pad0: f32,
+    radius: f32,
     color: vec3<f32>,
-    _pad1: f32,
+    opacity: f32,
     normal: vec3<f32>,
-    _pad2: f32,
+    curvature: f32,
 };
 
+// SrParams (src/sr.rs)
 struct Params {
     factor: u32,
+    min_opacity: f32,
+    max_aspect_ratio: f32,
+    // 0: 無制限
+    max_scale: f32,
+    // 子 Surfel を置く円盤の半径 (sigma 単位)
+    sample_radius: f32,
+    // 目標の面密度 (点 / m^2)。0: 全スプラット factor 個 / 正: 面積に比例 (factor が上限)
+    density: f32,
+    // 0: 黄金角スパイラル / 1: スプラット自身の 2D ガウシアンからの乱数サンプル
+    sample_mode: u32,
+    seed: u32,
+    // ガウシアンサンプラの打ち切り半径 (sigma 単位)。0: 打ち切りなし
+    truncate: f32,
     _pad0: u32,
     _pad1: u32,
     _pad2: u32,
@@ -29,6 +43,9 @@ struct Params {
 @group(0) @binding(0) var<storage, read> input_splats : array<GaussianSplat>;
 @group(0) @binding(1) var<storage, read_write> output_surfels : array<Surfel>;
 @group(0) @binding(2) var<uniform> params : Params;
+// スプラットごとの出力数 (count_surfels) → exclusive prefix sum (scan.wgsl) 後は出力先の先頭位置。
+// 要素数はスプラット数 + 1 で、末尾には有効な Surfel の総数が入る
+@group(0) @binding(3) var<storage, read_write> offsets : array<u32>;
 
 // --- Helpers ---
 
@@ -44,19 +61,98 @@ fn quat_to_mat3(q: vec4<f32>) -> mat3x3<f32> {
     );
 }
 
-// Sigmoid function for opacity (if stored as logit)
-fn sigmoid(x: f32) -> f32 {
-    return 1.0 / (1.0 + exp(-x));
+// PCG hash (Jarzynski & Olano, "Hash Functions for GPU Rendering")
+fn pcg_hash(input: u32) -> u32 {
+    let state = input * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+// [0, 1) の一様乱数 (24 bit)
+fn next_uniform(state: ptr<function, u32>) -> f32 {
+    *state = pcg_hash(*state);
+    return f32(*state >> 8u) * (1.0 / 16777216.0);
+}
+
+// 乱数列はスプラットの位置と seed から決まる (入力順やチャンク分割に依存しない)
+fn splat_seed(pos: vec3<f32>) -> u32 {
+    let p = bitcast<vec3<u32>>(pos);
+    return pcg_hash(params.seed ^ pcg_hash(p.x ^ pcg_hash(p.y ^ pcg_hash(p.z))));
+}
+
+// 標準 2D ガウシアン (truncate > 0 なら半径 truncate で打ち切り)。
+// 打ち切り付き Rayleigh 分布の逆関数で半径を引くので、棄却なしで子ごとに乱数 2 つ
+fn gaussian_sample(seed: u32, index: u32) -> vec2<f32> {
+    var state = seed + index;
+    let u = next_uniform(&state);
+    let theta = next_uniform(&state) * 6.2831853 - 3.1415927;
+    var tail = 1.0;
+    if (params.truncate > 0.0) { tail = 1.0 - exp(-0.5 * params.truncate * params.truncate); }
+    let r = sqrt(-2.0 * log(1.0 - u * tail));
+    return vec2<f32>(r * cos(theta), r * sin(theta));
 }
 
 fn concentric_sample(index: u32, total: u32) -> vec2<f32> {
     if (index == 0u) { return vec2<f32>(0.0, 0.0); }
     let theta = f32(index) * 2.3999632; // Golden Angle
-    let r = sqrt(f32(index) / f32(total));
-    // 0.8倍することで、ガウシアンの裾野(薄い部分)を避けて中心寄りに配置する
-    return vec2<f32>(r * 0.8 * cos(theta), r * 0.8 * sin(theta));
+    // sample_radius < 1 でガウシアンの裾野(薄い部分)を避けて中心寄りに配置する (既定 0.8)
+    let r = sqrt(f32(index) / f32(total)) * params.sample_radius;
+    return vec2<f32>(r * cos(theta), r * sin(theta));
+}
+
+// SLAM用にノイズ除去: 薄い霧 (低オパシティ) と球体に近い形状を除く
+// スケール・オパシティは読み込み時に活性化済み (exp / sigmoid, ValueDomain)
+fn is_valid(splat: GaussianSplat) -> bool {
+    let s = splat.scale;
+    let max_s = max(s.x, max(s.y, s.z));
+    let min_s = min(s.x, min(s.y, s.z));
+
+    // オパシティ閾値 (薄い霧を除去)
+    if (splat.opacity < params.min_opacity) { return false; }
+
+    // アスペクト比閾値 (球体に近い形状を除去し、平らな面だけ残す)
+    // 比率が大きい＝丸っこい＝壁ではない可能性
+    if (min_s / max_s > params.max_aspect_ratio) { return false; }
+
+    // スケールが大きすぎるものを除去（背景の巨大なビルボード等）
+    // シーンによりますが、極端に大きい板は精度を下げる要因
+    if (params.max_scale > 0.0 && max_s > params.max_scale) { return false; }
+
+    return true;
+}
+
+// 接平面方向の 2 軸のスケール (最も薄い軸 = 法線を除いたもの)
+fn tangent_scales(s: vec3<f32>) -> vec2<f32> {
+    if (s.x < s.y && s.x < s.z) { return vec2<f32>(s.y, s.z); }
+    if (s.y < s.z) { return vec2<f32>(s.x, s.z); }
+    return vec2<f32>(s.x, s.y);
+}
+
+// 有効なスプラットの子 Surfel 数。density 指定時は 1 sigma の楕円の面積 (pi * scale_u * scale_v) に比例し、
+// 小さな破片でも中心の 1 点は残す (1 - factor)
+fn child_count(splat: GaussianSplat) -> u32 {
+    if (params.density <= 0.0) { return params.factor; }
+    let t = tangent_scales(splat.scale);
+    let n = floor(params.density * 3.14159265 * t.x * t.y + 0.5);
+    return u32(clamp(n, 1.0, f32(params.factor)));
 }
 
+// Pass 1: 各スプラットが出力する Surfel 数 (無効なら 0)
+@compute @workgroup_size(64)
+fn count_surfels(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let idx = global_id.x;
+    if (idx >= arrayLength(&offsets)) { return; }
+
+    var count = 0u;
+    if (idx < arrayLength(&input_splats)) {
+        let splat = input_splats[idx];
+        if (is_valid(splat)) { count = child_count(splat); }
+    }
+    offsets[idx] = count;
+}
+
+// Pass 2 (prefix sum の後): 有効なスプラットの子 Surfel を入力順に詰めて書き込む
+// スレッドはスプラットごとに factor (子の数の上限) 個割り当て、余りは何もしない
 @compute @workgroup_size(64)
 fn compute_sr(@builtin(global_invocation_id) global_id: vec3<u32>) {
     let idx = global_id.x;
@@ -68,56 +164,22 @@ fn compute_sr(@builtin(global_invocation_id) global_id: vec3<u32>) {
     let child_idx = idx % params.factor;
     let splat = input_splats[parent_idx];
 
-    // =========================================================
-    // 1. Scale & Opacity Correction (重要！)
-    // =========================================================
-    
-    // 【修正】スケールは対数で保存されているため、exp() で実数に戻す
-    let s = exp(splat.scale);
-    
-    // 【修正】オパシティもSigmoidを通す（一般的なPLYの場合）
-    // もしすでに0-1の範囲ならこの行は不要だが、通しても害は少ない
-    let opacity = sigmoid(splat.opacity);
-
-    // =========================================================
-    // 2. Filtering (SLAM用にノイズ除去)
-    // =========================================================
+    // 無効なスプラットは出力しない (offsets にも数えられていない)
+    if (!is_valid(splat)) { return; }
+    let count = child_count(splat);
+    if (child_idx >= count) { return; }
 
+    let s = splat.scale;
+    let opacity = splat.opacity;
     let max_s = max(s.x, max(s.y, s.z));
-    let min_s = min(s.x, min(s.y, s.z));
-    
-    var valid = true;
-    
-    // オパシティ閾値 (薄い霧を除去)
-    if (opacity < 0.3) { valid = false; }
-    
-    // アスペクト比閾値 (球体に近い形状を除去し、平らな面だけ残す)
-    // 0.6以上の比率がある＝丸っこい＝壁ではない可能性
-    if (min_s / max_s > 0.6) { valid = false; } 
-
-    // スケールが大きすぎるものを除去（背景の巨大なビルボード等）
-    // シーンによりますが、極端に大きい板は精度を下げる要因
-    // if (max_s > 10.0) { valid = false; }
-
-    if (!valid) {
-        // 無効な点は描画しない（縮退させる）
-        var out_invalid: Surfel;
-        out_invalid.pos = vec3<f32>(0.0);
-        out_invalid.color = vec3<f32>(0.0);
-        out_invalid.normal = vec3<f32>(0.0);
-        // padding
-        out_invalid._pad0 = 0.0; out_invalid._pad1 = 0.0; out_invalid._pad2 = 0.0;
-        output_surfels[idx] = out_invalid;
-        return;
-    }
 
     // =========================================================
-    // 3. Geometry Calculation
+    // 1. Geometry Calculation
     // =========================================================
 
+    // rot は読み込み時に (x, y, z, w) へ正規化済み (QuatOrder)
     let q = normalize(splat.rot);
-    let q_fixed = vec4<f32>(q.y, q.z, q.w, q.x);
-    let R = quat_to_mat3(q_fixed);
+    let R = quat_to_mat3(q);
 
     // 法線方向(最も薄い軸)を特定
     var local_n = vec3<f32>(0.0, 0.0, 1.0);
@@ -139,13 +201,18 @@ fn compute_sr(@builtin(global_invocation_id) global_id: vec3<u32>) {
     let normal = normalize(R * local_n);
 
     // =========================================================
-    // 4. Deterministic Planar Sampling
+    // 2. Planar Sampling (spiral: 決定的 / gaussian: seed 付き乱数)
     // =========================================================
     
     var offset_local = vec3<f32>(0.0);
 
-    if (params.factor > 1u) {
-        let sample_pt = concentric_sample(child_idx, params.factor);
+    if (params.sample_mode == 1u || count > 1u) {
+        var sample_pt: vec2<f32>;
+        if (params.sample_mode == 1u) {
+            sample_pt = gaussian_sample(splat_seed(splat.pos), child_idx);
+        } else {
+            sample_pt = concentric_sample(child_idx, count);
+        }
         
         // 接平面上のみに展開。法線方向への移動はゼロ！
         // これで「表面に張り付いた」点群になる
@@ -163,8 +230,12 @@ fn compute_sr(@builtin(global_invocation_id) global_id: vec3<u32>) {
     out.pos = pos_world;
     out.color = rgb;
     out.normal = normal;
-    
-    out._pad0 = 0.0; out._pad1 = 0.0; out._pad2 = 0.0;
 
-    output_surfels[idx] = out;
+    // 子サーフェルは親の円盤を count 個で分け合うので、半径は 1/sqrt(count) 倍
+    let var_s = s * s;
+    out.radius = max_s / sqrt(f32(count));
+    out.opacity = opacity;
+    out.curvature = min(var_s.x, min(var_s.y, var_s.z)) / (var_s.x + var_s.y + var_s.z);
+
+    output_surfels[offsets[parent_idx] + child_idx] = out;
 }
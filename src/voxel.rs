@@ -0,0 +1,549 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use rayon::prelude::*;
+
+use crate::cpu::CpuPool;
+use crate::error::{GsError, GsResult};
+use crate::scan::ScanPipeline;
+use crate::sh::ShCoeffs;
+use crate::{GaussianSplat, Surfel};
+
+// ============================================================================
+//  Voxel Grid Downsampling
+// ============================================================================
+//
+// ワールド座標に揃えたボクセル (floor(p / voxel_size)) ごとに点をまとめる (PCL の VoxelGrid と同じ格子)。
+// 点の属性を 1 行 (xyz, 重み, 平均する属性...) の表 (VoxelTable) にして、ボクセルごとの重み付き平均と
+// 平均位置に最も近い点 (代表点) を求め、平均で置き換えられない属性 (回転・スケール) は代表点から取る。
+// GPU: セルのハッシュによる counting sort + ハッシュごとの最小インデックス (atomicMax) + prefix sum (voxel.wgsl)。CPU: HashMap。
+// 出力はどちらもボクセルの最初の点の順。
+
+// Number of leading columns of every VoxelTable row: position (3) and weight (1)
+const HEAD: usize = 4;
+// Columns of a surfel row: xyz, weight, rgb, opacity, normal, radius, curvature
+const SURFEL_WIDTH: usize = 13;
+const SURFEL_NORMAL: usize = 8;
+// Columns of a splat row before the higher-order SH: xyz, weight, sh_dc, opacity
+const SPLAT_HEAD: usize = 8;
+// floor(p / voxel_size) の上限 (i32 に収める)
+const MAX_CELL: f32 = 1073741824.0;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum VoxelMode {
+    // ボクセル内の点の平均
+    #[default]
+    Centroid,
+    // 不透明度で重み付けした平均 (半透明の floater に引っ張られにくい)
+    OpacityWeighted,
+    // 平均位置に最も近い点をそのまま残す
+    Nearest,
+}
+
+impl std::str::FromStr for VoxelMode {
+    type Err = GsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "centroid" => Ok(Self::Centroid),
+            "opacity_weighted" => Ok(Self::OpacityWeighted),
+            "nearest" => Ok(Self::Nearest),
+            _ => Err(GsError::invalid(format!("Unknown mode '{}' (expected 'centroid', 'opacity_weighted' or 'nearest')", s))),
+        }
+    }
+}
+
+impl VoxelMode {
+    fn weight(self, opacity: f32) -> f32 {
+        match self {
+            Self::OpacityWeighted => opacity.max(0.0),
+            _ => 1.0,
+        }
+    }
+}
+
+// 1 / voxel_size (the GPU and the CPU both multiply so that they agree on voxel boundaries)
+pub fn inverse_size(voxel_size: f32) -> GsResult<f32> {
+    if !(voxel_size > 0.0 && voxel_size.is_finite()) {
+        return Err(GsError::invalid(format!("voxel_size must be > 0 and finite, got {}", voxel_size)));
+    }
+    Ok(1.0 / voxel_size)
+}
+
+// Per-point rows of `width` floats: xyz, weight, then the attributes averaged per voxel
+#[derive(Clone, Debug)]
+pub struct VoxelTable {
+    pub width: usize,
+    // 法線の列 (代表点の向きに揃えて平均し、正規化する)
+    pub normal_offset: Option<usize>,
+    pub values: Vec<f32>,
+}
+
+impl VoxelTable {
+    // nearest は代表点を選ぶだけなので位置と重みのみ
+    pub fn splats(splats: &[GaussianSplat], sh: &ShCoeffs, mode: VoxelMode) -> Self {
+        let stride = sh.stride();
+        let width = if mode == VoxelMode::Nearest { HEAD } else { SPLAT_HEAD + stride };
+        let mut values = Vec::with_capacity(splats.len() * width);
+        for (i, s) in splats.iter().enumerate() {
+            values.extend_from_slice(&s.pos);
+            values.push(mode.weight(s.opacity));
+            if width > HEAD {
+                values.extend_from_slice(&s.sh_dc);
+                values.push(s.opacity);
+                if stride > 0 { values.extend_from_slice(sh.splat(i)); }
+            }
+        }
+        Self { width, normal_offset: None, values }
+    }
+
+    pub fn surfels(surfels: &[Surfel], mode: VoxelMode) -> Self {
+        let width = if mode == VoxelMode::Nearest { HEAD } else { SURFEL_WIDTH };
+        let mut values = Vec::with_capacity(surfels.len() * width);
+        for s in surfels {
+            values.extend_from_slice(&s.pos);
+            values.push(mode.weight(s.opacity));
+            if width > HEAD {
+                values.extend_from_slice(&s.color);
+                values.push(s.opacity);
+                values.extend_from_slice(&s.normal);
+                values.push(s.radius);
+                values.push(s.curvature);
+            }
+        }
+        Self { width, normal_offset: (width > HEAD).then_some(SURFEL_NORMAL), values }
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len() / self.width
+    }
+
+    fn row(&self, i: usize) -> &[f32] {
+        &self.values[i * self.width..(i + 1) * self.width]
+    }
+
+    fn pos(&self, i: usize) -> [f32; 3] {
+        let row = self.row(i);
+        [row[0], row[1], row[2]]
+    }
+}
+
+// One row per voxel (same columns as the VoxelTable; the weight column holds the weight sum)
+// and the representative point of every voxel, in order of the first point of each voxel
+#[derive(Clone, Debug, Default)]
+pub struct Merged {
+    pub width: usize,
+    pub values: Vec<f32>,
+    pub reps: Vec<u32>,
+}
+
+impl Merged {
+    // GPU 出力の 1 ボクセル = width 個の平均 (f32 のビット) + 代表点
+    pub fn from_gpu(width: usize, words: &[u32]) -> Self {
+        let mut merged = Self { width, ..Default::default() };
+        for row in words.chunks_exact(width + 1) {
+            merged.values.extend(row[..width].iter().map(|&w| f32::from_bits(w)));
+            merged.reps.push(row[width]);
+        }
+        merged
+    }
+
+    // GPU のチャンクごとの結果 (結果, チャンクの先頭インデックス) をまとめ直す。各行の重みの合計を重みとして
+    // 平均し直し、代表点は元の点の中から全体の平均位置に最も近い点を選び直す (チャンクの代表点から選ぶと、
+    // nearest がチャンク内で最も近いだけの点になる)
+    pub fn combine(pool: &CpuPool, mut parts: Vec<(Merged, u32)>, table: &VoxelTable, inv_size: f32) -> Self {
+        match parts.len() {
+            0 => return Self { width: table.width, ..Default::default() },
+            1 => return parts.pop().unwrap().0,
+            _ => {}
+        }
+        let mut chunks = VoxelTable { width: table.width, normal_offset: table.normal_offset, values: Vec::new() };
+        let mut reps = Vec::new();
+        for (part, offset) in parts {
+            chunks.values.extend(part.values);
+            reps.extend(part.reps.iter().map(|&r| r + offset));
+        }
+        let mut merged = merge_cpu(pool, &chunks, inv_size);
+        for r in &mut merged.reps { *r = reps[*r as usize]; }
+
+        // 代表点のセル → ボクセル。元の点を 1 回なめて (距離の 2 乗, インデックス) の辞書順で最小の点を選ぶ
+        let voxel_of: HashMap<[i32; 3], usize> = merged.reps.iter().enumerate()
+            .map(|(v, &r)| (cell_of(table.pos(r as usize), inv_size), v))
+            .collect();
+        let mut best: Vec<(f32, u32)> = merged.reps.iter().map(|&r| (f32::MAX, r)).collect();
+        for i in 0..table.len() {
+            let p = table.pos(i);
+            let Some(&v) = voxel_of.get(&cell_of(p, inv_size)) else { continue };
+            let mean = merged.row(v);
+            let d = [p[0] - mean[0], p[1] - mean[1], p[2] - mean[2]];
+            let d2 = d[0] * d[0] + d[1] * d[1] + d[2] * d[2];
+            let (best_d2, best_i) = best[v];
+            if d2 < best_d2 || (d2 == best_d2 && (i as u32) < best_i) {
+                best[v] = (d2, i as u32);
+            }
+        }
+        merged.reps = best.into_iter().map(|(_, r)| r).collect();
+        merged
+    }
+
+    pub fn len(&self) -> usize {
+        self.reps.len()
+    }
+
+    fn row(&self, v: usize) -> &[f32] {
+        &self.values[v * self.width..(v + 1) * self.width]
+    }
+}
+
+fn cell_of(p: [f32; 3], inv_size: f32) -> [i32; 3] {
+    p.map(|x| (x * inv_size).floor().clamp(-MAX_CELL, MAX_CELL) as i32)
+}
+
+// CPU version of the GPU merge (same arithmetic). Members are summed in index order here; the GPU sums them in the
+// order scatter_members happened to write them, so the means agree only up to float rounding
+pub fn merge_cpu(pool: &CpuPool, table: &VoxelTable, inv_size: f32) -> Merged {
+    let n = table.len();
+    // 1. 最初に現れた順にボクセル番号を振る
+    let mut ids: HashMap<[i32; 3], u32> = HashMap::new();
+    let voxel_of: Vec<u32> = (0..n).map(|i| {
+        let next = ids.len() as u32;
+        *ids.entry(cell_of(table.pos(i), inv_size)).or_insert(next)
+    }).collect();
+    let count = ids.len();
+
+    // 2. ボクセル順に並べる (counting sort, 各ボクセル内はインデックス順)
+    let mut start = vec![0usize; count + 1];
+    for &v in &voxel_of { start[v as usize + 1] += 1; }
+    for v in 0..count { start[v + 1] += start[v]; }
+    let mut cursor = start.clone();
+    let mut members = vec![0u32; n];
+    for (i, &v) in voxel_of.iter().enumerate() {
+        members[cursor[v as usize]] = i as u32;
+        cursor[v as usize] += 1;
+    }
+
+    // 3. ボクセルごとに平均
+    let width = table.width;
+    let mut values = vec![0.0f32; count * width];
+    let mut reps = vec![0u32; count];
+    pool.install(|| {
+        values.par_chunks_mut(width).zip(reps.par_iter_mut()).enumerate().for_each(|(v, (out, rep))| {
+            *rep = reduce(table, &members[start[v]..start[v + 1]], out);
+        });
+    });
+    Merged { width, values, reps }
+}
+
+// reduce_voxels in voxel.wgsl: weighted mean of the rows of `members` (ascending) into `out`; returns the representative
+fn reduce(table: &VoxelTable, members: &[u32], out: &mut [f32]) -> u32 {
+    let width = table.width;
+    let leader = members[0] as usize;
+    let mut weight_sum: f32 = members.iter().map(|&m| table.row(m as usize)[3]).sum();
+    let uniform_weights = weight_sum.is_nan() || weight_sum <= 0.0;
+    if uniform_weights { weight_sum = members.len() as f32; }
+
+    out.fill(0.0);
+    let origin = table.pos(leader);
+    let leader_n = table.normal_offset.map(|o| {
+        let row = table.row(leader);
+        [row[o], row[o + 1], row[o + 2]]
+    });
+    for &m in members {
+        let row = table.row(m as usize);
+        let w = if uniform_weights { 1.0 } else { row[3] } / weight_sum;
+        // 位置は最初の点からの差で平均する (桁落ち対策)
+        for c in 0..3 { out[c] += w * (row[c] - origin[c]); }
+        let sign = match (table.normal_offset, leader_n) {
+            (Some(o), Some(n)) if row[o] * n[0] + row[o + 1] * n[1] + row[o + 2] * n[2] < 0.0 => -1.0,
+            _ => 1.0,
+        };
+        for c in HEAD..width {
+            let flip = table.normal_offset.is_some_and(|o| (o..o + 3).contains(&c));
+            out[c] += w * if flip { row[c] * sign } else { row[c] };
+        }
+    }
+    let mean = [0, 1, 2].map(|c| origin[c] + out[c]);
+    out[..3].copy_from_slice(&mean);
+    out[3] = weight_sum;
+
+    if let (Some(o), Some(n)) = (table.normal_offset, leader_n) {
+        let sum = [out[o], out[o + 1], out[o + 2]];
+        let len = (sum[0] * sum[0] + sum[1] * sum[1] + sum[2] * sum[2]).sqrt();
+        let unit = if len > 0.0 { sum.map(|x| x / len) } else { n };
+        out[o..o + 3].copy_from_slice(&unit);
+    }
+
+    // 平均位置に最も近い点 ((距離の 2 乗, インデックス) の辞書順)
+    let mut rep = leader as u32;
+    let mut rep_d2 = f32::MAX;
+    for &m in members {
+        let p = table.pos(m as usize);
+        let d = [p[0] - mean[0], p[1] - mean[1], p[2] - mean[2]];
+        let d2 = d[0] * d[0] + d[1] * d[1] + d[2] * d[2];
+        if d2 < rep_d2 || (d2 == rep_d2 && m < rep) {
+            rep = m;
+            rep_d2 = d2;
+        }
+    }
+    rep
+}
+
+// Splats of the downsampled set: the representative splat with the averaged position, color, opacity and SH
+// (rotation and scale come from the representative)
+pub fn merged_splats(splats: &[GaussianSplat], sh: &ShCoeffs, merged: &Merged, mode: VoxelMode) -> (Vec<GaussianSplat>, ShCoeffs) {
+    let stride = sh.stride();
+    let mut out = Vec::with_capacity(merged.len());
+    let mut coeffs = Vec::with_capacity(merged.len() * stride);
+    for (v, &rep) in merged.reps.iter().enumerate() {
+        let mut splat = splats[rep as usize];
+        if mode == VoxelMode::Nearest {
+            if stride > 0 { coeffs.extend_from_slice(sh.splat(rep as usize)); }
+        } else {
+            let row = merged.row(v);
+            splat.pos = [row[0], row[1], row[2]];
+            splat.sh_dc = [row[4], row[5], row[6]];
+            splat.opacity = row[7];
+            coeffs.extend_from_slice(&row[SPLAT_HEAD..]);
+        }
+        out.push(splat);
+    }
+    (out, ShCoeffs { degree: sh.degree, coeffs })
+}
+
+// Surfels of the downsampled set (averaged color / opacity / radius / curvature, renormalized mean normal)
+pub fn merged_surfels(surfels: &[Surfel], merged: &Merged, mode: VoxelMode) -> Vec<Surfel> {
+    merged.reps.iter().enumerate().map(|(v, &rep)| {
+        let mut surfel = surfels[rep as usize];
+        if mode != VoxelMode::Nearest {
+            let row = merged.row(v);
+            surfel.pos = [row[0], row[1], row[2]];
+            surfel.color = [row[4], row[5], row[6]];
+            surfel.opacity = row[7];
+            surfel.normal = [row[8], row[9], row[10]];
+            surfel.radius = row[11];
+            surfel.curvature = row[12];
+        }
+        surfel
+    }).collect()
+}
+
+// ============================================================================
+//  GPU Hash Table Merge
+// ============================================================================
+
+// Uniform of voxel.wgsl (Params)
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct VoxelParams {
+    inv_size: f32,
+    table_mask: u32,
+    width: u32,
+    normal_offset: u32,
+    count: u32,
+    first_base: u32,
+    _pad: [u32; 2],
+}
+
+impl VoxelParams {
+    // ハッシュ表は点数の 2 倍以上 (衝突が少なく、find_first がバケットを走査することはまれ)
+    pub fn new(table: &VoxelTable, count: usize, inv_size: f32) -> Self {
+        let table_len = (count * 2).next_power_of_two().max(64) as u32;
+        Self {
+            inv_size,
+            table_mask: table_len - 1,
+            width: table.width as u32,
+            normal_offset: table.normal_offset.map_or(u32::MAX, |o| o as u32),
+            count: count as u32,
+            first_base: crate::scan::buffer_len(table_len + 1) as u32,
+            _pad: [0; 2],
+        }
+    }
+
+    pub fn table_len(&self) -> u32 {
+        self.table_mask + 1
+    }
+
+    // Bytes of the table buffer: per-hash counts (scanned over table_len + 1) followed by the per-hash first points
+    pub fn table_size(&self) -> u64 {
+        (self.first_base as u64 + self.table_len() as u64) * 4
+    }
+}
+
+// Bindings of voxel.wgsl (each pass binds at most 4 storage buffers)
+pub struct VoxelBuffers<'a> {
+    pub attrs: wgpu::BindingResource<'a>,
+    pub table: wgpu::BindingResource<'a>,
+    pub voxel_of: wgpu::BindingResource<'a>,
+    pub params: wgpu::BindingResource<'a>,
+    pub leaders: wgpu::BindingResource<'a>,
+    pub cursor: wgpu::BindingResource<'a>,
+    pub members: wgpu::BindingResource<'a>,
+}
+
+// count_cells → scan → bucket_points → find_first → mark_leaders → scan → resolve_voxels → scan → scatter_members (group)
+// と reduce_voxels
+pub struct VoxelPipeline {
+    count: wgpu::ComputePipeline,
+    bucket: wgpu::ComputePipeline,
+    first: wgpu::ComputePipeline,
+    mark: wgpu::ComputePipeline,
+    resolve: wgpu::ComputePipeline,
+    scatter: wgpu::ComputePipeline,
+    reduce: wgpu::ComputePipeline,
+    scan: ScanPipeline,
+    layouts: [wgpu::BindGroupLayout; 7],
+}
+
+// Storage bindings of each pass (binding 3 is the uniform in all of them)
+const COUNT: &[(u32, bool)] = &[(0, true), (1, false)];
+const BUCKET: &[(u32, bool)] = &[(0, true), (1, false), (6, false)];
+const FIRST: &[(u32, bool)] = &[(0, true), (1, false), (2, false), (6, false)];
+const MARK: &[(u32, bool)] = &[(2, false), (4, false)];
+const RESOLVE: &[(u32, bool)] = &[(2, false), (4, false), (5, false)];
+const SCATTER: &[(u32, bool)] = &[(2, false), (5, false), (6, false)];
+const REDUCE: &[(u32, bool)] = &[(0, true), (5, false), (6, false), (7, false)];
+
+impl VoxelPipeline {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Voxel Shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("voxel.wgsl"))),
+        });
+
+        let layout = |storage: &[(u32, bool)]| {
+            let mut entries: Vec<wgpu::BindGroupLayoutEntry> = storage.iter().map(|&(binding, read_only)| wgpu::BindGroupLayoutEntry {
+                binding,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Storage { read_only }, has_dynamic_offset: false, min_binding_size: None },
+                count: None,
+            }).collect();
+            // Params
+            entries.push(wgpu::BindGroupLayoutEntry {
+                binding: 3,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None },
+                count: None,
+            });
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor { label: Some("Voxel Bind Group Layout"), entries: &entries })
+        };
+        let layouts = [layout(COUNT), layout(BUCKET), layout(FIRST), layout(MARK), layout(RESOLVE), layout(SCATTER), layout(REDUCE)];
+
+        let pipeline = |label, layout: &wgpu::BindGroupLayout, entry_point| {
+            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Voxel Pipeline Layout"),
+                bind_group_layouts: &[layout],
+                push_constant_ranges: &[],
+            });
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point: Some(entry_point),
+                compilation_options: Default::default(),
+                cache: None,
+            })
+        };
+
+        Self {
+            count: pipeline("Voxel Count Pipeline", &layouts[0], "count_cells"),
+            bucket: pipeline("Voxel Bucket Pipeline", &layouts[1], "bucket_points"),
+            first: pipeline("Voxel First Point Pipeline", &layouts[2], "find_first"),
+            mark: pipeline("Voxel Mark Pipeline", &layouts[3], "mark_leaders"),
+            resolve: pipeline("Voxel Resolve Pipeline", &layouts[4], "resolve_voxels"),
+            scatter: pipeline("Voxel Scatter Pipeline", &layouts[5], "scatter_members"),
+            reduce: pipeline("Voxel Reduce Pipeline", &layouts[6], "reduce_voxels"),
+            scan: ScanPipeline::new(device),
+            layouts,
+        }
+    }
+
+    fn bind_group(&self, device: &wgpu::Device, layout: usize, entries: Vec<(u32, wgpu::BindingResource)>) -> wgpu::BindGroup {
+        let entries: Vec<wgpu::BindGroupEntry> = entries.into_iter().map(|(binding, resource)| wgpu::BindGroupEntry { binding, resource }).collect();
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Voxel Bind Group"),
+            layout: &self.layouts[layout],
+            entries: &entries,
+        })
+    }
+
+    // Groups `point_count` points by voxel. `table`, `leaders` and `cursor` must be zeroed; `table` is scanned over
+    // table_len + 1 elements and holds VoxelParams::table_size() bytes, `leaders` / `cursor` are scanned over
+    // point_count + 1 (scan::buffer_len(len) u32 each).
+    // Afterwards leaders[point_count] holds the number of voxels.
+    #[allow(clippy::too_many_arguments)]
+    pub fn group(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        buffers: &VoxelBuffers,
+        table: &wgpu::Buffer,
+        leaders: &wgpu::Buffer,
+        cursor: &wgpu::Buffer,
+        point_count: u32,
+        table_len: u32,
+    ) {
+        let b = buffers;
+        let count = self.bind_group(device, 0, vec![(0, b.attrs.clone()), (1, b.table.clone()), (3, b.params.clone())]);
+        let bucket = self.bind_group(device, 1, vec![(0, b.attrs.clone()), (1, b.table.clone()), (6, b.members.clone()), (3, b.params.clone())]);
+        let first = self.bind_group(device, 2, vec![
+            (0, b.attrs.clone()), (1, b.table.clone()), (2, b.voxel_of.clone()), (6, b.members.clone()), (3, b.params.clone()),
+        ]);
+        let mark = self.bind_group(device, 3, vec![(2, b.voxel_of.clone()), (4, b.leaders.clone()), (3, b.params.clone())]);
+        let resolve = self.bind_group(device, 4, vec![(2, b.voxel_of.clone()), (4, b.leaders.clone()), (5, b.cursor.clone()), (3, b.params.clone())]);
+        let scatter = self.bind_group(device, 5, vec![(2, b.voxel_of.clone()), (5, b.cursor.clone()), (6, b.members.clone()), (3, b.params.clone())]);
+
+        pass(encoder, &self.count, &count, point_count);
+        self.scan.encode(device, encoder, table, table_len + 1);
+        pass(encoder, &self.bucket, &bucket, point_count);
+        pass(encoder, &self.first, &first, point_count);
+        pass(encoder, &self.mark, &mark, point_count);
+        self.scan.encode(device, encoder, leaders, point_count + 1);
+        pass(encoder, &self.resolve, &resolve, point_count);
+        self.scan.encode(device, encoder, cursor, point_count + 1);
+        pass(encoder, &self.scatter, &scatter, point_count);
+    }
+
+    // One output row (width + 1 u32) per voxel; `output` is bound with exactly voxel_count rows
+    pub fn reduce(&self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, buffers: &VoxelBuffers, output: wgpu::BindingResource, voxel_count: u32) {
+        let b = buffers;
+        let reduce = self.bind_group(device, 6, vec![(0, b.attrs.clone()), (5, b.cursor.clone()), (6, b.members.clone()), (7, output), (3, b.params.clone())]);
+        pass(encoder, &self.reduce, &reduce, voxel_count);
+    }
+}
+
+fn pass(encoder: &mut wgpu::CommandEncoder, pipeline: &wgpu::ComputePipeline, bind_group: &wgpu::BindGroup, threads: u32) {
+    let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+    cpass.set_pipeline(pipeline);
+    cpass.set_bind_group(0, bind_group, &[]);
+    cpass.dispatch_workgroups(threads.div_ceil(64), 1, 1);
+}
+
+#[cfg(test)]
+mod combine_tests {
+    use super::*;
+
+    // GPU のチャンク分割と同じく、行を分けてまとめた結果を combine すると全体を一度にまとめた結果になる
+    #[test]
+    fn chunked_nearest_matches_single_pass() {
+        // 2 つのボクセルにまたがる点。前半のチャンクには平均から遠い点だけが入る
+        let points: [[f32; 3]; 8] = [
+            [0.05, 0.05, 0.05], [0.95, 0.95, 0.95], [0.1, 0.9, 0.1], [1.5, 0.5, 0.5],
+            [0.5, 0.5, 0.45], [0.9, 0.1, 0.9], [1.9, 0.1, 0.1], [1.55, 0.45, 0.5],
+        ];
+        let table = VoxelTable { width: HEAD, normal_offset: None, values: points.iter().flat_map(|p| [p[0], p[1], p[2], 1.0]).collect() };
+        let pool = CpuPool::default();
+        let whole = merge_cpu(&pool, &table, 1.0);
+
+        let parts = table.values.chunks(4 * HEAD).enumerate().map(|(i, rows)| {
+            let chunk = VoxelTable { width: HEAD, normal_offset: None, values: rows.to_vec() };
+            (merge_cpu(&pool, &chunk, 1.0), (i * 4) as u32)
+        }).collect();
+        let combined = Merged::combine(&pool, parts, &table, 1.0);
+
+        assert_eq!(combined.reps, whole.reps);
+        assert_eq!(combined.reps, vec![4, 7]);
+        for (a, b) in combined.values.iter().zip(&whole.values) {
+            assert!((a - b).abs() < 1e-6, "{} != {}", a, b);
+        }
+    }
+}
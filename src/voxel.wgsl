@@ -0,0 +1,208 @@
+// src/voxel.wgsl
+// ボクセルグリッドによるダウンサンプリング (src/voxel.rs)
+// 1. count_cells: 各点のセル (floor(p / voxel_size)) のハッシュごとに点数を数え、ハッシュごとの最小インデックスを
+//    atomicMax (NONE - idx) で記録する → prefix sum (scan.wgsl) で各ハッシュの開始位置にする
+// 2. bucket_points: 点をハッシュ順に並べる (以降 table[h] は h の終端 = h + 1 の開始)
+// 3. find_first: ハッシュの最小インデックスの点が同じセルならそれがボクセルの最初の点。別のセルと衝突して
+//    取られた場合だけ、同じハッシュの点から同じセルの最小インデックスを探す
+// 4. mark_leaders: 各ボクセルの最初の点に 1 を立てる → prefix sum で出力順 (最初の点の順) の番号にする
+// 5. resolve_voxels: 各点の出力番号を求めてボクセルごとに数える → prefix sum で各ボクセルの開始位置にする
+// 6. scatter_members: 点をボクセル順に並べる (以降 cursor[v] は v の終端 = v + 1 の開始)。
+//    ボクセル内の順序は atomicAdd の順で不定なので、7 の和は実行ごとに丸め誤差の範囲で変わり得る
+// 7. reduce_voxels: ボクセルごとに属性の (重み付き) 平均と、平均位置に最も近い点を求める
+// ストレージバッファは 1 ステージ 4 個まで (downlevel の上限) なので、パスごとに使うバッファを分けている
+// atomicCompareExchangeWeak は GLSL バックエンドで使えないので、ハッシュ表は knn.wgsl と同じ数え上げ + 並べ替えで作る
+
+struct Params {
+    // 1 / voxel_size
+    inv_size: f32,
+    // ハッシュ表の大きさ - 1 (2 のべき乗)。table は表の大きさ + 1 の長さ (scan するため)
+    table_mask: u32,
+    // attrs の 1 行の長さ (xyz, 重み, 平均する属性...)
+    width: u32,
+    // 法線の列 (向きを揃えて平均し、正規化する)。NONE は法線なし
+    normal_offset: u32,
+    count: u32,
+    // table の中のハッシュごとの最小インデックス (NONE - idx, 0 は空) の開始位置。数え上げと scan の作業領域の後ろ
+    first_base: u32,
+    _pad0: u32,
+    _pad1: u32,
+};
+
+const NONE: u32 = 0xffffffffu;
+const MAX_CELL: f32 = 1073741824.0;
+
+@group(0) @binding(0) var<storage, read> attrs : array<f32>;
+@group(0) @binding(1) var<storage, read_write> table : array<atomic<u32>>;
+// ボクセルの最初の点 → (resolve_voxels 以降) 出力ボクセルの番号
+@group(0) @binding(2) var<storage, read_write> voxel_of : array<u32>;
+@group(0) @binding(3) var<uniform> params : Params;
+@group(0) @binding(4) var<storage, read_write> leaders : array<u32>;
+@group(0) @binding(5) var<storage, read_write> cursor : array<atomic<u32>>;
+// ハッシュ順の点 → (scatter_members 以降) ボクセル順の点
+@group(0) @binding(6) var<storage, read_write> members : array<u32>;
+// ボクセルごとに width 個の平均 (bitcast した f32) + 代表点のインデックス
+@group(0) @binding(7) var<storage, read_write> out_voxels : array<u32>;
+
+fn point_pos(idx: u32) -> vec3<f32> {
+    let base = idx * params.width;
+    return vec3<f32>(attrs[base], attrs[base + 1u], attrs[base + 2u]);
+}
+
+fn cell_of(p: vec3<f32>) -> vec3<i32> {
+    let c = floor(p * params.inv_size);
+    return vec3<i32>(clamp(c, vec3<f32>(-MAX_CELL), vec3<f32>(MAX_CELL)));
+}
+
+fn cell_hash(c: vec3<i32>) -> u32 {
+    let u = bitcast<vec3<u32>>(c);
+    return ((u.x * 73856093u) ^ (u.y * 19349663u) ^ (u.z * 83492791u)) & params.table_mask;
+}
+
+@compute @workgroup_size(64)
+fn count_cells(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let idx = global_id.x;
+    if (idx >= params.count) { return; }
+    let h = cell_hash(cell_of(point_pos(idx)));
+    atomicAdd(&table[h], 1u);
+    // 0 クリアのまま使えるよう、最小値の代わりに NONE - idx の最大値を取る
+    atomicMax(&table[params.first_base + h], NONE - idx);
+}
+
+@compute @workgroup_size(64)
+fn bucket_points(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let idx = global_id.x;
+    if (idx >= params.count) { return; }
+    members[atomicAdd(&table[cell_hash(cell_of(point_pos(idx)))], 1u)] = idx;
+}
+
+@compute @workgroup_size(64)
+fn find_first(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let idx = global_id.x;
+    if (idx >= params.count) { return; }
+    let c = cell_of(point_pos(idx));
+    let h = cell_hash(c);
+    let hash_first = NONE - atomicLoad(&table[params.first_base + h]);
+    if (all(cell_of(point_pos(hash_first)) == c)) {
+        voxel_of[idx] = hash_first;
+        return;
+    }
+
+    // 衝突した別のセルに最小インデックスを取られたときだけバケットを走査する (その点は除く)
+    var start = 0u;
+    if (h > 0u) { start = atomicLoad(&table[h - 1u]); }
+    let end = atomicLoad(&table[h]);
+    var first = idx;
+    for (var s = start; s < end; s = s + 1u) {
+        let m = members[s];
+        if (m < first && all(cell_of(point_pos(m)) == c)) { first = m; }
+    }
+    voxel_of[idx] = first;
+}
+
+@compute @workgroup_size(64)
+fn mark_leaders(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let idx = global_id.x;
+    if (idx >= params.count) { return; }
+    leaders[idx] = select(0u, 1u, voxel_of[idx] == idx);
+}
+
+@compute @workgroup_size(64)
+fn resolve_voxels(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let idx = global_id.x;
+    if (idx >= params.count) { return; }
+    let voxel = leaders[voxel_of[idx]];
+    voxel_of[idx] = voxel;
+    atomicAdd(&cursor[voxel], 1u);
+}
+
+@compute @workgroup_size(64)
+fn scatter_members(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let idx = global_id.x;
+    if (idx >= params.count) { return; }
+    members[atomicAdd(&cursor[voxel_of[idx]], 1u)] = idx;
+}
+
+fn out_f32(i: u32) -> f32 {
+    return bitcast<f32>(out_voxels[i]);
+}
+
+@compute @workgroup_size(64)
+fn reduce_voxels(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let v = global_id.x;
+    let width = params.width;
+    if (v >= arrayLength(&out_voxels) / (width + 1u)) { return; }
+    var start = 0u;
+    if (v > 0u) { start = atomicLoad(&cursor[v - 1u]); }
+    let end = atomicLoad(&cursor[v]);
+
+    // 最初の点 (法線の向きと位置の基準) と重みの合計。重みが無ければ一様に平均する
+    var leader = NONE;
+    var weight_sum = 0.0;
+    for (var s = start; s < end; s = s + 1u) {
+        let m = members[s];
+        leader = min(leader, m);
+        weight_sum = weight_sum + attrs[m * width + 3u];
+    }
+    let uniform_weights = !(weight_sum > 0.0);
+    if (uniform_weights) { weight_sum = f32(end - start); }
+
+    let base = v * (width + 1u);
+    for (var c = 0u; c < width; c = c + 1u) { out_voxels[base + c] = 0u; }
+    let origin = point_pos(leader);
+    let has_normal = params.normal_offset != NONE;
+    var leader_n = vec3<f32>(0.0);
+    if (has_normal) {
+        let o = leader * width + params.normal_offset;
+        leader_n = vec3<f32>(attrs[o], attrs[o + 1u], attrs[o + 2u]);
+    }
+
+    for (var s = start; s < end; s = s + 1u) {
+        let m = members[s];
+        let row = m * width;
+        var w = attrs[row + 3u];
+        if (uniform_weights) { w = 1.0; }
+        w = w / weight_sum;
+        // 位置は最初の点からの差で平均する (桁落ち対策)
+        let d = point_pos(m) - origin;
+        for (var c = 0u; c < 3u; c = c + 1u) {
+            out_voxels[base + c] = bitcast<u32>(out_f32(base + c) + w * d[c]);
+        }
+        var sign = 1.0;
+        if (has_normal) {
+            let o = row + params.normal_offset;
+            if (dot(vec3<f32>(attrs[o], attrs[o + 1u], attrs[o + 2u]), leader_n) < 0.0) { sign = -1.0; }
+        }
+        for (var c = 4u; c < width; c = c + 1u) {
+            var value = attrs[row + c];
+            if (has_normal && c >= params.normal_offset && c < params.normal_offset + 3u) { value = value * sign; }
+            out_voxels[base + c] = bitcast<u32>(out_f32(base + c) + w * value);
+        }
+    }
+    let mean = origin + vec3<f32>(out_f32(base), out_f32(base + 1u), out_f32(base + 2u));
+    for (var c = 0u; c < 3u; c = c + 1u) { out_voxels[base + c] = bitcast<u32>(mean[c]); }
+    out_voxels[base + 3u] = bitcast<u32>(weight_sum);
+
+    if (has_normal) {
+        let o = base + params.normal_offset;
+        let n = vec3<f32>(out_f32(o), out_f32(o + 1u), out_f32(o + 2u));
+        let len = length(n);
+        var unit = leader_n;
+        if (len > 0.0) { unit = n / len; }
+        for (var c = 0u; c < 3u; c = c + 1u) { out_voxels[o + c] = bitcast<u32>(unit[c]); }
+    }
+
+    // 平均位置に最も近い点 ((距離の 2 乗, インデックス) の辞書順)
+    var rep = leader;
+    var rep_d2 = 3.4e38;
+    for (var s = start; s < end; s = s + 1u) {
+        let m = members[s];
+        let d = point_pos(m) - mean;
+        let d2 = d.x * d.x + d.y * d.y + d.z * d.z;
+        if (d2 < rep_d2 || (d2 == rep_d2 && m < rep)) {
+            rep = m;
+            rep_d2 = d2;
+        }
+    }
+    out_voxels[base + width] = rep;
+}
@@ -0,0 +1,52 @@
+# テスト共通のヘルパー (pytest には収集されない)
+import gs_slam_core
+import struct
+
+# 学習器 (3DGS) の PLY と同じ必須プロパティ。値は前活性化 (log scale / logit opacity) で rot は wxyz
+PROPS = ["x", "y", "z", "f_dc_0", "f_dc_1", "f_dc_2", "opacity",
+         "scale_0", "scale_1", "scale_2", "rot_0", "rot_1", "rot_2", "rot_3"]
+
+
+def write_ply(path, rows):
+    # rows: PROPS の順に 14 個の float を並べた行
+    with open(path, "wb") as f:
+        f.write(b"ply\nformat binary_little_endian 1.0\n")
+        f.write(f"element vertex {len(rows)}\n".encode())
+        for p in PROPS:
+            f.write(f"property float {p}\n".encode())
+        f.write(b"end_header\n")
+        for row in rows:
+            f.write(struct.pack(f"<{len(PROPS)}f", *row))
+
+
+# GPU / CPU の両方で同じテストを回すときのバックエンド
+BACKENDS = ("cpu", "gpu")
+
+
+def make_manager(positions, backend, scale=(0.05, 0.05, 0.005), opacities=None, colors=None, sh_rest=None):
+    # 軸が揃った (回転なしの) スプラット。近傍探索・ボクセル化・外れ値除去のテスト用
+    n = len(positions)
+    m = gs_slam_core.SplatManager.from_arrays(
+        positions,
+        [list(scale)] * n,
+        [[0.0, 0.0, 0.0, 1.0]] * n,
+        opacities if opacities is not None else [0.9] * n,
+        colors=colors if colors is not None else [[0.5, 0.5, 0.5]] * n,
+        sh_rest=sh_rest,
+    )
+    m.backend = backend
+    return m
+
+
+def random_manager(n, seed=0, sh_degree=0, extent=10.0):
+    # 乱数で作った任意向きのスプラット (形式の往復テスト用)。numpy を使うテストだけが呼ぶ
+    import numpy as np
+    rng = np.random.default_rng(seed)
+    pos = rng.uniform(-extent, extent, (n, 3))
+    scales = np.exp(rng.uniform(-6, -1, (n, 3)))
+    rot = rng.normal(size=(n, 4))
+    rot /= np.linalg.norm(rot, axis=1, keepdims=True)
+    opacities = rng.uniform(0, 1, n)
+    sh_dc = rng.uniform(-1.5, 1.5, (n, 3))
+    rest = rng.uniform(-0.9, 0.9, (n, (sh_degree + 1) ** 2 - 1, 3)) if sh_degree > 0 else None
+    return gs_slam_core.SplatManager.from_arrays(pos, scales, rot, opacities, sh_dc=sh_dc, sh_rest=rest)
@@ -0,0 +1,45 @@
+import gs_slam_core
+import math
+import os
+
+from splat_helpers import write_ply
+
+TEMP_PLY = "data/test_activation.ply"
+
+
+def sigmoid(x):
+    return 1.0 / (1.0 + math.exp(-x))
+
+
+def test_value_domains():
+    # opacity=0.4, scale=(-1, -2, -3) を保存
+    row = [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.4, -1.0, -2.0, -3.0, 1.0, 0.0, 0.0, 0.0]
+    write_ply(TEMP_PLY, [row])
+    try:
+        # Pre-activation (trainer output): exp / sigmoid are applied at load time
+        m = gs_slam_core.SplatManager(TEMP_PLY)
+        assert abs(m.get_splat_opacity(0) - sigmoid(0.4)) < 1e-6
+        expected = [math.exp(-1.0), math.exp(-2.0), math.exp(-3.0)]
+        assert all(abs(a - b) < 1e-6 for a, b in zip(m.get_splat_scale(0), expected))
+        print("✅ preactivation: scale/opacity activated at load")
+
+        # Already activated: values are kept as-is
+        row_act = [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.4, 0.3, 0.2, 0.1, 1.0, 0.0, 0.0, 0.0]
+        write_ply(TEMP_PLY, [row_act])
+        m = gs_slam_core.SplatManager(TEMP_PLY, value_domain="activated")
+        assert abs(m.get_splat_opacity(0) - 0.4) < 1e-6
+        assert all(abs(a - b) < 1e-6 for a, b in zip(m.get_splat_scale(0), [0.3, 0.2, 0.1]))
+        print("✅ activated: values stored unchanged")
+
+        try:
+            gs_slam_core.SplatManager(TEMP_PLY, value_domain="exp")
+            raise AssertionError("Unknown value_domain should raise ValueError")
+        except ValueError:
+            print("✅ Unknown value_domain rejected")
+    finally:
+        os.remove(TEMP_PLY)
+
+
+if __name__ == "__main__":
+    os.makedirs("data", exist_ok=True)
+    test_value_domains()
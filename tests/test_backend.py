@@ -0,0 +1,109 @@
+import gs_slam_core
+import os
+import random
+
+from splat_helpers import write_ply
+
+TEMP_PLY = "data/test_backend.ply"
+TEMP_LARGE_PLY = "data/test_backend_large.ply"
+
+
+def simple_rows(n):
+    # 最小軸は z (rot は wxyz の単位クォータニオン)
+    return [[i * 0.1, 0.0, 1.0, 0.2, -0.1, 0.4, 1.0, -1.0, -1.5, -4.0, 1.0, 0.0, 0.0, 0.0] for i in range(n)]
+
+
+def random_rows(n, seed=0):
+    rng = random.Random(seed)
+    return [[rng.uniform(-5, 5) for _ in range(7)] + [rng.uniform(-5, -1) for _ in range(3)] + [rng.gauss(0, 1) for _ in range(4)]
+            for _ in range(n)]
+
+
+def test_backend_setting():
+    write_ply(TEMP_PLY, simple_rows(32))
+    try:
+        m = gs_slam_core.SplatManager(TEMP_PLY)
+        assert m.backend == "auto"
+        assert m.last_backend is None, "Nothing has been computed yet"
+
+        m.backend = "CPU"
+        assert m.backend == "cpu"
+        assert m.compute_geometry() == m.count()
+        assert m.last_backend == "cpu"
+        assert all(abs(a - b) < 1e-6 for a, b in zip(m.get_surfel_normal(0), [0.0, 0.0, 1.0]))
+        print("✅ backend='cpu' runs compute_geometry on the CPU")
+
+        assert gs_slam_core.SplatManager(TEMP_PLY, backend="cpu").backend == "cpu"
+        try:
+            m.backend = "tpu"
+            raise AssertionError("Unknown backend should raise InvalidParameterError")
+        except gs_slam_core.InvalidParameterError:
+            print("✅ Unknown backend rejected")
+        assert m.backend == "cpu", "A rejected value must not change the setting"
+    finally:
+        os.remove(TEMP_PLY)
+
+
+def test_auto_fallback():
+    # GPU の有無に関わらず auto は成功し、実際に使われた方を報告する
+    write_ply(TEMP_PLY, simple_rows(32))
+    try:
+        m = gs_slam_core.SplatManager(TEMP_PLY)
+        assert m.compute_geometry() == m.count()
+        used = m.last_backend
+        assert used in ("gpu", "cpu")
+        auto = [m.get_surfel_color(i) for i in range(m.count())]
+
+        m.compute_geometry_cpu()
+        assert m.last_backend == "cpu"
+        for i in range(m.count()):
+            assert all(abs(a - b) < 1e-4 for a, b in zip(m.get_surfel_color(i), auto[i]))
+        print(f"✅ backend='auto' computed geometry on the {used.upper()}")
+
+        m.backend = "gpu"
+        try:
+            m.compute_geometry()
+            assert m.last_backend == "gpu"
+            print("✅ backend='gpu' ran on the GPU")
+        except (gs_slam_core.GpuUnavailableError, gs_slam_core.DeviceLostError) as e:
+            assert m.last_backend == "cpu", "A failed GPU run must not be reported"
+            print(f"⚠️ backend='gpu' raised {type(e).__name__} (no usable GPU)")
+    finally:
+        os.remove(TEMP_PLY)
+
+
+def test_cpu_threads():
+    # 複数ブロック (1024 点単位) にまたがる点数で、スレッド数によらず同じ結果になること
+    write_ply(TEMP_LARGE_PLY, random_rows(3000))
+    try:
+        m = gs_slam_core.SplatManager(TEMP_LARGE_PLY, backend="cpu")
+        assert m.cpu_threads is None, "Default uses all cores"
+        assert m.compute_geometry(view_dir=[0.0, 0.0, 1.0]) == m.count()
+        parallel = [(m.get_surfel_normal(i), m.get_surfel_color(i)) for i in range(m.count())]
+
+        m.cpu_threads = 1
+        assert m.cpu_threads == 1
+        assert m.compute_geometry(view_dir=[0.0, 0.0, 1.0]) == m.count()
+        for i, (normal, color) in enumerate(parallel):
+            assert m.get_surfel_normal(i) == normal and m.get_surfel_color(i) == color, f"Mismatch at {i}"
+            assert abs(sum(v * v for v in normal) - 1.0) < 1e-5
+        print(f"✅ cpu_threads=1 matches the default pool ({m.count()} splats)")
+
+        m.cpu_threads = 3
+        assert m.compute_super_resolution(4) == m.compute_super_resolution(1) * 4
+        m.cpu_threads = None
+        assert m.cpu_threads is None
+        try:
+            m.cpu_threads = 0
+            raise AssertionError("cpu_threads=0 should raise InvalidParameterError")
+        except gs_slam_core.InvalidParameterError:
+            print("✅ cpu_threads=0 rejected")
+    finally:
+        os.remove(TEMP_LARGE_PLY)
+
+
+if __name__ == "__main__":
+    os.makedirs("data", exist_ok=True)
+    test_backend_setting()
+    test_auto_fallback()
+    test_cpu_threads()
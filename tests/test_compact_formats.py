@@ -0,0 +1,136 @@
+import gs_slam_core
+import numpy as np
+import os
+import struct
+
+from splat_helpers import random_manager
+
+SH_C0 = 0.28209479177387814
+TEMP_SPLAT = "data/test_compact.splat"
+TEMP_KSPLAT = "data/test_compact.ksplat"
+
+
+def match_by_position(src, dst):
+    # ksplat (level >= 1) はバケット順に並べ替えるので最近傍で対応付ける
+    order = []
+    for p in dst.positions:
+        order.append(int(np.argmin(np.sum((src.positions - p) ** 2, axis=1))))
+    assert len(set(order)) == len(order), "Splats did not map one-to-one"
+    return np.array(order)
+
+
+def check_close(src, dst, order, pos_tol, scale_rtol, rot_tol, color_tol):
+    assert np.allclose(dst.positions, src.positions[order], atol=pos_tol)
+    assert np.allclose(dst.scales, src.scales[order], rtol=scale_rtol)
+    # q と -q は同じ回転
+    dots = np.abs(np.sum(dst.rotations * src.rotations[order], axis=1))
+    assert np.all(dots > 1 - rot_tol), f"Rotation error too large: {1 - dots.min()}"
+    assert np.allclose(dst.opacities, src.opacities[order], atol=1 / 255 + 1e-6)
+    src_rgb = 0.5 + SH_C0 * src.sh_dc[order]
+    dst_rgb = 0.5 + SH_C0 * dst.sh_dc
+    assert np.allclose(dst_rgb, src_rgb, atol=color_tol)
+
+
+def test_splat_reader():
+    # 参照実装と同じエンコードで書いた 1 スプラットを読む
+    with open(TEMP_SPLAT, "wb") as f:
+        f.write(struct.pack("<3f3f", 1.0, -2.0, 3.5, 0.1, 0.2, 0.3))
+        f.write(bytes([255, 128, 0, 191]))
+        f.write(bytes([128 + 64, 128, 128 + 64, 128]))  # wxyz = (0.5, 0, 0.5, 0) -> 正規化
+    try:
+        m = gs_slam_core.SplatManager(TEMP_SPLAT)
+        assert m.count() == 1
+        assert m.get_splat_pos(0) == [1.0, -2.0, 3.5]
+        assert np.allclose(m.get_splat_scale(0), [0.1, 0.2, 0.3])
+        assert np.allclose(m.get_splat_rot(0), [0.0, 0.7071068, 0.0, 0.7071068], atol=1e-6)
+        assert abs(m.get_splat_opacity(0) - 191 / 255) < 1e-6
+        rgb = 0.5 + SH_C0 * np.array(m.get_splat_sh(0))
+        assert np.allclose(rgb, [1.0, 128 / 255, 0.0], atol=1e-6)
+        print("✅ .splat reader decodes the antimatter15 layout")
+    finally:
+        os.remove(TEMP_SPLAT)
+
+
+def test_splat_roundtrip():
+    src = random_manager(300, seed=1, sh_degree=2)
+    src.save_splat(TEMP_SPLAT)
+    try:
+        assert os.path.getsize(TEMP_SPLAT) == 300 * 32
+        dst = gs_slam_core.SplatManager(TEMP_SPLAT)
+        assert dst.count() == src.count()
+        assert dst.sh_degree() == 0, ".splat stores DC only"
+        check_close(src, dst, np.arange(300), pos_tol=0, scale_rtol=0, rot_tol=2e-3, color_tol=0.5 / 255 + 1e-6)
+        print("✅ .splat round-trip within 8-bit tolerances")
+    finally:
+        os.remove(TEMP_SPLAT)
+
+
+def test_ksplat_roundtrip():
+    src = random_manager(700, seed=2, sh_degree=1)
+    for level, pos_tol, scale_rtol, sh_tol in [(0, 0, 0, 0), (1, 1e-4, 1e-3, 1e-3), (2, 1e-4, 1e-3, 1.5 / 255)]:
+        src.save_ksplat(TEMP_KSPLAT, compression_level=level)
+        try:
+            dst = gs_slam_core.SplatManager(TEMP_KSPLAT)
+            assert dst.count() == src.count()
+            assert dst.sh_degree() == 1
+            order = np.arange(700) if level == 0 else match_by_position(src, dst)
+            if level == 0:
+                assert np.array_equal(dst.positions, src.positions), "Level 0 keeps f32 positions and order"
+            check_close(src, dst, order, pos_tol, scale_rtol, rot_tol=1e-3, color_tol=0.5 / 255 + 1e-6)
+            for j, i in enumerate(order[:50]):
+                assert np.allclose(dst.get_splat_sh_rest(j), src.get_splat_sh_rest(int(i)), atol=sh_tol + 1e-6)
+            print(f"✅ .ksplat level {level} round-trip within tolerances")
+        finally:
+            os.remove(TEMP_KSPLAT)
+
+    try:
+        src.save_ksplat(TEMP_KSPLAT, compression_level=3)
+        raise AssertionError("compression_level=3 should raise ValueError")
+    except ValueError:
+        print("✅ Invalid compression level rejected")
+
+
+def test_dense_buckets():
+    # 1 セルに 256 個以上入ると満杯バケット + 部分バケットになる
+    rng = np.random.default_rng(3)
+    n = 600
+    pos = rng.uniform(0.1, 4.9, (n, 3))
+    m = gs_slam_core.SplatManager.from_arrays(pos, np.full((n, 3), 0.05), [[0, 0, 0, 1]] * n, np.full(n, 0.5))
+    m.save_ksplat(TEMP_KSPLAT, compression_level=1)
+    try:
+        with open(TEMP_KSPLAT, "rb") as f:
+            data = f.read()
+        bucket_count, = struct.unpack_from("<I", data, 4096 + 12)
+        full, partial = struct.unpack_from("<II", data, 4096 + 32)
+        assert (bucket_count, full, partial) == (3, 2, 1), (bucket_count, full, partial)
+        dst = gs_slam_core.SplatManager(TEMP_KSPLAT)
+        order = match_by_position(m, dst)
+        assert np.allclose(dst.positions, pos[order], atol=1e-4)
+        print("✅ Full and partial buckets decode to the right centers")
+    finally:
+        os.remove(TEMP_KSPLAT)
+
+
+def test_compact_to_geometry():
+    # 読み込んだスプラットはそのまま幾何計算に使える
+    src = random_manager(100, seed=4)
+    src.save_ksplat(TEMP_KSPLAT)
+    try:
+        m = gs_slam_core.SplatManager(TEMP_KSPLAT)
+        assert m.compute_geometry_cpu() == 100
+        m.save_splat(TEMP_SPLAT)
+        assert gs_slam_core.SplatManager(TEMP_SPLAT).count() == 100
+        print("✅ .ksplat -> geometry -> .splat conversion")
+    finally:
+        os.remove(TEMP_KSPLAT)
+        if os.path.exists(TEMP_SPLAT):
+            os.remove(TEMP_SPLAT)
+
+
+if __name__ == "__main__":
+    os.makedirs("data", exist_ok=True)
+    test_splat_reader()
+    test_splat_roundtrip()
+    test_ksplat_roundtrip()
+    test_dense_buckets()
+    test_compact_to_geometry()
@@ -0,0 +1,204 @@
+import gs_slam_core
+import gzip
+import os
+import struct
+
+from splat_helpers import PROPS
+
+TEMP_DIR = "data"
+
+
+def write_file(name, data):
+    path = os.path.join(TEMP_DIR, name)
+    with open(path, "wb") as f:
+        f.write(data)
+    return path
+
+
+def ply_header(props, count=1, extra=b""):
+    lines = [b"ply", b"format binary_little_endian 1.0", extra, f"element vertex {count}".encode()]
+    lines += [f"property float {p}".encode() for p in props]
+    return b"\n".join(l for l in lines if l) + b"\nend_header\n"
+
+
+def test_hierarchy():
+    # 既存の except ValueError / OSError / RuntimeError がそのまま動くこと
+    e = gs_slam_core
+    assert issubclass(e.GsIOError, e.GsError) and issubclass(e.GsIOError, OSError)
+    assert issubclass(e.FormatError, ValueError)
+    assert issubclass(e.PlyFormatError, e.FormatError)
+    assert issubclass(e.InvalidParameterError, ValueError)
+    assert issubclass(e.GpuUnavailableError, RuntimeError) and issubclass(e.DeviceLostError, RuntimeError)
+    assert issubclass(e.BufferTooLargeError, MemoryError)
+    assert not issubclass(e.GpuUnavailableError, ValueError), "No GPU must not look like a bad file"
+    print("✅ Exception hierarchy")
+
+
+def test_ply_property():
+    path = write_file("test_err_missing.ply", ply_header([p for p in PROPS if p != "scale_2"]) + struct.pack("<13f", *range(13)))
+    try:
+        gs_slam_core.SplatManager(path)
+        raise AssertionError("Missing scale_2 should raise PlyFormatError")
+    except gs_slam_core.PlyFormatError as e:
+        assert e.property == "scale_2", e.property
+        assert e.line is None
+        assert "scale_2" in str(e)
+        print(f"✅ PlyFormatError.property: {e.property}")
+    finally:
+        os.remove(path)
+
+
+def test_ply_line():
+    # ヘッダー 4 行目の未知の型で失敗する
+    path = write_file("test_err_line.ply", ply_header(PROPS, extra=b"element vertex 1\nproperty float128 x"))
+    try:
+        gs_slam_core.SplatManager(path)
+        raise AssertionError("Unknown property type should raise PlyFormatError")
+    except gs_slam_core.PlyFormatError as e:
+        assert e.line == 4, e.line
+        assert "float128" in str(e) and "line 4" in str(e)
+        print(f"✅ PlyFormatError.line: {e.line}")
+    finally:
+        os.remove(path)
+
+
+def test_ply_element_overflow():
+    # vertex より前の要素のレコード数が巨大でもオフセット計算で溢れず PlyFormatError
+    cases = {
+        "stride": b"element face 18446744073709551615\nproperty float a",
+        "list": b"element face 18446744073709551615\nproperty list uchar int vertex_indices",
+    }
+    for name, extra in cases.items():
+        path = write_file(f"test_err_{name}.ply", ply_header(PROPS, extra=extra) + struct.pack("<14f", *range(14)))
+        try:
+            gs_slam_core.SplatManager(path)
+            raise AssertionError(f"Oversized '{name}' element should raise PlyFormatError")
+        except gs_slam_core.PlyFormatError as e:
+            assert "face" in str(e), str(e)
+            print(f"✅ PlyFormatError ({name}): {e}")
+        finally:
+            os.remove(path)
+
+
+def test_ply_vertex_count():
+    # ヘッダの vertex 数が本体に入り切らない場合は確保前に PlyFormatError
+    row = struct.pack("<14f", *range(14))
+    ascii_header = ply_header(PROPS, count=100000000000).replace(b"binary_little_endian", b"ascii")
+    cases = {
+        "binary": ply_header(PROPS, count=100000000000) + row,
+        "ascii": ascii_header + b" ".join(b"%d" % v for v in range(14)) + b"\n",
+        "overflow": ply_header(PROPS, count=18446744073709551615) + row,
+    }
+    for name, data in cases.items():
+        path = write_file(f"test_err_count_{name}.ply", data)
+        try:
+            gs_slam_core.SplatManager(path)
+            raise AssertionError(f"PLY with an oversized vertex count ({name}) should raise PlyFormatError")
+        except gs_slam_core.PlyFormatError as e:
+            assert "PLY" in str(e), str(e)
+            print(f"✅ PlyFormatError ({name}): {e}")
+        finally:
+            os.remove(path)
+
+
+def test_format_errors():
+    # gzip は正しいが中身が SPZ ではない
+    path = write_file("test_err.spz", gzip.compress(b"\x00" * 64))
+    try:
+        gs_slam_core.SplatManager.load_spz(path)
+        raise AssertionError("Corrupt SPZ should raise FormatError")
+    except gs_slam_core.FormatError as e:
+        assert not isinstance(e, gs_slam_core.PlyFormatError)
+        assert "SPZ" in str(e)
+        print(f"✅ FormatError: {e}")
+    finally:
+        os.remove(path)
+
+    path = write_file("test_err.pcd", b"# .PCD v0.7\nFIELDS x y z\nSIZE 4 4\nTYPE F F F\nDATA ascii\n")
+    try:
+        gs_slam_core.read_pcd(path)
+        raise AssertionError("Inconsistent PCD header should raise FormatError")
+    except gs_slam_core.FormatError as e:
+        assert "PCD" in str(e)
+        print(f"✅ FormatError: {e}")
+    finally:
+        os.remove(path)
+
+
+def test_pcd_point_count():
+    # ヘッダの POINTS / WIDTH が本体に入り切らない場合は確保前に FormatError
+    header = b"# .PCD v0.7\nFIELDS x y z\nSIZE 4 4 4\nTYPE F F F\nCOUNT 1 1 1\n"
+    body = struct.pack("<3f", 1.0, 2.0, 3.0)
+    cases = {
+        "binary": header + b"POINTS 100000000000\nDATA binary\n" + body,
+        "ascii": header + b"POINTS 100000000000\nDATA ascii\n1 2 3\n",
+        "overflow": header + b"POINTS 18446744073709551615\nDATA binary\n" + body,
+        "width": header + b"WIDTH 4294967296\nHEIGHT 4294967296\nDATA binary\n" + body,
+        "compressed": header + b"POINTS 300000000\nDATA binary_compressed\n"
+                      + struct.pack("<II", 4, 3600000000) + b"\x00\x00\x00\x00",
+    }
+    for name, data in cases.items():
+        path = write_file(f"test_err_{name}.pcd", data)
+        try:
+            gs_slam_core.read_pcd(path)
+            raise AssertionError(f"PCD with an oversized point count ({name}) should raise FormatError")
+        except gs_slam_core.FormatError as e:
+            assert "PCD" in str(e)
+            print(f"✅ FormatError ({name}): {e}")
+        finally:
+            os.remove(path)
+
+
+def test_io_error():
+    missing = os.path.join(TEMP_DIR, "does_not_exist.ply")
+    try:
+        gs_slam_core.SplatManager(missing)
+        raise AssertionError("Missing file should raise GsIOError")
+    except gs_slam_core.GsIOError as e:
+        assert isinstance(e, OSError)
+        assert e.errno == 2, e.errno
+        print(f"✅ GsIOError (errno {e.errno})")
+
+
+def test_invalid_parameter():
+    m = gs_slam_core.SplatManager.from_arrays([[0, 0, 0]], [[0.1, 0.1, 0.1]], [[0, 0, 0, 1]], [0.5])
+    cases = [
+        lambda: gs_slam_core.SplatManager.from_arrays([[0, 0, 0]], [[1, 1, 1]], [[0, 0, 0, 1]], [1], quat_order="zyxw"),
+        lambda: m.save_ply(os.path.join(TEMP_DIR, "unused.ply")),  # compute_geometry 前
+        lambda: m.save_splat_ply(os.path.join(TEMP_DIR, "unused.ply"), encoding="utf8"),
+        lambda: m.save_spz(os.path.join(TEMP_DIR, "unused.spz"), fractional_bits=23),
+        lambda: m.compute_geometry_cpu(view_dir=[0, 0, 0]),
+        lambda: m.compute_super_resolution(0),
+    ]
+    for case in cases:
+        try:
+            case()
+            raise AssertionError("Expected InvalidParameterError")
+        except gs_slam_core.InvalidParameterError:
+            pass
+    print(f"✅ InvalidParameterError for {len(cases)} bad arguments")
+
+
+def test_gpu_error():
+    # GPU の有無は環境依存。失敗するなら GPU 系の例外であること
+    m = gs_slam_core.SplatManager.from_arrays([[0, 0, 0]], [[0.1, 0.1, 0.01]], [[0, 0, 0, 1]], [0.5])
+    try:
+        assert m.compute_geometry() == 1
+        print("✅ GPU available")
+    except (gs_slam_core.GpuUnavailableError, gs_slam_core.DeviceLostError) as e:
+        print(f"⚠️ No usable GPU ({type(e).__name__}: {e}); CPU path still works")
+        assert m.compute_geometry_cpu() == 1
+
+
+if __name__ == "__main__":
+    os.makedirs(TEMP_DIR, exist_ok=True)
+    test_hierarchy()
+    test_ply_property()
+    test_ply_line()
+    test_ply_element_overflow()
+    test_ply_vertex_count()
+    test_format_errors()
+    test_pcd_point_count()
+    test_io_error()
+    test_invalid_parameter()
+    test_gpu_error()
@@ -8,84 +8,63 @@ import struct
 # =========================================================
 
 
-def parse_ply_ascii(filepath):
+PLY_TYPES = {"float": "f", "uchar": "B", "char": "b", "short": "h", "ushort": "H",
+             "int": "i", "uint": "I", "double": "d"}
+
+
+def read_ply_vertices(filepath):
+    """
+    簡易PLYパーサー (ascii / binary_little_endian, vertex 要素のみ)
+    Returns: (property names, list of dict {name: value})
+    """
+    with open(filepath, "rb") as f:
+        data = f.read()
+
+    end = data.index(b"end_header\n") + len(b"end_header\n")
+    fmt, count, props = None, 0, []
+    for line in data[:end].decode().splitlines():
+        tokens = line.split()
+        if tokens[0] == "format":
+            fmt = tokens[1]
+        elif tokens[0] == "element":
+            count = int(tokens[2])
+        elif tokens[0] == "property":
+            props.append((tokens[2], tokens[1]))
+
+    names = [n for n, _ in props]
+    rows = []
+    if fmt == "ascii":
+        for line in data[end:].decode().splitlines()[:count]:
+            parts = line.split()
+            rows.append({n: (float(v) if t in ("float", "double") else int(v)) for (n, t), v in zip(props, parts)})
+    else:
+        assert fmt == "binary_little_endian", f"Unexpected PLY format {fmt}"
+        record = struct.Struct("<" + "".join(PLY_TYPES[t] for _, t in props))
+        for i in range(count):
+            rows.append(dict(zip(names, record.unpack_from(data, end + i * record.size))))
+    return names, rows
+
+
+def parse_ply(filepath):
     """
-    簡易PLYパーサー (ASCIIのみ対応)
+    Surfel PLY
     Returns: list of dict {'x', 'y', 'z', 'r', 'g', 'b', 'nx', 'ny', 'nz'}
     """
-    points = []
-    with open(filepath, "r") as f:
-        lines = f.readlines()
-
-    header_end = False
-    for line in lines:
-        line = line.strip()
-        if not header_end:
-            if line == "end_header":
-                header_end = True
-            continue
-
-        if not line:
-            continue
-
-        # x, y, z, r, g, b, nx, ny, nz
-        parts = line.split()
-        if len(parts) < 9:
-            continue
-
-        pt = {
-            "x": float(parts[0]),
-            "y": float(parts[1]),
-            "z": float(parts[2]),
-            "r": int(parts[3]),
-            "g": int(parts[4]),
-            "b": int(parts[5]),
-            "nx": float(parts[6]),
-            "ny": float(parts[7]),
-            "nz": float(parts[8]),
-        }
-        points.append(pt)
-    return points
-
-
-def parse_pcd_ascii(filepath):
+    names, rows = read_ply_vertices(filepath)
+    assert names == ["x", "y", "z", "red", "green", "blue", "nx", "ny", "nz"], names
+    return [{"x": r["x"], "y": r["y"], "z": r["z"], "r": r["red"], "g": r["green"], "b": r["blue"],
+             "nx": r["nx"], "ny": r["ny"], "nz": r["nz"]} for r in rows]
+
+
+def parse_pcd(filepath):
     """
-    簡易PCDパーサー (ASCII, DATA ascii以降を読み込む)
+    PCD (in-crate reader, packed rgb 展開済み)
     Returns: list of dict (same structure as above)
     """
-    points = []
-    with open(filepath, "r") as f:
-        lines = f.readlines()
-
-    data_section = False
-    for line in lines:
-        line = line.strip()
-        if not data_section:
-            if line.startswith("DATA ascii"):
-                data_section = True
-            continue
-
-        if not line:
-            continue
-
-        # x, y, z, r, g, b, nx, ny, nz
-        parts = line.split()
-        if len(parts) < 9:
-            continue
-
-        pt = {
-            "x": float(parts[0]),
-            "y": float(parts[1]),
-            "z": float(parts[2]),
-            "r": int(parts[3]),
-            "g": int(parts[4]),
-            "b": int(parts[5]),
-            "nx": float(parts[6]),
-            "ny": float(parts[7]),
-            "nz": float(parts[8]),
-        }
-        points.append(pt)
-    return points
+    cloud = gs_slam_core.read_pcd(filepath)
+    return [{"x": x, "y": y, "z": z, "r": rgb[0], "g": rgb[1], "b": rgb[2], "nx": nx, "ny": ny, "nz": nz}
+            for x, y, z, rgb, nx, ny, nz in zip(cloud["x"], cloud["y"], cloud["z"], cloud["rgb"],
+                                                 cloud["normal_x"], cloud["normal_y"], cloud["normal_z"])]
 
 
 # =========================================================
@@ -123,8 +102,8 @@ def test_export_integrity():
 
     # 4. Verify Content
     print("Parsing exported files for verification...")
-    ply_data = parse_ply_ascii(ply_output)
-    pcd_data = parse_pcd_ascii(pcd_output)
+    ply_data = parse_ply(ply_output)
+    pcd_data = parse_pcd(pcd_output)
 
     # Test A: Count Consistency
     print(
@@ -148,7 +127,7 @@ def test_export_integrity():
         # PLY File
         ply_pt = ply_data[i]
 
-        # Check XYZ (Allow small float error due to ascii conversion)
+        # Check XYZ (binary export keeps float32 exactly)
         assert abs(mem_pos[0] - ply_pt["x"]) < 1e-4
         assert abs(mem_pos[1] - ply_pt["y"]) < 1e-4
         assert abs(mem_pos[2] - ply_pt["z"]) < 1e-4
@@ -185,6 +164,17 @@ def test_export_integrity():
         )
         print(f"✅ Index {i}: Normal vector length is {length:.4f}")
 
+    # Test E: ASCII encoding carries the same data
+    ply_ascii = "data/test_output_ascii.ply"
+    manager.save_ply(ply_ascii, encoding="ascii")
+    ascii_data = parse_ply(ply_ascii)
+    for i in indices_to_check:
+        for key in ["x", "y", "z", "nx", "ny", "nz"]:
+            assert abs(ascii_data[i][key] - ply_data[i][key]) < 1e-5
+        assert (ascii_data[i]["r"], ascii_data[i]["g"], ascii_data[i]["b"]) == (ply_data[i]["r"], ply_data[i]["g"], ply_data[i]["b"])
+    os.remove(ply_ascii)
+    print("✅ ASCII and binary PLY exports agree")
+
     # Cleanup
     if os.path.exists(ply_output):
         os.remove(ply_output)
@@ -193,9 +183,48 @@ def test_export_integrity():
     print("✅ Export Test Passed: Cleaned up files.")
 
 
+def test_splat_roundtrip():
+    ply_input = "data/object_0.ply"
+    splat_output = "data/test_splats.ply"
+
+    print(f"\n--- Testing 3DGS Splat Export ---")
+    manager = gs_slam_core.SplatManager(ply_input)
+    manager.save_splat_ply(splat_output)
+
+    # 学習器と同じプロパティ名で書き出されていること
+    names, rows = read_ply_vertices(splat_output)
+    for p in ["x", "y", "z", "f_dc_0", "f_dc_1", "f_dc_2", "opacity",
+              "scale_0", "scale_1", "scale_2", "rot_0", "rot_1", "rot_2", "rot_3"]:
+        assert p in names, f"Missing property {p}"
+    rest = [n for n in names if n.startswith("f_rest_")]
+    assert len(rest) == 3 * ((manager.sh_degree() + 1) ** 2 - 1)
+
+    # 保存値は学習器の形式 (log scale, logit opacity, wxyz)
+    x, y, z, w = manager.get_splat_rot(0)
+    assert abs(rows[0]["rot_0"] - w) < 1e-6 and abs(rows[0]["rot_1"] - x) < 1e-6
+    assert abs(math.exp(rows[0]["scale_0"]) - manager.get_splat_scale(0)[0]) < 1e-5
+
+    # 再読み込みで元の値に戻ること
+    reloaded = gs_slam_core.SplatManager(splat_output)
+    assert reloaded.count() == manager.count()
+    assert reloaded.sh_degree() == manager.sh_degree()
+    for i in [0, manager.count() // 2, manager.count() - 1]:
+        def close(a, b, tol=1e-5):
+            return all(abs(p - q) < tol for p, q in zip(a, b))
+        assert reloaded.get_splat_pos(i) == manager.get_splat_pos(i)
+        assert close(reloaded.get_splat_rot(i), manager.get_splat_rot(i))
+        assert close(reloaded.get_splat_scale(i), manager.get_splat_scale(i))
+        assert abs(reloaded.get_splat_opacity(i) - manager.get_splat_opacity(i)) < 1e-5
+        assert reloaded.get_splat_sh_rest(i) == manager.get_splat_sh_rest(i)
+    print("✅ Splat PLY round-trips through SplatManager")
+
+    os.remove(splat_output)
+
+
 if __name__ == "__main__":
     try:
         test_export_integrity()
+        test_splat_roundtrip()
     except Exception as e:
         print(f"❌ Test Failed: {e}")
         exit(1)
@@ -15,6 +15,14 @@ try:
     print(f"  Normal[0]: {manager.get_surfel_normal(0)}")
     print(f"  Color[0]:  {manager.get_surfel_color(0)}")
 
+    # 2回目以降は GpuContext (デバイス・パイプライン・バッファ) を再利用する
+    timings = []
+    for _ in range(5):
+        start = time.time()
+        assert manager.compute_geometry() == count
+        timings.append((time.time() - start) * 1000)
+    print(f"✅ Repeated GPU Compute (context reused): {', '.join(f'{t:.2f}' for t in timings)} ms")
+
 except Exception as e:
     print(f"⚠️ GPU Compute Failed (Expected in some WSL2 envs): {e}")
     print("Skipping to CPU verification...")
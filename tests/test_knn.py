@@ -0,0 +1,177 @@
+import gs_slam_core
+import math
+import random
+import time
+
+from splat_helpers import BACKENDS, make_manager
+
+# 近傍探索 (knn / radius_search) を総当たりの結果と比べる。
+# GPU (ハッシュグリッド) と CPU (KD-tree) は同距離をインデックス順に並べるので同じ結果になる
+
+
+def dist(a, b):
+    return math.sqrt(sum((x - y) ** 2 for x, y in zip(a, b)))
+
+
+def brute_force(positions, q, k, radius=None):
+    # (距離, インデックス) 順。float32 に丸めた位置で比べる
+    found = sorted((dist(p, q), i) for i, p in enumerate(positions))
+    if radius is not None:
+        found = [(d, i) for d, i in found if d <= radius]
+    return found[:k]
+
+
+def random_positions(n, seed):
+    rng = random.Random(seed)
+    return [[rng.uniform(-5, 5), rng.uniform(-5, 5), rng.uniform(-1, 1)] for _ in range(n)]
+
+
+def check_rows(indices, distances, positions, queries, k, radius=None, label=""):
+    assert indices.shape == (len(queries), k) and distances.shape == (len(queries), k), f"{label}: shape {indices.shape}"
+    for row, (idx, d, q) in enumerate(zip(indices.tolist(), distances.tolist(), queries)):
+        expected = brute_force(positions, q, k, radius)
+        got = [(dd, i) for dd, i in zip(d, idx) if i >= 0]
+        assert len(got) == len(expected), f"{label}: query {row} found {len(got)}, expected {len(expected)}"
+        for (gd, gi), (ed, ei) in zip(got, expected):
+            # 同距離に近い候補は丸め誤差で入れ替わり得るので距離で比べる
+            assert gi == ei or abs(gd - ed) < 1e-5, f"{label}: query {row} got {gi} ({gd}), expected {ei} ({ed})"
+            assert abs(gd - ed) < 1e-4 * max(1.0, ed)
+        assert all(i == -1 and math.isinf(dd) for i, dd in zip(idx[len(got):], d[len(got):])), f"{label}: bad padding"
+
+
+def test_knn(n=2000, k=8):
+    for backend in BACKENDS:
+        positions = random_positions(n, seed=1)
+        m = make_manager(positions, backend)
+        positions = [m.get_splat_pos(i) for i in range(n)]
+        rng = random.Random(2)
+        # 点群上の点 (自分自身が距離 0 で先頭に来る)、内部の任意の点、点群の外の遠い点
+        queries = positions[:50] + random_positions(50, seed=3) + [[rng.uniform(-50, 50) for _ in range(3)] for _ in range(20)]
+        try:
+            indices, distances = m.knn(queries, k)
+        except (gs_slam_core.GpuUnavailableError, gs_slam_core.DeviceLostError) as e:
+            print(f"⚠️ Skipping knn on {backend}: {e}")
+            continue
+        assert m.last_backend == backend
+        check_rows(indices, distances, positions, queries, k, label=f"knn/{backend}")
+        assert [row[0] for row in indices.tolist()[:50]] == list(range(50)), "A splat is its own nearest neighbor"
+        print(f"✅ knn (k={k}) on {backend} matches brute force for {len(queries)} queries over {n} splats")
+
+        radius = 0.8
+        indices, distances = m.radius_search(queries, radius, max_neighbors=16)
+        check_rows(indices, distances, positions, queries, 16, radius=radius, label=f"radius/{backend}")
+        counts = [sum(i >= 0 for i in row) for row in indices.tolist()]
+        assert max(counts) == 16 and min(counts) == 0, "Synthetic queries should hit both the cap and empty neighborhoods"
+        print(f"✅ radius_search (r={radius}) on {backend}: {min(counts)}-{max(counts)} neighbors per query")
+
+
+def test_planar_and_duplicates():
+    for backend in BACKENDS:
+        # 厚さ 0 の平面 (体積 0 の外接箱) と重複点 (同距離はインデックス順)
+        positions = [[x * 0.05, y * 0.05, 0.0] for x in range(40) for y in range(40)]
+        positions += positions[:100]
+        m = make_manager(positions, backend)
+        positions = [m.get_splat_pos(i) for i in range(len(positions))]
+        queries = [[0.5, 0.5, 0.0], [1.0, 0.3, 0.2], [0.0, 0.0, 0.0], [3.0, 3.0, 3.0]]
+        try:
+            indices, distances = m.knn(queries, 12)
+        except (gs_slam_core.GpuUnavailableError, gs_slam_core.DeviceLostError) as e:
+            print(f"⚠️ Skipping planar knn on {backend}: {e}")
+            continue
+        check_rows(indices, distances, positions, queries, 12, label=f"planar/{backend}")
+        # (0, 0, 0) は 0 番と重複の 1600 番が距離 0
+        assert indices.tolist()[2][:2] == [0, 1600]
+        indices, distances = m.radius_search(queries, 0.12, max_neighbors=64)
+        check_rows(indices, distances, positions, queries, 64, radius=0.12, label=f"planar radius/{backend}")
+        print(f"✅ Planar grid with duplicate points on {backend}")
+
+
+def test_parity(n=5000, k=16):
+    positions = random_positions(n, seed=4)
+    cpu = make_manager(positions, "cpu")
+    gpu = make_manager(positions, "gpu")
+    queries = random_positions(500, seed=5)
+    try:
+        gpu_idx, gpu_dist = gpu.knn(queries, k)
+    except (gs_slam_core.GpuUnavailableError, gs_slam_core.DeviceLostError) as e:
+        print(f"⚠️ Skipping GPU / CPU parity: {e}")
+        return
+    cpu_idx, cpu_dist = cpu.knn(queries, k)
+    assert gpu_idx.tolist() == cpu_idx.tolist()
+    for a, b in zip(gpu_dist.tolist(), cpu_dist.tolist()):
+        assert all(abs(x - y) < 1e-5 for x, y in zip(a, b))
+    print(f"✅ GPU hash grid and CPU KD-tree agree ({len(queries)} queries, k={k})")
+
+
+def test_far_outlier(n=20000, k=8):
+    # 1 点だけ遠く離れていても、グリッドは残りの点の範囲で細かく切る (全点が数セルに集まると O(N^2))
+    positions = random_positions(n, seed=7) + [[1.0e6, -1.0e6, 1.0e6]]
+    cpu = make_manager(positions, "cpu")
+    gpu = make_manager(positions, "gpu")
+    positions = [cpu.get_splat_pos(i) for i in range(len(positions))]
+    try:
+        start = time.perf_counter()
+        gpu_idx, gpu_dist = gpu.knn(positions, k)
+        elapsed = time.perf_counter() - start
+    except (gs_slam_core.GpuUnavailableError, gs_slam_core.DeviceLostError) as e:
+        print(f"⚠️ Skipping far outlier knn: {e}")
+        return
+    cpu_idx, cpu_dist = cpu.knn(positions, k)
+    assert gpu_idx.tolist() == cpu_idx.tolist()
+    for a, b in zip(gpu_dist.tolist(), cpu_dist.tolist()):
+        assert all(abs(x - y) < 1e-5 * max(1.0, y) for x, y in zip(a, b))
+    # 外れ値自身のクエリと、外れ値の近くのクエリ (距離が大きく f32 では同距離が並ぶので CPU と比べる)
+    queries = [positions[-1], [9.9e5, -1.0e6, 1.0e6], [0.0, 0.0, 0.0]]
+    indices, _ = gpu.knn(queries, k)
+    assert indices.tolist() == cpu.knn(queries, k)[0].tolist()
+    assert indices.tolist()[0][0] == n
+    indices, distances = gpu.radius_search(queries, 0.5, max_neighbors=16)
+    check_rows(indices, distances, positions, queries, 16, radius=0.5, label="far outlier radius")
+    print(f"✅ A far outlier does not collapse the grid ({n + 1} queries in {elapsed:.2f}s, matches the CPU KD-tree)")
+
+
+def test_edge_cases():
+    m = make_manager(random_positions(5, seed=6), "cpu")
+    # k が点数より多い分は -1 / inf
+    indices, distances = m.knn([[0.0, 0.0, 0.0]], 8)
+    row = indices.tolist()[0]
+    assert sorted(row[:5]) == list(range(5)) and row[5:] == [-1, -1, -1]
+    assert all(math.isinf(d) for d in distances.tolist()[0][5:])
+    indices, _ = m.knn([], 3)
+    assert indices.shape == (0, 3)
+    print("✅ Rows are padded when k exceeds the point count")
+
+    # Surfel は compute_geometry の後のみ (位置はスプラット中心と同じ)
+    try:
+        m.knn([[0.0, 0.0, 0.0]], 2, source="surfels")
+        raise AssertionError("Surfel search before compute_geometry should fail")
+    except gs_slam_core.InvalidParameterError:
+        pass
+    m.compute_geometry()
+    assert m.knn([[0.0, 0.0, 0.0]], 2, source="surfels")[0].tolist() == m.knn([[0.0, 0.0, 0.0]], 2)[0].tolist()
+    print("✅ source='surfels' searches the surfel positions")
+
+    for call in [lambda: m.knn([[0.0, 0.0, 0.0]], 0),
+                 lambda: m.radius_search([[0.0, 0.0, 0.0]], 0.0),
+                 lambda: m.radius_search([[0.0, 0.0, 0.0]], float("inf")),
+                 lambda: m.radius_search([[0.0, 0.0, 0.0]], 1.0, max_neighbors=0),
+                 lambda: m.knn([[0.0, 0.0, 0.0]], 2, source="voxels")]:
+        try:
+            call()
+            raise AssertionError("Invalid neighbor query should raise InvalidParameterError")
+        except gs_slam_core.InvalidParameterError:
+            pass
+    try:
+        m.knn([0.0, 0.0], 2)
+        raise AssertionError("points must have 3 columns")
+    except ValueError:
+        pass
+    print("✅ Invalid neighbor queries rejected")
+
+
+if __name__ == "__main__":
+    test_knn()
+    test_planar_and_duplicates()
+    test_parity()
+    test_far_outlier()
+    test_edge_cases()
@@ -0,0 +1,231 @@
+import gs_slam_core
+import math
+import random
+
+from splat_helpers import BACKENDS
+
+# compute_geometry の normal_mode / orient / viewpoints と法線の信頼度 (normal_confidence)
+
+
+def random_rotation(rng):
+    q = [rng.gauss(0, 1) for _ in range(4)]
+    n = math.sqrt(sum(v * v for v in q))
+    return [v / n for v in q]
+
+
+def tilted_plane(n, seed, noise=0.005, scale=(0.05, 0.05, 0.05)):
+    # z = 0.3 x - 0.2 y の面上に、向きがばらばらなスプラットを置く
+    rng = random.Random(seed)
+    positions = []
+    for _ in range(n):
+        x, y = rng.uniform(-2, 2), rng.uniform(-2, 2)
+        positions.append([x, y, 0.3 * x - 0.2 * y + rng.gauss(0, noise)])
+    rotations = [random_rotation(rng) for _ in range(n)]
+    m = gs_slam_core.SplatManager.from_arrays(positions, [list(scale)] * n, rotations, [1.0] * n, colors=[[0.5, 0.5, 0.5]] * n)
+    length = math.sqrt(0.3 ** 2 + 0.2 ** 2 + 1.0)
+    return m, [-0.3 / length, 0.2 / length, 1.0 / length]
+
+
+def dot(a, b):
+    return sum(x * y for x, y in zip(a, b))
+
+
+def normals_of(m):
+    return [m.get_surfel_normal(i) for i in range(m.count())]
+
+
+def test_pca():
+    for backend in BACKENDS:
+        m, truth = tilted_plane(3000, seed=1)
+        m.backend = backend
+        try:
+            m.compute_geometry(normal_mode="pca", k=16)
+        except (gs_slam_core.GpuUnavailableError, gs_slam_core.DeviceLostError) as e:
+            print(f"⚠️ Skipping PCA normals on {backend}: {e}")
+            continue
+        assert m.last_backend == backend
+        alignment = [abs(dot(n, truth)) for n in normals_of(m)]
+        assert min(alignment) > 0.95, f"PCA normals should follow the plane: {min(alignment)}"
+        confidence = [m.get_surfel_confidence(i) for i in range(m.count())]
+        assert all(0.0 <= c <= 1.0 for c in confidence)
+        assert sum(confidence) / len(confidence) > 0.9, "A thin noisy plane is confidently planar"
+        print(f"✅ PCA normals on {backend} recover the plane (min |cos| = {min(alignment):.4f})")
+
+        # 等方的なスプラットの軸は当てにならない (信頼度 0)
+        m.compute_geometry()
+        assert all(m.get_surfel_confidence(i) == 0.0 for i in range(m.count()))
+        print("✅ Axis normals of isotropic splats have zero confidence")
+
+
+def test_orientation():
+    for backend in BACKENDS:
+        m, truth = tilted_plane(2000, seed=2)
+        m.backend = backend
+        viewpoint = [0.0, 0.0, 5.0]
+        try:
+            m.compute_geometry(normal_mode="pca", orient="viewpoint", viewpoints=viewpoint)
+        except (gs_slam_core.GpuUnavailableError, gs_slam_core.DeviceLostError) as e:
+            print(f"⚠️ Skipping orientation on {backend}: {e}")
+            continue
+        for i, n in enumerate(normals_of(m)):
+            p = m.get_splat_pos(i)
+            assert dot(n, [v - q for v, q in zip(viewpoint, p)]) > 0, f"Normal {i} faces away from the viewpoint"
+        print(f"✅ orient='viewpoint' on {backend} faces every normal toward the camera")
+
+        # 視点なしの MST は面全体で符号が揃い、最も高い点から上向きに始まる
+        m.compute_geometry(normal_mode="pca", orient="mst")
+        assert all(dot(n, truth) > 0 for n in normals_of(m)), "MST orientation should give one consistent side"
+        # 視点の下から見ると全体が下を向く
+        m.compute_geometry(normal_mode="pca", orient="mst", viewpoints=[[0.0, 0.0, -5.0], [0.0, 0.0, -6.0]])
+        assert all(dot(n, truth) < 0 for n in normals_of(m))
+        print(f"✅ orient='mst' on {backend} propagates a consistent sign")
+
+
+def test_anisotropic_disks():
+    # 薄い円盤は自身の向きを保つ (信頼度 ~1)
+    n = 200
+    rng = random.Random(3)
+    positions = [[rng.uniform(-1, 1), rng.uniform(-1, 1), rng.uniform(-1, 1)] for _ in range(n)]
+    m = gs_slam_core.SplatManager.from_arrays(positions, [[0.1, 0.1, 0.001]] * n, [[0.0, 0.0, 0.0, 1.0]] * n, [1.0] * n)
+    m.backend = "cpu"
+    m.compute_geometry_cpu(normal_mode="pca", k=8)
+    for i, nrm in enumerate(normals_of(m)):
+        assert abs(abs(nrm[2]) - 1.0) < 0.02, f"Disk normal {i} drifted: {nrm}"
+        assert m.get_surfel_confidence(i) > 0.98
+    print("✅ Flat splats keep their own axis under normal_mode='pca'")
+
+
+def test_parity():
+    cpu, _ = tilted_plane(2000, seed=4, noise=0.02)
+    gpu, _ = tilted_plane(2000, seed=4, noise=0.02)
+    gpu.backend = "gpu"
+    try:
+        gpu.compute_geometry(normal_mode="pca", orient="mst")
+    except (gs_slam_core.GpuUnavailableError, gs_slam_core.DeviceLostError) as e:
+        print(f"⚠️ Skipping GPU / CPU parity: {e}")
+        return
+    cpu.compute_geometry_cpu(normal_mode="pca", orient="mst")
+    for a, b in zip(normals_of(cpu), normals_of(gpu)):
+        assert dot(a, b) > 0.999
+    print("✅ GPU and CPU neighborhoods give the same normals")
+
+
+def test_invalid():
+    m, _ = tilted_plane(10, seed=5)
+    for kwargs in [dict(normal_mode="mesh"), dict(orient="up"), dict(normal_mode="pca", k=2),
+                   dict(orient="viewpoint"), dict(orient="viewpoint", viewpoints=[])]:
+        try:
+            m.compute_geometry_cpu(**kwargs)
+            raise AssertionError(f"compute_geometry_cpu({kwargs}) should raise InvalidParameterError")
+        except gs_slam_core.InvalidParameterError:
+            pass
+    try:
+        m.compute_geometry_cpu(orient="viewpoint", viewpoints=[1.0, 2.0])
+        raise AssertionError("viewpoints must have 3 columns")
+    except ValueError:
+        pass
+    try:
+        m.get_surfel_confidence(0)
+        raise AssertionError("No confidence before compute_geometry")
+    except IndexError:
+        pass
+    print("✅ Invalid normal parameters rejected")
+
+    # 点が k より少なくても動く
+    m.compute_geometry_cpu(normal_mode="pca", k=32, orient="mst")
+    assert all(abs(dot(n, n) - 1.0) < 1e-4 for n in normals_of(m))
+    print("✅ Neighborhoods smaller than k are handled")
+
+
+
+def wall(n, seed):
+    # x = 0 の壁。法線 (最も薄い x 軸) の符号はばらばら (z 軸まわり 180 度で -x)
+    rng = random.Random(seed)
+    positions = [[0.0, rng.uniform(-2, 2), rng.uniform(0, 2)] for _ in range(n)]
+    rotations = [[0.0, 0.0, 0.0, 1.0] if rng.random() < 0.5 else [0.0, 0.0, 1.0, 0.0] for _ in range(n)]
+    return gs_slam_core.SplatManager.from_arrays(positions, [[0.001, 0.1, 0.1]] * n, rotations, [1.0] * n)
+
+
+def test_orient_normals():
+    for backend in BACKENDS:
+        m = wall(1000, seed=6)
+        m.backend = backend
+        try:
+            m.compute_geometry()
+        except (gs_slam_core.GpuUnavailableError, gs_slam_core.DeviceLostError) as e:
+            print(f"⚠️ Skipping orient_normals on {backend}: {e}")
+            continue
+        inward = sum(n[0] < 0 for n in normals_of(m))
+        # 部屋の中 (x > 0) を通った軌跡
+        trajectory = [[2.0, y * 0.5, 1.0] for y in range(-4, 5)]
+        assert m.orient_normals(trajectory) == inward
+        assert m.last_backend == backend
+        assert all(n[0] > 0 for n in normals_of(m)), "Every normal should face the trajectory"
+        assert m.orient_normals(trajectory) == 0, "Already oriented normals stay put"
+        print(f"✅ orient_normals on {backend} flips {inward} normals toward the nearest camera")
+
+        # 壁の裏 (x < 0) から観測した Surfel は、近い方ではなく観測したキーフレームを向く
+        cameras = trajectory + [[-2.0, 0.0, 1.0]]
+        behind = len(cameras) - 1
+        observers = [behind if i % 3 == 0 else -1 for i in range(m.count())]
+        m.orient_normals(cameras, observers)
+        for i, n in enumerate(normals_of(m)):
+            assert (n[0] < 0) == (i % 3 == 0), f"Surfel {i} faces the wrong camera"
+        # (N, K): 行の中で最も多いキーフレーム (-1 は埋め草)。全部 -1 なら最も近いカメラ
+        rows = [[behind, 0, behind] if i % 2 else [0, behind, -1] if i % 5 else [-1, -1, -1] for i in range(m.count())]
+        m.orient_normals(cameras, rows)
+        for i, n in enumerate(normals_of(m)):
+            assert (n[0] < 0) == (i % 2 == 1), f"Surfel {i} should face its most frequent observer"
+        print(f"✅ orient_normals on {backend} prefers the most frequent observing keyframe")
+
+
+def test_orient_parity():
+    cpu, gpu = wall(3000, seed=7), wall(3000, seed=7)
+    gpu.backend = "gpu"
+    rng = random.Random(8)
+    cameras = [[rng.uniform(-3, 3), rng.uniform(-3, 3), rng.uniform(0, 2)] for _ in range(50)]
+    observers = [[rng.randrange(-1, 50) for _ in range(4)] for _ in range(3000)]
+    try:
+        gpu.compute_geometry(normal_mode="pca")
+        flipped = gpu.orient_normals(cameras, observers)
+    except (gs_slam_core.GpuUnavailableError, gs_slam_core.DeviceLostError) as e:
+        print(f"⚠️ Skipping orient_normals parity: {e}")
+        return
+    cpu.compute_geometry_cpu(normal_mode="pca")
+    assert cpu.orient_normals(cameras, observers) == flipped
+    assert normals_of(cpu) == normals_of(gpu)
+    print(f"✅ GPU orient_main and the CPU path agree ({flipped} flips)")
+
+
+def test_orient_invalid():
+    m = wall(10, seed=9)
+    try:
+        m.orient_normals([[1.0, 0.0, 0.0]])
+        raise AssertionError("orient_normals before compute_geometry should fail")
+    except gs_slam_core.InvalidParameterError:
+        pass
+    m.compute_geometry_cpu()
+    for cameras, observers in [([], None), ([[1.0, 0.0, 0.0]], [1] * 10)]:
+        try:
+            m.orient_normals(cameras, observers)
+            raise AssertionError("Invalid cameras / observers should raise InvalidParameterError")
+        except gs_slam_core.InvalidParameterError:
+            pass
+    for cameras, observers in [([1.0, 0.0], None), ([[1.0, 0.0, 0.0]], [0] * 9)]:
+        try:
+            m.orient_normals(cameras, observers)
+            raise AssertionError("Mis-shaped cameras / observers should raise ValueError")
+        except ValueError:
+            pass
+    print("✅ Invalid orient_normals arguments rejected")
+
+
+if __name__ == "__main__":
+    test_pca()
+    test_orientation()
+    test_anisotropic_disks()
+    test_parity()
+    test_invalid()
+    test_orient_normals()
+    test_orient_parity()
+    test_orient_invalid()
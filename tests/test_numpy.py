@@ -0,0 +1,100 @@
+import gs_slam_core
+import numpy as np
+import os
+
+from splat_helpers import write_ply
+
+TEMP_PLY = "data/test_numpy.ply"
+
+
+def make_rows(n, seed=0):
+    rng = np.random.default_rng(seed)
+    rows = []
+    for _ in range(n):
+        q = rng.normal(size=4)
+        q /= np.linalg.norm(q)
+        rows.append([*rng.uniform(-5, 5, 3), *rng.uniform(-1, 1, 3), rng.uniform(-2, 2),
+                     *rng.permutation([-1.0, -2.5, -4.0]), *q])
+    return rows
+
+
+def test_views_match_getters():
+    write_ply(TEMP_PLY, make_rows(100))
+    try:
+        m = gs_slam_core.SplatManager(TEMP_PLY)
+        pos = m.positions
+        assert pos.shape == (100, 3) and pos.dtype == np.float32
+        assert pos.strides == (64, 4), f"Splat views must stride over the struct: {pos.strides}"
+        assert not pos.flags.writeable
+        assert m.rotations.shape == (100, 4)
+        assert m.opacities.shape == (100,)
+        for i in range(m.count()):
+            assert list(pos[i]) == m.get_splat_pos(i)
+            assert list(m.scales[i]) == m.get_splat_scale(i)
+            assert list(m.rotations[i]) == m.get_splat_rot(i)
+            assert m.opacities[i] == np.float32(m.get_splat_opacity(i))
+        print("✅ Splat views match per-index getters")
+
+        m.compute_geometry_cpu()
+        normals = m.normals
+        assert normals.shape == (100, 3) and normals.strides == (48, 4)
+        for i in range(m.count()):
+            assert list(normals[i]) == m.get_surfel_normal(i)
+            assert list(m.colors[i]) == m.get_surfel_color(i)
+            assert m.normal_confidence[i] == np.float32(m.get_surfel_confidence(i))
+        assert m.normal_confidence.shape == (100,)
+        assert np.array_equal(m.surfel_positions, pos)
+        print("✅ Surfel views match per-index getters")
+
+        # ビューが残っている間は再計算できない
+        try:
+            m.compute_geometry_cpu()
+            raise AssertionError("Recomputing with a live view should raise BufferError")
+        except BufferError:
+            print("✅ Live views block reallocation")
+        copied = np.array(normals)
+        del normals
+        m.compute_geometry_cpu()
+        assert np.array_equal(copied, m.normals)
+        print("✅ Recompute allowed once views are released")
+    finally:
+        os.remove(TEMP_PLY)
+
+
+def test_from_arrays():
+    write_ply(TEMP_PLY, make_rows(50, seed=1))
+    try:
+        m = gs_slam_core.SplatManager(TEMP_PLY)
+        clone = gs_slam_core.SplatManager.from_arrays(m.positions, m.scales, m.rotations, m.opacities, sh_dc=m.sh_dc)
+        assert clone.count() == m.count()
+        for name in ["positions", "scales", "rotations", "opacities", "sh_dc"]:
+            assert np.array_equal(getattr(clone, name), getattr(m, name)), f"{name} not round-tripped"
+        print("✅ from_arrays round-trips the views")
+
+        # 学習器の生の値 (wxyz, log / logit) からも生成できる
+        rows = np.array(make_rows(50, seed=1), dtype=np.float64)
+        raw = gs_slam_core.SplatManager.from_arrays(rows[:, 0:3], rows[:, 7:10], rows[:, 10:14], rows[:, 6],
+                                                    sh_dc=rows[:, 3:6], quat_order="wxyz", value_domain="preactivation")
+        assert np.allclose(raw.scales, m.scales) and np.allclose(raw.rotations, m.rotations)
+        assert np.allclose(raw.opacities, m.opacities)
+        print("✅ from_arrays accepts raw trainer values")
+
+        rgb = np.full((2, 3), 0.5, dtype=np.float32)
+        grey = gs_slam_core.SplatManager.from_arrays(np.zeros((2, 3)), np.ones((2, 3)), [[0, 0, 0, 1]] * 2, [1, 1], colors=rgb)
+        grey.compute_geometry_cpu()
+        assert np.allclose(grey.colors, 0.5, atol=1e-6)
+        print("✅ from_arrays colors decode back to the same RGB")
+
+        try:
+            gs_slam_core.SplatManager.from_arrays(np.zeros((3, 3)), np.ones((2, 3)), np.zeros((3, 4)), np.ones(3))
+            raise AssertionError("Mismatched shapes should raise ValueError")
+        except ValueError:
+            print("✅ Mismatched shapes rejected")
+    finally:
+        os.remove(TEMP_PLY)
+
+
+if __name__ == "__main__":
+    os.makedirs("data", exist_ok=True)
+    test_views_match_getters()
+    test_from_arrays()
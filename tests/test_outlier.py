@@ -0,0 +1,173 @@
+import gs_slam_core
+import math
+import random
+
+from splat_helpers import BACKENDS, make_manager
+
+# 外れ値除去 (statistical_outlier_mask / radius_outlier_mask / filter_splats) を総当たりの PCL 相当の判定と比べる
+
+
+def scene(seed):
+    # 揺らした平面の格子 (表面) と、離れた位置に浮かぶ少数の floater
+    rng = random.Random(seed)
+    surface = [[x * 0.1 + rng.uniform(-0.02, 0.02), y * 0.1 + rng.uniform(-0.02, 0.02), rng.uniform(-0.01, 0.01)]
+               for x in range(20) for y in range(20)]
+    floaters = [[rng.uniform(-3, 5), rng.uniform(-3, 5), rng.uniform(1, 3)] for _ in range(8)]
+    positions = surface + floaters
+    order = list(range(len(positions)))
+    rng.shuffle(order)
+    return [positions[i] for i in order], {order.index(len(surface) + f) for f in range(len(floaters))}
+
+
+def dist(a, b):
+    return math.sqrt(sum((x - y) ** 2 for x, y in zip(a, b)))
+
+
+def statistical_reference(positions, k, std_ratio):
+    # 自身を除く k 近傍までの平均距離 → 平均 + std_ratio * 標準偏差 (不偏分散) 以下を残す。(平均距離, 閾値) も返す
+    means = [sum(sorted(dist(p, q) for j, q in enumerate(positions) if j != i)[:k]) / k for i, p in enumerate(positions)]
+    mu = sum(means) / len(means)
+    sigma = math.sqrt(sum((d - mu) ** 2 for d in means) / (len(means) - 1))
+    threshold = mu + std_ratio * sigma
+    return [d <= threshold for d in means], means, threshold
+
+
+def radius_reference(positions, radius, min_neighbors):
+    return [sum(1 for j, q in enumerate(positions) if j != i and dist(p, q) <= radius) >= min_neighbors
+            for i, p in enumerate(positions)]
+
+
+def test_statistical():
+    for backend in BACKENDS:
+        positions, floaters = scene(1)
+        m = make_manager(positions, backend)
+        positions = [m.get_splat_pos(i) for i in range(m.count())]
+        try:
+            mask = m.statistical_outlier_mask(k=8, std_ratio=1.0)
+        except (gs_slam_core.GpuUnavailableError, gs_slam_core.DeviceLostError) as e:
+            print(f"⚠️ Skipping statistical outlier removal on {backend}: {e}")
+            continue
+        assert m.last_backend == backend
+        assert mask.shape == (len(positions),)
+        keep = mask.tolist()
+        want, means, threshold = statistical_reference(positions, 8, 1.0)
+        for i, (got, expected) in enumerate(zip(keep, want)):
+            # 閾値ちょうどの点は float32 の丸めで判定が分かれ得る
+            if abs(means[i] - threshold) > 1e-5:
+                assert got == expected, f"Splat {i}: keep={got}, mean distance {means[i]} vs threshold {threshold}"
+        assert all(not keep[i] for i in floaters), "Floaters are removed"
+        assert sum(keep) >= 400 - 8, "Almost every surface splat is kept"
+        print(f"✅ statistical_outlier_mask on {backend}: kept {sum(keep)} of {len(keep)} splats, all floaters removed")
+
+
+def test_radius():
+    for backend in BACKENDS:
+        positions, floaters = scene(2)
+        m = make_manager(positions, backend)
+        positions = [m.get_splat_pos(i) for i in range(m.count())]
+        try:
+            keep = m.radius_outlier_mask(0.15, 4).tolist()
+        except (gs_slam_core.GpuUnavailableError, gs_slam_core.DeviceLostError) as e:
+            print(f"⚠️ Skipping radius outlier removal on {backend}: {e}")
+            continue
+        assert keep == radius_reference(positions, 0.15, 4)
+        assert all(not keep[i] for i in floaters)
+        # 近傍が 0 個でよければ全て残る
+        assert all(m.radius_outlier_mask(0.15, 0).tolist())
+        print(f"✅ radius_outlier_mask on {backend}: kept {sum(keep)} of {len(keep)} splats with >= 4 neighbors within 0.15")
+
+
+def test_duplicates():
+    # 同じ位置の点は互いに距離 0 の近傍 (自身が k 近傍の行から押し出されても正しく除く)
+    positions = [[0.0, 0.0, 0.0]] * 12 + [[0.1 * i, 0.0, 0.0] for i in range(1, 11)] + [[10.0, 10.0, 10.0]]
+    m = make_manager(positions, "cpu")
+    keep = m.statistical_outlier_mask(k=4, std_ratio=1.0).tolist()
+    assert all(keep[:12]) and not keep[-1]
+    keep = m.radius_outlier_mask(0.01, 11).tolist()
+    assert keep == [True] * 12 + [False] * 11
+    print("✅ Coincident splats count as each other's neighbors")
+
+
+def test_filter():
+    positions, floaters = scene(3)
+    n = len(positions)
+    sh_rest = [[[0.001 * i, 0.0, 0.0]] * 3 for i in range(n)]
+    m = make_manager(positions, "cpu", sh_rest=sh_rest)
+    before = [m.get_splat_pos(i) for i in range(n)]
+    m.compute_geometry()
+
+    mask = m.statistical_outlier_mask(k=8, std_ratio=1.0)
+    keep = mask.tolist()
+    # 表示用のビューが残っていると変更できない
+    view = m.positions
+    try:
+        m.filter_splats(mask)
+        raise AssertionError("filter_splats should fail while a NumPy view is alive")
+    except BufferError:
+        pass
+    del view
+
+    removed = m.filter_splats(mask)
+    kept = [i for i in range(n) if keep[i]]
+    assert removed == n - len(kept) and m.count() == len(kept)
+    for new, old in enumerate(kept):
+        assert m.get_splat_pos(new) == before[old], "Kept splats keep their order"
+        assert abs(m.get_splat_sh_rest(new)[0] - 0.001 * old) < 1e-7, "SH rest follows its splat"
+    assert not any(i in floaters for i in kept)
+    # Surfel はスプラットと対応しなくなるので破棄され、再計算が必要
+    try:
+        m.get_surfel_normal(0)
+        raise AssertionError("Surfels are dropped by filter_splats")
+    except IndexError:
+        pass
+    assert m.compute_geometry() == m.count()
+    # リストも受け付ける (すべて残す)
+    assert m.filter_splats([True] * m.count()) == 0
+    print(f"✅ filter_splats removed {removed} splats in place (order, SH rest kept; surfels dropped)")
+
+
+def test_parity():
+    positions = [[p[0] * 3.0, p[1] * 3.0, p[2]] for p in scene(4)[0]]
+    cpu, gpu = make_manager(positions, "cpu"), make_manager(positions, "gpu")
+    try:
+        b = gpu.statistical_outlier_mask(k=12, std_ratio=0.5).tolist()
+    except (gs_slam_core.GpuUnavailableError, gs_slam_core.DeviceLostError) as e:
+        print(f"⚠️ Skipping GPU / CPU parity: {e}")
+        return
+    assert cpu.statistical_outlier_mask(k=12, std_ratio=0.5).tolist() == b
+    assert cpu.radius_outlier_mask(0.35, 6).tolist() == gpu.radius_outlier_mask(0.35, 6).tolist()
+    print("✅ GPU hash grid and CPU KD-tree give the same masks")
+
+
+def test_invalid():
+    m = make_manager(scene(5)[0], "cpu")
+    for call in [lambda: m.statistical_outlier_mask(k=0), lambda: m.statistical_outlier_mask(std_ratio=-1.0),
+                 lambda: m.statistical_outlier_mask(std_ratio=float("nan")), lambda: m.radius_outlier_mask(0.0, 3),
+                 lambda: m.radius_outlier_mask(float("inf"), 3)]:
+        try:
+            call()
+            raise AssertionError("Invalid outlier parameters should raise InvalidParameterError")
+        except gs_slam_core.InvalidParameterError:
+            pass
+    try:
+        m.filter_splats([True] * (m.count() - 1))
+        raise AssertionError("A mask of the wrong length should raise ValueError")
+    except ValueError:
+        pass
+
+    empty = gs_slam_core.SplatManager.from_arrays([], [], [], [])
+    assert empty.statistical_outlier_mask().shape == (0,) and empty.radius_outlier_mask(1.0, 2).shape == (0,)
+    assert empty.filter_splats([]) == 0
+    single = make_manager([[0.0, 0.0, 0.0]], "cpu")
+    assert single.statistical_outlier_mask().tolist() == [True], "A splat without neighbors is not judged"
+    assert single.radius_outlier_mask(1.0, 1).tolist() == [False]
+    print("✅ Invalid parameters / masks rejected, empty and single-splat inputs handled")
+
+
+if __name__ == "__main__":
+    test_statistical()
+    test_radius()
+    test_duplicates()
+    test_filter()
+    test_parity()
+    test_invalid()
@@ -0,0 +1,98 @@
+import gs_slam_core
+import math
+import os
+import random
+
+from splat_helpers import write_ply
+
+TEMP_WXYZ = "data/test_parity_wxyz.ply"
+TEMP_XYZW = "data/test_parity_xyzw.ply"
+TEMP_GEOMETRY = "data/test_parity_geometry.ply"
+
+
+def random_quat_xyzw(rng):
+    q = [rng.gauss(0.0, 1.0) for _ in range(4)]
+    n = math.sqrt(sum(c * c for c in q))
+    return [c / n for c in q]
+
+
+def make_splats(n, seed=0):
+    rng = random.Random(seed)
+    splats = []
+    for _ in range(n):
+        # 最小軸が明確になるよう、スケールは十分に離す
+        scales = rng.sample([-1.0, -2.5, -4.0], 3)
+        splats.append({
+            "pos": [rng.uniform(-5, 5) for _ in range(3)],
+            "dc": [rng.uniform(-1, 1) for _ in range(3)],
+            "opacity": rng.uniform(-2, 2),
+            "scale": scales,
+            "rot": random_quat_xyzw(rng),
+        })
+    return splats
+
+
+def write_splats(path, splats, order):
+    rows = []
+    for s in splats:
+        x, y, z, w = s["rot"]
+        rot = [w, x, y, z] if order == "wxyz" else [x, y, z, w]
+        rows.append([*s["pos"], *s["dc"], s["opacity"], *s["scale"], *rot])
+    write_ply(path, rows)
+
+
+def close(a, b, tol):
+    return all(abs(x - y) < tol for x, y in zip(a, b))
+
+
+def test_quat_order_convention():
+    splats = make_splats(256)
+    write_splats(TEMP_WXYZ, splats, "wxyz")
+    write_splats(TEMP_XYZW, splats, "xyzw")
+    try:
+        m_wxyz = gs_slam_core.SplatManager(TEMP_WXYZ, quat_order="wxyz")
+        m_xyzw = gs_slam_core.SplatManager(TEMP_XYZW, quat_order="xyzw")
+        for i in range(len(splats)):
+            assert close(m_wxyz.get_splat_rot(i), splats[i]["rot"], 1e-6), "Internal rot must be (x, y, z, w)"
+            assert m_wxyz.get_splat_rot(i) == m_xyzw.get_splat_rot(i)
+        print("✅ Both quaternion orders normalize to the same internal convention")
+
+        try:
+            gs_slam_core.SplatManager(TEMP_WXYZ, quat_order="zyxw")
+            raise AssertionError("Unknown quat_order should raise ValueError")
+        except ValueError:
+            print("✅ Unknown quat_order rejected")
+    finally:
+        os.remove(TEMP_WXYZ)
+        os.remove(TEMP_XYZW)
+
+
+def test_cpu_gpu_parity():
+    # 他のテストの出力に依存しないよう入力は自分で書く
+    write_splats(TEMP_GEOMETRY, make_splats(256), "wxyz")
+    try:
+        manager = gs_slam_core.SplatManager(TEMP_GEOMETRY)
+        manager.compute_geometry_cpu()
+        cpu = [(manager.get_surfel_normal(i), manager.get_surfel_color(i)) for i in range(manager.count())]
+
+        # auto だと GPU が無い時に CPU で計算されてしまうので明示する
+        manager.backend = "gpu"
+        try:
+            manager.compute_geometry()
+        except Exception as e:
+            print(f"⚠️ GPU Compute Failed (skipping parity check): {e}")
+            return
+
+        for i in range(manager.count()):
+            n_cpu, c_cpu = cpu[i]
+            assert close(manager.get_surfel_normal(i), n_cpu, 1e-4), f"Normal mismatch at {i}"
+            assert close(manager.get_surfel_color(i), c_cpu, 1e-4), f"Color mismatch at {i}"
+        print(f"✅ compute_geometry and compute_geometry_cpu agree on {manager.count()} splats")
+    finally:
+        os.remove(TEMP_GEOMETRY)
+
+
+if __name__ == "__main__":
+    os.makedirs("data", exist_ok=True)
+    test_quat_order_convention()
+    test_cpu_gpu_parity()
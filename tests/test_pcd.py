@@ -0,0 +1,86 @@
+import gs_slam_core
+import os
+import struct
+
+from splat_helpers import write_ply
+
+TEMP_PLY = "data/test_pcd_input.ply"
+TEMP_PCD = "data/test_pcd_output.pcd"
+
+
+def make_rows(n):
+    # 繰り返しの多いデータ (LZF が効くように) と一意な座標
+    return [[i * 0.01, (i % 10) * 0.5, 1.0, (i % 3) - 1.0, 0.5, -0.5, 0.4, -1.0, -2.0, -4.0, 1.0, 0.0, 0.0, 0.0] for i in range(n)]
+
+
+def read_header(path):
+    with open(path, "rb") as f:
+        data = f.read()
+    end = data.index(b"DATA")
+    end = data.index(b"\n", end) + 1
+    header = {}
+    for line in data[:end].decode().splitlines():
+        if line.startswith("#"):
+            continue
+        key, *values = line.split()
+        header[key] = values
+    return header, data[end:]
+
+
+def test_pcd_encodings():
+    write_ply(TEMP_PLY, make_rows(500))
+    try:
+        m = gs_slam_core.SplatManager(TEMP_PLY)
+        m.compute_geometry_cpu()
+        expected_rgb = [tuple(int(c * 255.0) for c in m.get_surfel_color(i)) for i in range(m.count())]
+
+        sizes = {}
+        for encoding in ["ascii", "binary", "binary_compressed"]:
+            m.save_pcd(TEMP_PCD, encoding=encoding, extra_fields=["opacity", "curvature", "radius"])
+            sizes[encoding] = os.path.getsize(TEMP_PCD)
+
+            header, body = read_header(TEMP_PCD)
+            assert header["DATA"] == [encoding]
+            assert header["FIELDS"] == ["x", "y", "z", "rgb", "normal_x", "normal_y", "normal_z",
+                                        "opacity", "curvature", "radius"]
+            assert header["TYPE"][3] == "F" and header["SIZE"][3] == "4", "rgb must be a packed 4-byte float"
+            assert header["POINTS"] == [str(m.count())]
+
+            cloud = gs_slam_core.read_pcd(TEMP_PCD)
+            assert len(cloud["x"]) == m.count()
+            for i in range(m.count()):
+                assert cloud["x"][i] == m.get_splat_pos(i)[0]
+                assert tuple(cloud["rgb"][i]) == expected_rgb[i], f"rgb mismatch at {i} ({encoding})"
+                n = m.get_surfel_normal(i)
+                assert abs(cloud["normal_z"][i] - n[2]) < 1e-6
+                assert abs(cloud["opacity"][i] - m.get_splat_opacity(i)) < 1e-6
+                assert abs(cloud["radius"][i] - max(m.get_splat_scale(i))) < 1e-6
+                assert 0.0 <= cloud["curvature"][i] <= 1.0 / 3.0 + 1e-6
+            print(f"✅ {encoding}: {m.count()} points read back with packed rgb and extra fields")
+
+        # binary の packed rgb は PCL と同じ 0x00RRGGBB
+        m.save_pcd(TEMP_PCD, encoding="binary")
+        header, body = read_header(TEMP_PCD)
+        assert len(body) == m.count() * 7 * 4
+        packed = struct.unpack_from("<I", body, 12)[0]
+        r, g, b = expected_rgb[0]
+        assert packed == (r << 16) | (g << 8) | b
+        print("✅ Binary PCD stores the PCL packed rgb convention")
+
+        assert sizes["binary_compressed"] < sizes["binary"], sizes
+        print(f"✅ binary_compressed is smaller than binary ({sizes['binary_compressed']} < {sizes['binary']} bytes)")
+
+        try:
+            m.save_pcd(TEMP_PCD, extra_fields=["intensity"])
+            raise AssertionError("Unknown extra field should raise ValueError")
+        except ValueError:
+            print("✅ Unknown extra field rejected")
+    finally:
+        os.remove(TEMP_PLY)
+        if os.path.exists(TEMP_PCD):
+            os.remove(TEMP_PCD)
+
+
+if __name__ == "__main__":
+    os.makedirs("data", exist_ok=True)
+    test_pcd_encodings()
@@ -0,0 +1,77 @@
+import gs_slam_core
+import os
+import struct
+
+TEMP_DIR = "data"
+
+# 学習器の出力とは異なる順序・余分なプロパティを含むヘッダー
+SHUFFLED_PROPS = [
+    "rot_0", "rot_1", "rot_2", "rot_3",
+    "x", "y", "z",
+    "f_rest_0", "f_rest_1",
+    "opacity",
+    "scale_0", "scale_1", "scale_2",
+    "f_dc_0", "f_dc_1", "f_dc_2",
+]
+
+
+def write_ply(path, props, rows):
+    with open(path, "wb") as f:
+        f.write(b"ply\n")
+        f.write(b"format binary_little_endian 1.0\n")
+        f.write(b"comment synthetic test data\n")
+        f.write(f"element vertex {len(rows)}\n".encode())
+        for p in props:
+            f.write(f"property float {p}\n".encode())
+        f.write(b"end_header\n")
+        for row in rows:
+            f.write(struct.pack(f"<{len(props)}f", *[row[p] for p in props]))
+
+
+def make_row(i):
+    return {
+        "x": 1.0 + i, "y": 2.0 + i, "z": 3.0 + i,
+        "f_dc_0": 0.1, "f_dc_1": 0.2, "f_dc_2": 0.3,
+        "f_rest_0": 9.0, "f_rest_1": 9.0,
+        "opacity": 0.5,
+        "scale_0": -1.0, "scale_1": -2.0, "scale_2": -3.0,
+        "rot_0": 1.0, "rot_1": 0.0, "rot_2": 0.0, "rot_3": 0.0,
+    }
+
+
+def test_shuffled_properties():
+    path = os.path.join(TEMP_DIR, "test_shuffled.ply")
+    write_ply(path, SHUFFLED_PROPS, [make_row(i) for i in range(3)])
+    try:
+        manager = gs_slam_core.SplatManager(path)
+        assert manager.count() == 3, f"Expected 3 splats, got {manager.count()}"
+        for i in range(3):
+            pos = manager.get_splat_pos(i)
+            assert pos == [1.0 + i, 2.0 + i, 3.0 + i], f"Position mismatch at {i}: {pos}"
+        sh = manager.get_splat_sh(0)
+        assert all(abs(a - b) < 1e-6 for a, b in zip(sh, [0.1, 0.2, 0.3])), f"SH mismatch: {sh}"
+        print("✅ Properties are mapped by name regardless of order")
+    finally:
+        os.remove(path)
+
+
+def test_missing_properties():
+    path = os.path.join(TEMP_DIR, "test_missing.ply")
+    props = [p for p in SHUFFLED_PROPS if p not in ("opacity", "rot_3")]
+    write_ply(path, props, [make_row(0)])
+    try:
+        gs_slam_core.SplatManager(path)
+        raise AssertionError("Loading a PLY without opacity/rot_3 should fail")
+    except ValueError as e:
+        msg = str(e)
+        assert "opacity" in msg and "rot_3" in msg, f"Error should list missing properties: {msg}"
+        assert "f_rest_0" in msg, f"Error should list found properties: {msg}"
+        print(f"✅ Missing properties rejected: {msg}")
+    finally:
+        os.remove(path)
+
+
+if __name__ == "__main__":
+    os.makedirs(TEMP_DIR, exist_ok=True)
+    test_shuffled_properties()
+    test_missing_properties()
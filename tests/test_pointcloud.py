@@ -0,0 +1,126 @@
+import gs_slam_core
+import math
+import os
+import struct
+
+TEMP_PLY = "data/test_pointcloud.ply"
+TEMP_PCD = "data/test_pointcloud.pcd"
+
+
+def make_points(n):
+    # z = 0 の平面上の点 (法線 +Z) と、斜めの法線を持つ点を混ぜる
+    points = []
+    for i in range(n):
+        x, y = (i % 10) * 0.1, (i // 10) * 0.1
+        normal = [0.0, 0.0, 1.0] if i % 2 == 0 else [0.0, math.sqrt(0.5), math.sqrt(0.5)]
+        rgb = [(i * 7) % 256, (i * 13) % 256, (i * 29) % 256]
+        points.append({"pos": [x, y, 0.0], "rgb": rgb, "normal": normal})
+    return points
+
+
+def write_ascii_ply(path, points):
+    with open(path, "w") as f:
+        f.write("ply\nformat ascii 1.0\n")
+        f.write(f"element vertex {len(points)}\n")
+        for p in ["x", "y", "z"]:
+            f.write(f"property float {p}\n")
+        for p in ["red", "green", "blue"]:
+            f.write(f"property uchar {p}\n")
+        for p in ["nx", "ny", "nz"]:
+            f.write(f"property float {p}\n")
+        f.write("end_header\n")
+        for p in points:
+            f.write(" ".join(str(v) for v in p["pos"] + p["rgb"] + p["normal"]) + "\n")
+
+
+def write_binary_pcd(path, points):
+    with open(path, "wb") as f:
+        f.write(b"# .PCD v0.7\nVERSION 0.7\nFIELDS x y z rgb normal_x normal_y normal_z\n")
+        f.write(b"SIZE 4 4 4 4 4 4 4\nTYPE F F F F F F F\nCOUNT 1 1 1 1 1 1 1\n")
+        f.write(f"WIDTH {len(points)}\nHEIGHT 1\nVIEWPOINT 0 0 0 1 0 0 0\nPOINTS {len(points)}\nDATA binary\n".encode())
+        for p in points:
+            r, g, b = p["rgb"]
+            f.write(struct.pack("<3fI3f", *p["pos"], (r << 16) | (g << 8) | b, *p["normal"]))
+
+
+def close(a, b, tol=1e-4):
+    return all(abs(x - y) < tol for x, y in zip(a, b))
+
+
+def check_manager(m, points, label):
+    assert m.count() == len(points)
+    m.compute_geometry_cpu()
+    for i, p in enumerate(points):
+        assert close(m.get_splat_pos(i), p["pos"]), f"{label}: position mismatch at {i}"
+        # 0-255 → 0-1 → SH DC → RGB で往復すること
+        assert close(m.get_surfel_color(i), [c / 255.0 for c in p["rgb"]], 1e-5), f"{label}: color mismatch at {i}"
+        # flat スプラットの法線は入力法線と一致
+        assert close(m.get_surfel_normal(i), p["normal"]), f"{label}: normal mismatch at {i}"
+    print(f"✅ {label}: positions, colors and normals preserved")
+
+
+def test_point_cloud_import():
+    points = make_points(100)
+    write_ascii_ply(TEMP_PLY, points)
+    write_binary_pcd(TEMP_PCD, points)
+    try:
+        check_manager(gs_slam_core.SplatManager.from_point_cloud(TEMP_PLY), points, "ASCII PLY")
+        check_manager(gs_slam_core.SplatManager.from_point_cloud(TEMP_PCD), points, "binary PCD")
+
+        # 半径指定: flat は法線方向に薄く、isotropic は全軸同じ
+        m = gs_slam_core.SplatManager.from_point_cloud(TEMP_PCD, shape="flat", radius=0.05)
+        scale = sorted(m.get_splat_scale(0))
+        assert close(scale[1:], [0.05, 0.05], 1e-7) and scale[0] < 0.05 * 0.1
+        m = gs_slam_core.SplatManager.from_point_cloud(TEMP_PCD, shape="isotropic", radius=0.05)
+        assert close(m.get_splat_scale(0), [0.05] * 3, 1e-7)
+        print("✅ flat / isotropic splat shapes")
+
+        # 半径の自動推定: 1.0 x 1.0 の平面に 100 点 → 間隔 ~0.09
+        m = gs_slam_core.SplatManager.from_point_cloud(TEMP_PLY)
+        assert 0.05 < max(m.get_splat_scale(0)) < 0.15, m.get_splat_scale(0)
+        print("✅ Radius estimated from point spacing")
+
+        # 法線のない点群は flat にできない
+        with open(TEMP_PLY, "w") as f:
+            f.write("ply\nformat ascii 1.0\nelement vertex 1\nproperty float x\nproperty float y\nproperty float z\nend_header\n0 0 0\n")
+        try:
+            gs_slam_core.SplatManager.from_point_cloud(TEMP_PLY, shape="flat")
+            raise AssertionError("shape='flat' without normals should raise ValueError")
+        except ValueError:
+            print("✅ flat shape without normals rejected")
+        assert gs_slam_core.SplatManager.from_point_cloud(TEMP_PLY).count() == 1
+    finally:
+        os.remove(TEMP_PLY)
+        os.remove(TEMP_PCD)
+
+
+def test_export_reimport():
+    # save_pcd / save_ply の出力をそのまま読み戻す
+    points = make_points(50)
+    write_ascii_ply(TEMP_PLY, points)
+    try:
+        m = gs_slam_core.SplatManager.from_point_cloud(TEMP_PLY)
+        m.compute_geometry_cpu()
+        for path, save in [(TEMP_PCD, m.save_pcd), (TEMP_PLY, m.save_ply)]:
+            save(path)
+            again = gs_slam_core.SplatManager.from_point_cloud(path)
+            again.compute_geometry_cpu()
+            for i in range(m.count()):
+                assert again.get_splat_pos(i) == m.get_splat_pos(i)
+                assert close(again.get_surfel_normal(i), m.get_surfel_normal(i))
+            print(f"✅ {os.path.splitext(path)[1]} export re-imported")
+
+        # ASCII の 3DGS PLY も SplatManager で読める
+        m.save_splat_ply(TEMP_PLY, encoding="ascii")
+        again = gs_slam_core.SplatManager(TEMP_PLY)
+        assert close(again.get_splat_scale(0), m.get_splat_scale(0), 1e-5)
+        print("✅ ASCII 3DGS PLY loaded")
+    finally:
+        os.remove(TEMP_PLY)
+        os.remove(TEMP_PCD)
+
+
+if __name__ == "__main__":
+    os.makedirs("data", exist_ok=True)
+    test_point_cloud_import()
+    test_export_reimport()
@@ -0,0 +1,102 @@
+import gs_slam_core
+import math
+import os
+import struct
+
+TEMP_PLY = "data/test_sh.ply"
+
+C0 = 0.2820947917
+C1 = 0.4886025119
+
+BASE_PROPS = ["x", "y", "z", "f_dc_0", "f_dc_1", "f_dc_2", "opacity",
+              "scale_0", "scale_1", "scale_2", "rot_0", "rot_1", "rot_2", "rot_3"]
+# degree 1: チャンネルごとに 3 係数 (f_rest_0..8, R→G→B の順)
+REST_PROPS = [f"f_rest_{i}" for i in range(9)]
+
+
+def write_degree1_ply(path, splats, rest_props=REST_PROPS):
+    props = BASE_PROPS + rest_props
+    with open(path, "wb") as f:
+        f.write(b"ply\nformat binary_little_endian 1.0\n")
+        f.write(f"element vertex {len(splats)}\n".encode())
+        for p in props:
+            f.write(f"property float {p}\n".encode())
+        f.write(b"end_header\n")
+        for s in splats:
+            f.write(struct.pack(f"<{len(props)}f", *s["base"], *s["rest"]))
+
+
+def reference_color(dc, rest, d):
+    # rest は PLY の並び (チャンネル優先)
+    x, y, z = d
+    basis = [-C1 * y, C1 * z, -C1 * x]
+    rgb = []
+    for c in range(3):
+        v = C0 * dc[c] + sum(b * rest[c * 3 + k] for k, b in enumerate(basis)) + 0.5
+        rgb.append(max(0.0, min(1.0, v)))
+    return rgb
+
+
+def test_view_dependent_color():
+    splats = []
+    for i in range(4):
+        dc = [0.1 * i, -0.2, 0.3]
+        rest = [0.05 * (k + 1) * (-1) ** i for k in range(9)]
+        base = [float(i), 0.0, 0.0, *dc, 0.0, -2.0, -2.0, -4.0, 1.0, 0.0, 0.0, 0.0]
+        splats.append({"base": base, "rest": rest, "dc": dc})
+    write_degree1_ply(TEMP_PLY, splats)
+
+    try:
+        manager = gs_slam_core.SplatManager(TEMP_PLY)
+        assert manager.sh_degree() == 1, f"Expected SH degree 1, got {manager.sh_degree()}"
+
+        view_dir = [0.0, 0.6, 0.8]
+        manager.compute_geometry_cpu(view_dir=view_dir)
+        for i, s in enumerate(splats):
+            expected = reference_color(s["dc"], s["rest"], view_dir)
+            got = manager.get_surfel_color(i)
+            assert all(abs(a - b) < 1e-5 for a, b in zip(got, expected)), f"CPU SH mismatch at {i}: {got} vs {expected}"
+        print("✅ CPU view-dependent color matches reference")
+
+        # DC-only when view_dir is omitted
+        manager.compute_geometry_cpu()
+        dc_only = manager.get_surfel_color(1)
+        assert abs(dc_only[0] - (0.5 + C0 * 0.1)) < 1e-5
+        print("✅ DC-only decode without view_dir")
+
+        try:
+            manager.compute_geometry(view_dir=view_dir)
+        except Exception as e:
+            print(f"⚠️ GPU Compute Failed (skipping GPU SH check): {e}")
+            return
+        for i, s in enumerate(splats):
+            expected = reference_color(s["dc"], s["rest"], view_dir)
+            got = manager.get_surfel_color(i)
+            assert all(abs(a - b) < 1e-4 for a, b in zip(got, expected)), f"GPU SH mismatch at {i}: {got} vs {expected}"
+        print("✅ GPU view-dependent color matches reference")
+    finally:
+        os.remove(TEMP_PLY)
+
+
+def test_incomplete_band():
+    # f_rest_0..4 は degree 1 (9 個) に満たないので無視され (警告のみ)、DC だけで読み込まれる
+    dc = [0.2, -0.1, 0.4]
+    splats = [{"base": [0.0, 0.0, 0.0, *dc, 0.0, -2.0, -2.0, -4.0, 1.0, 0.0, 0.0, 0.0], "rest": [0.3] * 5}]
+    write_degree1_ply(TEMP_PLY, splats, rest_props=REST_PROPS[:5])
+    try:
+        manager = gs_slam_core.SplatManager(TEMP_PLY)
+        assert manager.count() == 1
+        assert manager.sh_degree() == 0, f"Incomplete band should load DC-only, got degree {manager.sh_degree()}"
+        assert manager.get_splat_sh_rest(0) == []
+        manager.compute_geometry_cpu(view_dir=[0.0, 0.6, 0.8])
+        got = manager.get_surfel_color(0)
+        assert all(abs(g - (C0 * d + 0.5)) < 1e-5 for g, d in zip(got, dc)), f"DC-only color expected, got {got}"
+        print("✅ Incomplete f_rest_* band is ignored; the PLY loads DC-only")
+    finally:
+        os.remove(TEMP_PLY)
+
+
+if __name__ == "__main__":
+    os.makedirs("data", exist_ok=True)
+    test_view_dependent_color()
+    test_incomplete_band()
@@ -0,0 +1,107 @@
+import gs_slam_core
+import gzip
+import numpy as np
+import os
+import struct
+
+from splat_helpers import random_manager
+
+TEMP_SPZ = "data/test.spz"
+TEMP_PLY = "data/test_spz.ply"
+
+
+def test_v2_reader():
+    # 参照実装 (v2) と同じレイアウトで 1 点を手書きする
+    header = struct.pack("<IIIBBBB", 0x5053474E, 2, 1, 1, 8, 0, 0)
+    pos = b"".join(struct.pack("<i", round(v * 256))[:3] for v in [1.5, -2.25, 100.0])
+    alpha = bytes([204])
+    color = bytes([128, 166, 89])
+    scale = bytes([round((np.log(0.05) + 10) * 16)] * 3)
+    rot = bytes([128, 128, 128])  # xyz = 0 -> 単位クォータニオン
+    sh = bytes([128 + 64, 128, 128 - 64, 128, 128, 128, 128, 128, 255])
+    with open(TEMP_SPZ, "wb") as f:
+        f.write(gzip.compress(header + pos + alpha + color + scale + rot + sh))
+    try:
+        m = gs_slam_core.SplatManager.load_spz(TEMP_SPZ)
+        assert m.count() == 1 and m.sh_degree() == 1
+        assert m.get_splat_pos(0) == [1.5, -2.25, 100.0]
+        assert abs(m.get_splat_opacity(0) - 0.8) < 1e-6
+        assert np.allclose(m.get_splat_scale(0), 0.05, rtol=0.04)
+        assert np.allclose(m.get_splat_rot(0), [0, 0, 0, 1], atol=0.01)
+        assert np.allclose(m.get_splat_sh(0), [(128 / 255 - 0.5) / 0.15, (166 / 255 - 0.5) / 0.15, (89 / 255 - 0.5) / 0.15], atol=1e-5)
+        assert np.allclose(m.get_splat_sh_rest(0), [0.5, 0, -0.5, 0, 0, 0, 0, 0, 127 / 128])
+        print("✅ SPZ v2 reader decodes the reference layout")
+
+        # コンストラクタも gzip を判別して読む
+        assert gs_slam_core.SplatManager(TEMP_SPZ).get_splat_pos(0) == [1.5, -2.25, 100.0]
+        print("✅ SplatManager(path) sniffs SPZ")
+    finally:
+        os.remove(TEMP_SPZ)
+
+
+def test_roundtrip():
+    src = random_manager(2000, seed=1, sh_degree=3)
+    src.save_spz(TEMP_SPZ)
+    try:
+        dst = gs_slam_core.SplatManager.load_spz(TEMP_SPZ)
+        assert dst.count() == src.count() and dst.sh_degree() == 3
+        # 12 bit の固定小数点 / 1/16 刻みの log scale / 0.15 倍の 8 bit 色
+        assert np.allclose(dst.positions, src.positions, atol=0.5 / 4096 + 1e-6)
+        assert np.allclose(np.log(dst.scales), np.log(src.scales), atol=1 / 32 + 1e-4)
+        dots = np.abs(np.sum(dst.rotations * src.rotations, axis=1))
+        assert np.all(dots > 0.999), f"Rotation error too large: {1 - dots.min()}"
+        assert np.allclose(dst.opacities, src.opacities, atol=0.5 / 255 + 1e-6)
+        assert np.allclose(dst.sh_dc, src.sh_dc, atol=0.5 / (0.15 * 255) + 1e-5)
+        for i in range(0, 2000, 97):
+            a = np.array(dst.get_splat_sh_rest(i)).reshape(15, 3)
+            b = np.array(src.get_splat_sh_rest(i)).reshape(15, 3)
+            # 次数 1 は 5 bit (8 刻み)、それ以上は 4 bit (16 刻み)
+            assert np.allclose(a[:3], b[:3], atol=4 / 128 + 1e-6)
+            assert np.allclose(a[3:], b[3:], atol=8 / 128 + 1e-6)
+        print("✅ SPZ round-trip within quantization tolerances (SH degree 3)")
+
+        # 乱数の SH は gzip がほとんど効かないので、実データ (約 10 倍) より控えめに確認する
+        src.save_splat_ply(TEMP_PLY)
+        ratio = os.path.getsize(TEMP_PLY) / os.path.getsize(TEMP_SPZ)
+        assert ratio > 3, f"SPZ only {ratio:.1f}x smaller than PLY"
+        print(f"✅ SPZ is {ratio:.1f}x smaller than PLY")
+    finally:
+        os.remove(TEMP_SPZ)
+        if os.path.exists(TEMP_PLY):
+            os.remove(TEMP_PLY)
+
+
+def test_fractional_bits():
+    src = random_manager(200, seed=2, extent=3.0)
+    src.save_spz(TEMP_SPZ, fractional_bits=20)
+    try:
+        with gzip.open(TEMP_SPZ) as f:
+            assert f.read(16)[13] == 20
+        dst = gs_slam_core.SplatManager.load_spz(TEMP_SPZ)
+        assert np.allclose(dst.positions, src.positions, atol=0.5 / 2 ** 20 + 1e-6)
+        print("✅ fractional_bits=20 keeps sub-micrometer positions")
+    finally:
+        os.remove(TEMP_SPZ)
+
+    # 広いシーンは小数部を減らす必要がある (int24)
+    wide = random_manager(10, seed=3, extent=5000.0)
+    try:
+        wide.save_spz(TEMP_SPZ)
+        raise AssertionError("Positions beyond the int24 range should raise ValueError")
+    except ValueError:
+        print("✅ Out-of-range positions rejected at the default 12 bits")
+    assert not os.path.exists(TEMP_SPZ)
+    wide.save_spz(TEMP_SPZ, fractional_bits=8)
+    try:
+        dst = gs_slam_core.SplatManager.load_spz(TEMP_SPZ)
+        assert np.allclose(dst.positions, wide.positions, atol=0.5 / 256 + 1e-3)
+        print("✅ fractional_bits=8 covers a 10 km scene")
+    finally:
+        os.remove(TEMP_SPZ)
+
+
+if __name__ == "__main__":
+    os.makedirs("data", exist_ok=True)
+    test_v2_reader()
+    test_roundtrip()
+    test_fractional_bits()
@@ -48,26 +48,26 @@ def test_super_resolution():
     orig_count = manager.count()
     print(f"Original Splats: {orig_count}")
 
-    # 2. Test Factor = 1 (Should match original count)
+    # 2. Test Factor = 1 (filtered splats are dropped, so at most the original count)
     print("\n--- Test Case 1: Factor = 1 ---")
     try:
         count_x1 = manager.compute_super_resolution(1)
         print(f"Generated Surfels: {count_x1}")
-        assert count_x1 == orig_count, (
-            f"Count mismatch! Expected {orig_count}, got {count_x1}"
+        assert 0 < count_x1 <= orig_count, (
+            f"Count mismatch! Expected at most {orig_count}, got {count_x1}"
         )
     except Exception as e:
         print(f"⚠️ GPU Compute Failed (Factor 1): {e}")
         return
 
-    # 3. Test Factor = 4 (Should be exactly 4x)
+    # 3. Test Factor = 4 (Should be exactly 4x the valid splats)
     print("\n--- Test Case 2: Factor = 4 (Upsampling) ---")
     factor = 4
     try:
         count_x4 = manager.compute_super_resolution(factor)
         print(f"Generated Surfels: {count_x4}")
-        assert count_x4 == orig_count * factor, (
-            f"Count mismatch! Expected {orig_count * factor}, got {count_x4}"
+        assert count_x4 == count_x1 * factor, (
+            f"Count mismatch! Expected {count_x1 * factor}, got {count_x4}"
         )
 
         # Verify Normals and Colors in memory
@@ -0,0 +1,390 @@
+import gs_slam_core
+import math
+import os
+import random
+
+from splat_helpers import write_ply
+
+TEMP_PLY = "data/test_sr_cpu.ply"
+TEMP_LARGE_PLY = "data/test_sr_cpu_large.ply"
+TEMP_OUT = "data/test_sr_cpu_out.ply"
+
+SH_C0 = 0.28209479177387814
+
+
+def make_rows(n, seed=0):
+    # 学習器の出力と同じ前活性化の値 (log scale / logit opacity / wxyz)
+    rng = random.Random(seed)
+    rows = []
+    for _ in range(n):
+        pos = [rng.uniform(-5, 5) for _ in range(3)]
+        dc = [rng.uniform(-2, 2) for _ in range(3)]
+        opacity = rng.uniform(-3, 4)  # 一部は sigmoid < 0.3 で除外される
+        scale = [rng.uniform(-5, -1) for _ in range(3)]  # 一部は等方的で除外される
+        rot = [rng.gauss(0, 1) for _ in range(4)]
+        rows.append([*pos, *dc, opacity, *scale, *rot])
+    return rows
+
+
+def remove_files(*paths):
+    for path in paths:
+        if os.path.exists(path):
+            os.remove(path)
+
+
+def read_ascii_positions(path):
+    with open(path) as f:
+        lines = f.read().split("end_header\n", 1)[1].splitlines()
+    return [tuple(float(v) for v in line.split()[:3]) for line in lines if line]
+
+
+def sub(a, b):
+    return [x - y for x, y in zip(a, b)]
+
+
+def dot(a, b):
+    return sum(x * y for x, y in zip(a, b))
+
+
+def rotate(q, v):
+    # q = (x, y, z, w)。読み込んだ回転は正規化されていない
+    norm = math.sqrt(dot(q, q))
+    x, y, z, w = [v / norm for v in q]
+    u = [x, y, z]
+    t = [2 * (u[1] * v[2] - u[2] * v[1]), 2 * (u[2] * v[0] - u[0] * v[2]), 2 * (u[0] * v[1] - u[1] * v[0])]
+    c = [u[1] * t[2] - u[2] * t[1], u[2] * t[0] - u[0] * t[2], u[0] * t[1] - u[1] * t[0]]
+    return [v[i] + w * t[i] + c[i] for i in range(3)]
+
+
+def is_filtered(opacity, scale, min_opacity=0.3, max_aspect_ratio=0.6, max_scale=None):
+    return opacity < min_opacity or min(scale) / max(scale) > max_aspect_ratio or (max_scale is not None and max(scale) > max_scale)
+
+
+def test_tangent_plane(factor=8, n=300):
+    write_ply(TEMP_PLY, make_rows(n))
+    try:
+        m = gs_slam_core.SplatManager(TEMP_PLY, backend="cpu")
+        kept = [p for p in range(n) if not is_filtered(m.get_splat_opacity(p), m.get_splat_scale(p))]
+        assert 0 < len(kept) < n, "Synthetic data should contain both kept and filtered splats"
+
+        # 除外されたスプラットの Surfel は出力されない
+        assert m.compute_super_resolution(factor) == len(kept) * factor
+        assert m.last_backend == "cpu"
+        m.save_ply(TEMP_OUT, encoding="ascii")
+        positions = read_ascii_positions(TEMP_OUT)
+        assert len(positions) == len(kept) * factor
+        assert (0.0, 0.0, 0.0) not in positions
+
+        for k, p in enumerate(kept):
+            parent = m.get_splat_pos(p)
+            scale = m.get_splat_scale(p)
+            children = range(k * factor, (k + 1) * factor)
+
+            # 最も薄い軸が法線
+            axis = [0.0, 0.0, 0.0]
+            axis[scale.index(min(scale))] = 1.0
+            normal = rotate(m.get_splat_rot(p), axis)
+            expected_color = [min(max(0.5 + SH_C0 * v, 0.0), 1.0) for v in m.get_splat_sh(p)]
+
+            # 子 0 は親の中心
+            assert max(abs(a - b) for a, b in zip(positions[children[0]], parent)) < 1e-5
+            spread = 0.8 * sorted(scale)[2] + 1e-5
+            for c in children:
+                d = sub(positions[c], parent)
+                assert abs(dot(d, normal)) < 1e-5, f"Surfel {c} is off the tangent plane of splat {p}: {dot(d, normal)}"
+                assert math.sqrt(dot(d, d)) <= spread, f"Surfel {c} is outside the splat footprint"
+                assert abs(abs(dot(m.get_surfel_normal(c), normal)) - 1.0) < 1e-5
+                assert all(abs(a - b) < 1e-5 for a, b in zip(m.get_surfel_color(c), expected_color))
+
+        print(f"✅ CPU SR x{factor}: {len(kept) * factor} surfels on the tangent planes of {len(kept)} splats ({n - len(kept)} filtered)")
+    finally:
+        remove_files(TEMP_PLY, TEMP_OUT)
+
+
+def test_factor_one(n=300):
+    # factor=1 はサンプリングせず親の中心に 1 つ
+    write_ply(TEMP_PLY, make_rows(n))
+    try:
+        m = gs_slam_core.SplatManager(TEMP_PLY, backend="cpu")
+        kept = [p for p in range(n) if not is_filtered(m.get_splat_opacity(p), m.get_splat_scale(p))]
+        assert m.compute_super_resolution(1) == len(kept)
+        m.save_ply(TEMP_OUT, encoding="ascii")
+        for p, pos in zip(kept, read_ascii_positions(TEMP_OUT)):
+            assert max(abs(a - b) for a, b in zip(pos, m.get_splat_pos(p))) < 1e-5
+        print("✅ CPU SR x1 keeps the splat centers")
+    finally:
+        remove_files(TEMP_PLY, TEMP_OUT)
+
+
+def test_gpu_parity(factor=4):
+    write_ply(TEMP_PLY, make_rows(300))
+    try:
+        gpu = gs_slam_core.SplatManager(TEMP_PLY, backend="gpu")
+        try:
+            count = gpu.compute_super_resolution(factor)
+        except (gs_slam_core.GpuUnavailableError, gs_slam_core.DeviceLostError) as e:
+            print(f"⚠️ Skipping GPU parity: {e}")
+            return
+        cpu = gs_slam_core.SplatManager(TEMP_PLY, backend="cpu")
+        assert cpu.compute_super_resolution(factor) == count
+
+        gpu.save_ply(TEMP_OUT, encoding="ascii")
+        gpu_pos = read_ascii_positions(TEMP_OUT)
+        cpu.save_ply(TEMP_OUT, encoding="ascii")
+        cpu_pos = read_ascii_positions(TEMP_OUT)
+        for i in range(count):
+            assert max(abs(a - b) for a, b in zip(gpu_pos[i], cpu_pos[i])) < 1e-4, f"Position mismatch at {i}"
+            assert all(abs(a - b) < 1e-4 for a, b in zip(gpu.get_surfel_normal(i), cpu.get_surfel_normal(i)))
+            assert all(abs(a - b) < 1e-4 for a, b in zip(gpu.get_surfel_color(i), cpu.get_surfel_color(i)))
+        print(f"✅ CPU SR matches GPU SR ({count} surfels)")
+    finally:
+        remove_files(TEMP_PLY, TEMP_OUT)
+
+
+def test_filter_params(factor=4, n=300):
+    write_ply(TEMP_PLY, make_rows(n))
+    try:
+        m = gs_slam_core.SplatManager(TEMP_PLY, backend="cpu")
+        opacities = [m.get_splat_opacity(p) for p in range(n)]
+        scales = [m.get_splat_scale(p) for p in range(n)]
+
+        def expected(**params):
+            return sum(not is_filtered(o, s, **params) for o, s in zip(opacities, scales)) * factor
+
+        # フィルタ無効なら全スプラット
+        assert m.compute_super_resolution(factor, min_opacity=0.0, max_aspect_ratio=1.0) == n * factor
+        for params in [dict(min_opacity=0.8), dict(max_aspect_ratio=0.3), dict(max_scale=0.1), dict(min_opacity=0.5, max_scale=0.2)]:
+            count = m.compute_super_resolution(factor, **params)
+            assert count == expected(**params), f"{params}: expected {expected(**params)}, got {count}"
+            print(f"✅ {params}: {count} surfels")
+
+        # sample_radius=0 なら子はすべて親の中心
+        kept = [p for p in range(n) if not is_filtered(opacities[p], scales[p])]
+        m.compute_super_resolution(factor, sample_radius=0.0)
+        m.save_ply(TEMP_OUT, encoding="ascii")
+        positions = read_ascii_positions(TEMP_OUT)
+        for k, p in enumerate(kept):
+            for c in range(k * factor, (k + 1) * factor):
+                assert max(abs(a - b) for a, b in zip(positions[c], m.get_splat_pos(p))) < 1e-5
+        # 広げると子は max scale の sample_radius 倍まで離れる
+        m.compute_super_resolution(factor, sample_radius=1.5)
+        m.save_ply(TEMP_OUT, encoding="ascii")
+        positions = read_ascii_positions(TEMP_OUT)
+        farthest = 0.0
+        for k, p in enumerate(kept):
+            for c in range(k * factor, (k + 1) * factor):
+                d = sub(positions[c], m.get_splat_pos(p))
+                ratio = math.sqrt(dot(d, d)) / max(scales[p])
+                assert ratio <= 1.5 + 1e-4
+                farthest = max(farthest, ratio)
+        assert farthest > 0.8, "sample_radius=1.5 should reach beyond the default 0.8 sigma"
+        print(f"✅ sample_radius scales the sampling disc (farthest child at {farthest:.2f} sigma)")
+
+        for params in [dict(min_opacity=-0.1), dict(min_opacity=1.5), dict(max_aspect_ratio=0.0), dict(max_scale=0.0), dict(max_scale=float("inf")), dict(sample_radius=-1.0)]:
+            try:
+                m.compute_super_resolution(factor, **params)
+                raise AssertionError(f"{params} should raise InvalidParameterError")
+            except gs_slam_core.InvalidParameterError:
+                pass
+        print("✅ Out-of-range SR parameters rejected")
+
+        gpu = gs_slam_core.SplatManager(TEMP_PLY, backend="gpu")
+        params = dict(min_opacity=0.5, max_aspect_ratio=0.4, max_scale=0.2)
+        try:
+            count = gpu.compute_super_resolution(factor, sample_radius=1.2, **params)
+        except (gs_slam_core.GpuUnavailableError, gs_slam_core.DeviceLostError) as e:
+            print(f"⚠️ Skipping GPU parameter parity: {e}")
+            return
+        assert count == m.compute_super_resolution(factor, sample_radius=1.2, **params) == expected(**params)
+        gpu.save_ply(TEMP_OUT, encoding="ascii")
+        gpu_pos = read_ascii_positions(TEMP_OUT)
+        m.save_ply(TEMP_OUT, encoding="ascii")
+        for a, b in zip(gpu_pos, read_ascii_positions(TEMP_OUT)):
+            assert max(abs(x - y) for x, y in zip(a, b)) < 1e-4
+        print(f"✅ GPU SR honours the same parameters ({count} surfels)")
+    finally:
+        remove_files(TEMP_PLY, TEMP_OUT)
+
+
+def expected_children(scale, density, factor):
+    # 法線 (最も薄い軸) を除いた 2 軸の 1 sigma 楕円の面積に比例
+    s = scale
+    if s[0] < s[1] and s[0] < s[2]:
+        su, sv = s[1], s[2]
+    elif s[1] < s[2]:
+        su, sv = s[0], s[2]
+    else:
+        su, sv = s[0], s[1]
+    return int(min(max(math.floor(density * math.pi * su * sv + 0.5), 1), factor))
+
+
+def test_density(factor=32, density=200.0, n=300):
+    write_ply(TEMP_PLY, make_rows(n))
+    try:
+        m = gs_slam_core.SplatManager(TEMP_PLY, backend="cpu")
+        kept = [p for p in range(n) if not is_filtered(m.get_splat_opacity(p), m.get_splat_scale(p))]
+        counts = [expected_children(m.get_splat_scale(p), density, factor) for p in kept]
+        assert min(counts) == 1 and max(counts) == factor and len(set(counts)) > 5, "Synthetic scales should span the clamp range"
+
+        total = m.compute_super_resolution(factor, density=density)
+        assert total == sum(counts), f"Expected {sum(counts)}, got {total}"
+        m.save_ply(TEMP_OUT, encoding="ascii")
+        positions = read_ascii_positions(TEMP_OUT)
+
+        start = 0
+        for p, count in zip(kept, counts):
+            parent = m.get_splat_pos(p)
+            scale = m.get_splat_scale(p)
+            axis = [0.0, 0.0, 0.0]
+            axis[scale.index(min(scale))] = 1.0
+            normal = rotate(m.get_splat_rot(p), axis)
+            # 各スプラットの区間は子 0 (中心) から始まり、接平面上に並ぶ
+            assert max(abs(a - b) for a, b in zip(positions[start], parent)) < 1e-5, f"Splat {p} does not start at its center"
+            for c in range(start, start + count):
+                assert abs(dot(sub(positions[c], parent), normal)) < 1e-5
+            start += count
+        print(f"✅ density={density}/m²: {total} surfels, {min(counts)}-{max(counts)} per splat (factor {factor} caps)")
+
+        # 上限に届かない大きなスプラットは面密度が目標に近い
+        for p, count in zip(kept, counts):
+            s = sorted(m.get_splat_scale(p))
+            area = math.pi * s[1] * s[2]
+            if 10 <= count < factor:
+                assert abs(count / area - density) <= 0.5 / area + 1e-3
+        print("✅ Uncapped splats hit the target surface density")
+
+        for bad in (0.0, -5.0, float("nan")):
+            try:
+                m.compute_super_resolution(factor, density=bad)
+                raise AssertionError(f"density={bad} should raise InvalidParameterError")
+            except gs_slam_core.InvalidParameterError:
+                pass
+
+        gpu = gs_slam_core.SplatManager(TEMP_PLY, backend="gpu")
+        try:
+            gpu_total = gpu.compute_super_resolution(factor, density=density)
+        except (gs_slam_core.GpuUnavailableError, gs_slam_core.DeviceLostError) as e:
+            print(f"⚠️ Skipping GPU density parity: {e}")
+            return
+        assert gpu_total == total
+        gpu.save_ply(TEMP_OUT, encoding="ascii")
+        for a, b in zip(read_ascii_positions(TEMP_OUT), positions):
+            assert max(abs(x - y) for x, y in zip(a, b)) < 1e-4
+        print(f"✅ GPU density mode matches the CPU layout ({gpu_total} surfels)")
+    finally:
+        remove_files(TEMP_PLY, TEMP_OUT)
+
+
+def sigma_offsets(m, kept, factor, positions):
+    # 各子の親からのずれを接平面の 2 軸に射影し、その軸のスケールで割る (標準 2D ガウシアンの座標)
+    out = []
+    for k, p in enumerate(kept):
+        parent = m.get_splat_pos(p)
+        scale = m.get_splat_scale(p)
+        q = m.get_splat_rot(p)
+        order = sorted(range(3), key=lambda a: scale[a])
+        axes = []
+        for a in order:
+            e = [0.0, 0.0, 0.0]
+            e[a] = 1.0
+            axes.append(rotate(q, e))
+        for c in range(k * factor, (k + 1) * factor):
+            d = sub(positions[c], parent)
+            out.append((dot(d, axes[0]), dot(d, axes[1]) / scale[order[1]], dot(d, axes[2]) / scale[order[2]]))
+    return out
+
+
+def test_gaussian_sampler(factor=16, n=300):
+    write_ply(TEMP_PLY, make_rows(n))
+    try:
+        m = gs_slam_core.SplatManager(TEMP_PLY, backend="cpu")
+        kept = [p for p in range(n) if not is_filtered(m.get_splat_opacity(p), m.get_splat_scale(p))]
+
+        def run(manager, **params):
+            count = manager.compute_super_resolution(factor, sampler="gaussian", **params)
+            assert count == len(kept) * factor
+            manager.save_ply(TEMP_OUT, encoding="ascii")
+            return read_ascii_positions(TEMP_OUT)
+
+        # 同じ seed なら同じ結果、seed を変えると別の配置
+        first = run(m, seed=7)
+        assert run(m, seed=7) == first
+        other = run(m, seed=8)
+        assert sum(a != b for a, b in zip(first, other)) > len(first) * 0.9
+        print("✅ Gaussian sampler is reproducible per seed")
+
+        # 接平面上に、スプラット自身の分散で散らばる (標準 2D ガウシアンなら E[r^2] = 2)
+        offsets = sigma_offsets(m, kept, factor, first)
+        assert max(abs(h) for h, _, _ in offsets) < 1e-5, "Gaussian samples must stay on the tangent plane"
+        r2 = [u * u + v * v for _, u, v in offsets]
+        mean_u = sum(u for _, u, _ in offsets) / len(offsets)
+        mean_r2 = sum(r2) / len(r2)
+        assert abs(mean_u) < 0.1 and abs(mean_r2 - 2.0) < 0.3, f"Unexpected spread: mean u {mean_u:.3f}, E[r^2] {mean_r2:.3f}"
+        assert max(r2) > 4.0, "Untruncated samples should reach beyond 2 sigma"
+        print(f"✅ Gaussian sampler spread: E[r²] = {mean_r2:.2f} sigma² over {len(offsets)} surfels")
+
+        # truncate=k なら k sigma の楕円の内側
+        for k in (0.5, 1.5):
+            radii = [math.sqrt(u * u + v * v) for _, u, v in sigma_offsets(m, kept, factor, run(m, seed=7, truncate=k))]
+            assert max(radii) <= k + 1e-3, f"truncate={k}: sample at {max(radii):.4f} sigma"
+            assert max(radii) > k * 0.9
+        print("✅ truncate bounds the samples to k sigma")
+
+        for params in [dict(sampler="uniform"), dict(sampler="gaussian", truncate=0.0), dict(sampler="gaussian", truncate=-1.0), dict(sampler="gaussian", truncate=float("nan"))]:
+            try:
+                m.compute_super_resolution(factor, **params)
+                raise AssertionError(f"{params} should raise InvalidParameterError")
+            except gs_slam_core.InvalidParameterError:
+                pass
+        print("✅ Invalid sampler / truncate rejected")
+
+        # GPU と同じ乱数列 (GPU の sin / cos / log は近似なので誤差はスケール比で見る)
+        gpu = gs_slam_core.SplatManager(TEMP_PLY, backend="gpu")
+        try:
+            gpu_pos = run(gpu, seed=7, truncate=3.0)
+        except (gs_slam_core.GpuUnavailableError, gs_slam_core.DeviceLostError) as e:
+            print(f"⚠️ Skipping GPU Gaussian parity: {e}")
+            return
+        cpu_pos = run(m, seed=7, truncate=3.0)
+        for i, (a, b) in enumerate(zip(gpu_pos, cpu_pos)):
+            tol = 1e-3 * max(m.get_splat_scale(kept[i // factor])) + 1e-5
+            assert max(abs(x - y) for x, y in zip(a, b)) < tol, f"Gaussian sample mismatch at {i}"
+        print(f"✅ GPU Gaussian sampler matches the CPU ({len(gpu_pos)} surfels)")
+    finally:
+        remove_files(TEMP_PLY, TEMP_OUT)
+
+
+def test_gpu_compaction(n=70000):
+    # 70001 要素の prefix sum は 4 レベル (256 要素 / ワークグループ) になる
+    write_ply(TEMP_LARGE_PLY, make_rows(n, seed=1))
+    try:
+        gpu = gs_slam_core.SplatManager(TEMP_LARGE_PLY, backend="gpu")
+        try:
+            count = gpu.compute_super_resolution(1)
+        except (gs_slam_core.GpuUnavailableError, gs_slam_core.DeviceLostError) as e:
+            print(f"⚠️ Skipping GPU compaction: {e}")
+            return
+        cpu = gs_slam_core.SplatManager(TEMP_LARGE_PLY, backend="cpu")
+        assert cpu.compute_super_resolution(1) == count < n
+
+        gpu.save_ply(TEMP_OUT, encoding="ascii")
+        gpu_pos = read_ascii_positions(TEMP_OUT)
+        cpu.save_ply(TEMP_OUT, encoding="ascii")
+        cpu_pos = read_ascii_positions(TEMP_OUT)
+        assert len(gpu_pos) == count
+        for i in range(count):
+            assert max(abs(a - b) for a, b in zip(gpu_pos[i], cpu_pos[i])) < 1e-4, f"Compaction order differs at {i}"
+        print(f"✅ GPU SR compacts {n} splats to {count} surfels in input order")
+    finally:
+        remove_files(TEMP_LARGE_PLY, TEMP_OUT)
+
+
+if __name__ == "__main__":
+    os.makedirs("data", exist_ok=True)
+    test_tangent_plane()
+    test_factor_one()
+    test_gpu_parity()
+    test_filter_params()
+    test_density()
+    test_gaussian_sampler()
+    test_gpu_compaction()
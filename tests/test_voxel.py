@@ -0,0 +1,157 @@
+import gs_slam_core
+import math
+import random
+
+from splat_helpers import BACKENDS, make_manager
+
+# voxel_downsample: ボクセルごとの (重み付き) 平均 / 代表点。GPU (ハッシュ表) と CPU (HashMap) で同じ結果になる
+
+SH_C0 = 0.28209479177387814
+
+
+def clustered(voxels, per_voxel, seed, voxel_size=1.0):
+    # 各ボクセルの中に点を散らす。点の順序はボクセルをまたいで混ぜる
+    rng = random.Random(seed)
+    points = []
+    for v in voxels:
+        for _ in range(per_voxel):
+            points.append(([(c + rng.uniform(0.05, 0.95)) * voxel_size for c in v],
+                           [rng.random() for _ in range(3)], rng.uniform(0.1, 1.0)))
+    rng.shuffle(points)
+    return points
+
+
+def points_manager(points, backend):
+    # clustered() の (位置, 色, 不透明度) から作る
+    return make_manager([p for p, _, _ in points], backend, scale=(0.05, 0.05, 0.001),
+                        opacities=[o for _, _, o in points], colors=[c for _, c, _ in points])
+
+
+def expected(points, voxel_size, weighted):
+    # 最初に現れた順のボクセル → (重み付き) 平均位置・色・不透明度
+    groups = {}
+    for p, c, o in points:
+        groups.setdefault(tuple(math.floor(x / voxel_size) for x in p), []).append((p, c, o))
+    result = []
+    for members in groups.values():
+        weights = [o if weighted else 1.0 for _, _, o in members]
+        total = sum(weights)
+        mean = lambda values: [sum(w * v[a] for w, v in zip(weights, values)) / total for a in range(3)]
+        result.append((mean([p for p, _, _ in members]), mean([c for _, c, _ in members]),
+                       sum(w * o for w, (_, _, o) in zip(weights, members)) / total, members))
+    return result
+
+
+def close(a, b, tol=1e-4):
+    return all(abs(x - y) < tol for x, y in zip(a, b))
+
+
+def test_modes():
+    for backend in BACKENDS:
+        voxels = [(x, y, z) for x in range(-2, 3) for y in range(-1, 2) for z in range(2)]
+        points = clustered(voxels, 12, seed=1)
+        m = points_manager(points, backend)
+        points = [(m.get_splat_pos(i), c, m.get_splat_opacity(i)) for i, (_, c, _) in enumerate(points)]
+        try:
+            down = m.voxel_downsample(1.0)
+        except (gs_slam_core.GpuUnavailableError, gs_slam_core.DeviceLostError) as e:
+            print(f"⚠️ Skipping voxel_downsample on {backend}: {e}")
+            continue
+        assert m.last_backend == backend and down.backend == backend
+        assert m.count() == len(points), "The source manager is unchanged"
+        want = expected(points, 1.0, weighted=False)
+        assert down.count() == len(voxels) == len(want)
+        for i, (pos, color, opacity, _) in enumerate(want):
+            assert close(down.get_splat_pos(i), pos), f"Voxel {i}: {down.get_splat_pos(i)} vs {pos}"
+            rgb = [SH_C0 * c + 0.5 for c in down.get_splat_sh(i)]
+            assert close(rgb, color), f"Voxel {i} color {rgb} vs {color}"
+            assert abs(down.get_splat_opacity(i) - opacity) < 1e-4
+        print(f"✅ centroid on {backend}: {len(points)} splats -> {down.count()} voxels in first-appearance order")
+
+        down = m.voxel_downsample(1.0, mode="opacity_weighted")
+        for i, (pos, color, opacity, _) in enumerate(expected(points, 1.0, weighted=True)):
+            assert close(down.get_splat_pos(i), pos) and close([SH_C0 * c + 0.5 for c in down.get_splat_sh(i)], color)
+            assert abs(down.get_splat_opacity(i) - opacity) < 1e-4
+        print(f"✅ opacity_weighted on {backend} weights position and color by opacity")
+
+        down = m.voxel_downsample(1.0, mode="nearest")
+        for i, (pos, _, _, members) in enumerate(want):
+            kept = down.get_splat_pos(i)
+            nearest = min(members, key=lambda p: sum((a - b) ** 2 for a, b in zip(p[0], pos)))
+            assert kept == nearest[0], f"Voxel {i} should keep its point nearest the centroid"
+        print(f"✅ nearest on {backend} keeps the original splat nearest each centroid")
+
+        # 格子はワールド原点に揃う (大きなボクセルでも原点の両側は別。x, y は負側と正側、z は正側のみ)
+        assert m.voxel_downsample(100.0).count() == 4
+
+
+def test_surfels_and_sh():
+    for backend in BACKENDS:
+        # 同じボクセルで法線の符号が逆のスプラット (z 軸が最も薄い, x 軸まわり 180 度で -z)
+        n = 64
+        rng = random.Random(2)
+        positions = [[rng.uniform(0, 0.9), rng.uniform(0, 0.9), rng.uniform(0, 0.1)] for _ in range(n)]
+        rotations = [[0.0, 0.0, 0.0, 1.0] if i % 2 else [1.0, 0.0, 0.0, 0.0] for i in range(n)]
+        sh_rest = [[[0.01 * i, 0.0, -0.01 * i]] * 3 for i in range(n)]
+        m = gs_slam_core.SplatManager.from_arrays(positions, [[0.1, 0.1, 0.001]] * n, rotations, [0.8] * n, sh_rest=sh_rest)
+        m.backend = backend
+        try:
+            m.compute_geometry()
+            down = m.voxel_downsample(1.0)
+        except (gs_slam_core.GpuUnavailableError, gs_slam_core.DeviceLostError) as e:
+            print(f"⚠️ Skipping surfel downsampling on {backend}: {e}")
+            continue
+        assert down.count() == 1
+        normal = down.get_surfel_normal(0)
+        assert close(normal, m.get_surfel_normal(0), 1e-5), f"Opposite normals are aligned before averaging: {normal}"
+        assert down.sh_degree() == 1
+        rest = down.get_splat_sh_rest(0)
+        assert close(rest[:3], [0.01 * (n - 1) / 2, 0.0, -0.01 * (n - 1) / 2], 1e-5), f"SH rest is averaged: {rest[:3]}"
+        print(f"✅ Surfel normals on {backend} are sign-aligned and renormalized; SH rest is averaged")
+
+
+def test_parity():
+    # GPU はボクセル内の和の順序が不定なので、値は許容誤差で比べる (代表点が同距離で並ばない乱数の点群)
+    voxels = [(random.Random(v).randrange(-50, 50), v % 7, v % 3) for v in range(400)]
+    points = clustered(voxels, 20, seed=3, voxel_size=0.25)
+    cpu, gpu = points_manager(points, "cpu"), points_manager(points, "gpu")
+    try:
+        gpu.compute_geometry()
+    except (gs_slam_core.GpuUnavailableError, gs_slam_core.DeviceLostError) as e:
+        print(f"⚠️ Skipping GPU / CPU parity: {e}")
+        return
+    cpu.compute_geometry()
+    for mode in ["centroid", "opacity_weighted", "nearest"]:
+        a, b = cpu.voxel_downsample(0.25, mode=mode), gpu.voxel_downsample(0.25, mode=mode)
+        assert a.count() == b.count()
+        for i in range(a.count()):
+            assert close(a.get_splat_pos(i), b.get_splat_pos(i), 1e-5) and close(a.get_splat_sh(i), b.get_splat_sh(i), 1e-5)
+            assert close(a.get_surfel_normal(i), b.get_surfel_normal(i), 1e-5) and close(a.get_surfel_color(i), b.get_surfel_color(i), 1e-5)
+    print(f"✅ GPU hash table and CPU agree ({a.count()} voxels from {len(points)} splats)")
+
+    # ほぼ 1 点 1 ボクセル: ハッシュの衝突が多く、find_first がバケットを走査する経路も通る
+    a, b = cpu.voxel_downsample(0.01), gpu.voxel_downsample(0.01)
+    assert a.count() == b.count() and a.count() > len(points) // 2
+    assert all(close(a.get_splat_pos(i), b.get_splat_pos(i), 1e-5) for i in range(a.count()))
+    print(f"✅ GPU and CPU agree when most voxels hold one splat ({a.count()} voxels)")
+
+
+def test_invalid():
+    m = points_manager(clustered([(0, 0, 0)], 4, seed=4), "cpu")
+    for call in [lambda: m.voxel_downsample(0.0), lambda: m.voxel_downsample(-1.0),
+                 lambda: m.voxel_downsample(float("inf")), lambda: m.voxel_downsample(1.0, mode="median")]:
+        try:
+            call()
+            raise AssertionError("Invalid voxel_downsample arguments should raise InvalidParameterError")
+        except gs_slam_core.InvalidParameterError:
+            pass
+    empty = gs_slam_core.SplatManager.from_arrays([], [], [], [])
+    assert empty.voxel_downsample(1.0).count() == 0
+    print("✅ Invalid voxel sizes / modes rejected, empty input gives an empty manager")
+
+
+if __name__ == "__main__":
+    test_modes()
+    test_surfels_and_sh()
+    test_parity()
+    test_invalid()
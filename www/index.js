@@ -8,7 +8,15 @@ async function run() {
     const canvas = document.getElementById('canvas');
     const btnRGB = document.getElementById('btnRGB');
     const btnNormal = document.getElementById('btnNormal');
+    const btnViewDep = document.getElementById('btnViewDep');
     const btnExport = document.getElementById('btnExport');
+    const btnExportSplat = document.getElementById('btnExportSplat');
+    const btnExportPcd = document.getElementById('btnExportPcd');
+    const btnExportSpz = document.getElementById('btnExportSpz');
+    const btnExportDotSplat = document.getElementById('btnExportDotSplat');
+    const btnExportKsplat = document.getElementById('btnExportKsplat');
+    const selQuatOrder = document.getElementById('selQuatOrder');
+    const selValueDomain = document.getElementById('selValueDomain');
     
     const sliderSR = document.getElementById('sliderSR');
     const valSR = document.getElementById('valSR');
@@ -23,6 +31,16 @@ async function run() {
     window.addEventListener('resize', resize);
     resize();
 
+    // WASM 側のエラーは name 付きの Error (PlyFormatError / GpuUnavailableError など)
+    const describeError = (err) => (err && err.name && err.message) ? `${err.name}: ${err.message}` : String(err);
+
+    // PLY の読み込みオプションは ?quat_order=xyzw&value_domain=activated でも指定できる
+    const params = new URLSearchParams(window.location.search);
+    for (const [key, select] of [['quat_order', selQuatOrder], ['value_domain', selValueDomain]]) {
+        const value = params.get(key);
+        if ([...select.options].some(o => o.value === value)) select.value = value;
+    }
+
     try {
         const viewer = await WasmViewer.new("canvas");
         window.viewer = viewer; 
@@ -35,28 +53,44 @@ async function run() {
         animate();
 
         // File Loading
-        document.getElementById('fileInput').addEventListener('change', async (event) => {
-            const file = event.target.files[0];
-            if (!file) return;
-
+        let currentFile = null;
+        const loadFile = async (file) => {
             statusDiv.innerText = `Loading ${file.name}...`;
             btnExport.disabled = true;
+            btnExportSplat.disabled = true;
+            btnExportPcd.disabled = true;
+            btnExportSpz.disabled = true;
+            btnExportDotSplat.disabled = true;
+            btnExportKsplat.disabled = true;
             try {
                 const buffer = await file.arrayBuffer();
                 const data = new Uint8Array(buffer);
                 console.log(`Passing ${data.length} bytes to WASM`);
-                viewer.load_data(data);
+                viewer.load_data(data, selQuatOrder.value, selValueDomain.value);
                 statusDiv.innerText = `Rendering ${file.name}`;
                 btnExport.disabled = false;
+                btnExportSplat.disabled = false;
+                btnExportPcd.disabled = false;
+                btnExportSpz.disabled = false;
+                btnExportDotSplat.disabled = false;
+                btnExportKsplat.disabled = false;
                 
                 // Reset SR controls
                 sliderSR.value = 1;
                 valSR.innerText = "1x";
             } catch (err) {
                 console.error(err);
-                statusDiv.innerText = "Error loading file";
+                statusDiv.innerText = `Error loading ${file.name} (${describeError(err)})`;
             }
+        };
+        document.getElementById('fileInput').addEventListener('change', (event) => {
+            currentFile = event.target.files[0];
+            if (currentFile) loadFile(currentFile);
         });
+        // オプションを変えたら同じファイルを読み直す
+        selQuatOrder.onchange = selValueDomain.onchange = () => {
+            if (currentFile) loadFile(currentFile);
+        };
 
         // Mode Switching
         btnRGB.onclick = () => {
@@ -70,6 +104,14 @@ async function run() {
             btnNormal.classList.add('active');
         };
 
+        // View-dependent color (higher-order SH evaluated from the camera position)
+        let viewDependent = false;
+        btnViewDep.onclick = () => {
+            viewDependent = !viewDependent;
+            viewer.set_view_dependent_color(viewDependent);
+            btnViewDep.classList.toggle('active', viewDependent);
+        };
+
         // Super Resolution Controls
         sliderSR.oninput = () => {
             valSR.innerText = `${sliderSR.value}x`;
@@ -87,25 +129,25 @@ async function run() {
                     statusDiv.innerText = `Displaying Upsampled Geometry (${factor}x)`;
                 } catch(e) {
                     console.error(e);
-                    statusDiv.innerText = "SR Computation Failed";
+                    statusDiv.innerText = `SR Computation Failed (${describeError(e)})`;
                 }
             }, 10);
         };
 
-        // Export Functionality
-        btnExport.onclick = () => {
+        // Export Functionality (binary PLY)
+        const exportFile = (exporter, filename) => {
             if (!window.viewer) return;
-            statusDiv.innerText = "Generating PLY...";
+            statusDiv.innerText = `Generating ${filename}...`;
             
             setTimeout(() => {
                 try {
-                    const data = window.viewer.export_ply();
+                    const data = exporter(window.viewer);
                     const blob = new Blob([data], { type: 'application/octet-stream' });
                     const url = URL.createObjectURL(blob);
                     
                     const a = document.createElement('a');
                     a.href = url;
-                    a.download = "processed_geometry.ply";
+                    a.download = filename;
                     document.body.appendChild(a);
                     a.click();
                     document.body.removeChild(a);
@@ -114,14 +156,26 @@ async function run() {
                     statusDiv.innerText = "Export Complete.";
                 } catch (e) {
                     console.error(e);
-                    statusDiv.innerText = "Export Failed.";
+                    statusDiv.innerText = `Export Failed (${describeError(e)})`;
                 }
             }, 10);
         };
+        // Surfel 点群 (xyz / rgb / normal)
+        btnExport.onclick = () => exportFile(v => v.export_ply(), "processed_geometry.ply");
+        // 3DGS 形式 (学習器互換, 再読み込み可能)
+        btnExportSplat.onclick = () => exportFile(v => v.export_splat_ply(), "splats.ply");
+        // PCL 形式 (binary_compressed)
+        btnExportPcd.onclick = () => exportFile(v => v.export_pcd(), "processed_geometry.pcd");
+        // 圧縮形式 (SPZ: 既定の 12 bit 固定小数点 / antimatter15 / GaussianSplats3D)
+        btnExportSpz.onclick = () => exportFile(v => v.export_spz(), "scene.spz");
+        btnExportDotSplat.onclick = () => exportFile(v => v.export_splat(), "scene.splat");
+        btnExportKsplat.onclick = () => exportFile(v => v.export_ksplat(1), "scene.ksplat");
 
     } catch (e) {
         console.error("Initialization failed:", e);
-        statusDiv.innerText = "WebGPU initialization failed.";
+        statusDiv.innerText = e && e.name === "GpuUnavailableError"
+            ? `No usable GPU: ${e.message}`
+            : `WebGPU initialization failed (${describeError(e)})`;
     }
 }
 